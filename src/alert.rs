@@ -0,0 +1,728 @@
+use chrono::{DateTime, Utc, Weekday};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fmt;
+use std::str::FromStr;
+use tokio::time::{Duration, Instant};
+
+/// What an alert watches. Each type can be chosen by name or by its
+/// number, which is how they were chosen before names existed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlertType {
+    /// The price moved by a set amount from the baseline.
+    Dollar,
+    /// The price moved by a set percentage.
+    Percent,
+    /// The price crossed a target.
+    Target,
+    /// The price moved a set percentage off its session high or low.
+    Trailing,
+    /// The provider's 24h % change passed a threshold.
+    Change24h,
+    /// 24h volume crossed a level.
+    Volume,
+    /// Market cap crossed a level.
+    MarketCap,
+    /// The price stayed in a narrow range.
+    Flat,
+    /// The short moving average crossed the long one.
+    Sma,
+    /// The price left a band.
+    Band,
+    /// The price came within a set percentage of its 24h high or low.
+    Range24h,
+}
+
+impl AlertType {
+    /// Every type, in number order.
+    pub const ALL: [AlertType; 11] = [
+        AlertType::Dollar,
+        AlertType::Percent,
+        AlertType::Target,
+        AlertType::Trailing,
+        AlertType::Change24h,
+        AlertType::Volume,
+        AlertType::MarketCap,
+        AlertType::Flat,
+        AlertType::Sma,
+        AlertType::Band,
+        AlertType::Range24h,
+    ];
+
+    /// The legacy number, from 1.
+    pub fn number(self) -> usize {
+        AlertType::ALL.iter().position(|&t| t == self).unwrap_or_default() + 1
+    }
+
+    /// The name accepted by `--alert-type` and saved in the config.
+    pub fn name(self) -> &'static str {
+        match self {
+            AlertType::Dollar => "dollar",
+            AlertType::Percent => "percent",
+            AlertType::Target => "target",
+            AlertType::Trailing => "trailing",
+            AlertType::Change24h => "change24h",
+            AlertType::Volume => "volume",
+            AlertType::MarketCap => "mcap",
+            AlertType::Flat => "flat",
+            AlertType::Sma => "sma",
+            AlertType::Band => "band",
+            AlertType::Range24h => "range24h",
+        }
+    }
+
+    /// A short description for prompts and summaries.
+    pub fn label(self) -> &'static str {
+        match self {
+            AlertType::Dollar => "price change",
+            AlertType::Percent => "% change",
+            AlertType::Target => "price target",
+            AlertType::Trailing => "trailing stop or buy",
+            AlertType::Change24h => "24h % change",
+            AlertType::Volume => "24h volume",
+            AlertType::MarketCap => "market cap",
+            AlertType::Flat => "flat price",
+            AlertType::Sma => "SMA crossover",
+            AlertType::Band => "price band",
+            AlertType::Range24h => "near the 24h high or low",
+        }
+    }
+}
+
+impl FromStr for AlertType {
+    type Err = String;
+
+    /// Accepts a name (`percent`) or a legacy number (`2`), ignoring case.
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let input = input.trim().to_lowercase();
+        AlertType::ALL
+            .into_iter()
+            .find(|t| t.name() == input || t.number().to_string() == input)
+            .ok_or_else(|| {
+                let names: Vec<&str> = AlertType::ALL.iter().map(|t| t.name()).collect();
+                format!("unknown alert type '{}'; use 1-{} or one of {}", input, AlertType::ALL.len(), names.join(", "))
+            })
+    }
+}
+
+impl fmt::Display for AlertType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Why a threshold alert fired: how far the price moved from its baseline,
+/// in the alert's own unit (currency for `Dollar`, percent for `Percent`).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AlertMessage {
+    pub moved: f64,
+}
+
+impl AlertMessage {
+    pub fn verb(&self) -> &'static str {
+        if self.moved < 0.0 {
+            "dropped"
+        } else {
+            "rose"
+        }
+    }
+}
+
+/// The percent move from `baseline` to `current`, or `None` when the
+/// baseline isn't a positive, finite price to measure from.
+pub fn percent_change(baseline: f64, current: f64) -> Option<f64> {
+    (baseline > 0.0 && baseline.is_finite()).then(|| (current - baseline) / baseline * 100.0)
+}
+
+/// Decides a single-threshold $ or % change alert: whether `current` has
+/// moved at least `threshold` from `baseline`, either way, or with `strict`
+/// (`--strict-threshold`) more than it. A percent move needs a positive
+/// baseline, and a NaN or infinite price never alerts. Other alert types
+/// depend on more than two prices and always return `None`.
+pub fn should_alert(alert_type: AlertType, baseline: f64, current: f64, threshold: f64, strict: bool) -> Option<AlertMessage> {
+    if !baseline.is_finite() || !current.is_finite() {
+        return None;
+    }
+    let moved = match alert_type {
+        AlertType::Dollar => current - baseline,
+        AlertType::Percent => percent_change(baseline, current)?,
+        _ => return None,
+    };
+    meets_threshold(moved, threshold, strict).then_some(AlertMessage { moved })
+}
+
+/// Whether a move of `moved`, either way, reaches `threshold`, or with
+/// `strict` goes past it. Both usually start out as decimals, so this
+/// allows for binary rounding: a 0.05% move from 100 to 100.05 works out
+/// as 0.04999...% in `f64`, which reaches 0.05 but doesn't pass it.
+pub fn meets_threshold(moved: f64, threshold: f64, strict: bool) -> bool {
+    let margin = threshold.abs() * 1e-9;
+    if strict {
+        moved.abs() > threshold + margin
+    } else {
+        moved.abs() >= threshold - margin
+    }
+}
+
+/// Which side of a price target triggers an alert.
+#[derive(Serialize, Deserialize, clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    Above,
+    Below,
+}
+
+impl Direction {
+    /// Whether `price` is at or beyond `target` on this side.
+    pub fn is_breached(self, price: f64, target: f64) -> bool {
+        match self {
+            Direction::Above => price >= target,
+            Direction::Below => price <= target,
+        }
+    }
+
+    /// Like `is_breached`, but a price short of `target` by no more than
+    /// `tolerance` reaches it too, so one that only misses by rounding counts.
+    pub fn reached(self, price: f64, target: f64, tolerance: f64) -> bool {
+        match self {
+            Direction::Above => price >= target - tolerance,
+            Direction::Below => price <= target + tolerance,
+        }
+    }
+}
+
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Direction::Above => write!(f, "above"),
+            Direction::Below => write!(f, "below"),
+        }
+    }
+}
+
+/// Which way a trailing alert follows the price.
+#[derive(Serialize, Deserialize, clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum TrailingMode {
+    /// Alert on a fall of the threshold percent from the session high.
+    #[default]
+    TrailingStop,
+    /// Alert on a rise of the threshold percent from the session low, to
+    /// catch a reversal off a bottom.
+    TrailingBuy,
+}
+
+impl TrailingMode {
+    /// The price that triggers the alert, `percent` away from the session
+    /// high (stop) or low (buy). Both only ratchet towards the current price.
+    pub fn level(self, low: f64, high: f64, percent: f64) -> f64 {
+        match self {
+            TrailingMode::TrailingStop => high * (1.0 - percent / 100.0),
+            TrailingMode::TrailingBuy => low * (1.0 + percent / 100.0),
+        }
+    }
+
+    /// Whether `price` is past `level` on this mode's side.
+    pub fn is_breached(self, price: f64, level: f64) -> bool {
+        match self {
+            TrailingMode::TrailingStop => price < level,
+            TrailingMode::TrailingBuy => price > level,
+        }
+    }
+}
+
+impl fmt::Display for TrailingMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TrailingMode::TrailingStop => write!(f, "trailing-stop"),
+            TrailingMode::TrailingBuy => write!(f, "trailing-buy"),
+        }
+    }
+}
+
+/// What a percent alert measures its change against.
+#[derive(Serialize, Deserialize, clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum PercentBaseline {
+    /// The price when monitoring started.
+    #[default]
+    Start,
+    /// The price on the previous check, to catch sudden single-interval moves.
+    Previous,
+}
+
+/// The days of the week alerts are checked on (`--active-days mon-fri`).
+/// Written as days and ranges separated by commas, such as `mon,wed,fri`
+/// or `fri-mon`; a range may wrap past Sunday.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ActiveDays {
+    /// One bit per day, Monday first.
+    days: u8,
+}
+
+impl ActiveDays {
+    pub fn contains(&self, day: Weekday) -> bool {
+        self.days & (1 << day.num_days_from_monday()) != 0
+    }
+}
+
+impl FromStr for ActiveDays {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let day = |name: &str| {
+            name.trim().parse::<Weekday>().map_err(|_| format!("'{}' is not a day of the week; expected e.g. mon-fri", name.trim()))
+        };
+        let mut days = 0u8;
+        for part in input.split(',').filter(|part| !part.trim().is_empty()) {
+            let (first, last) = match part.split_once('-') {
+                Some((first, last)) => (day(first)?, day(last)?),
+                None => (day(part)?, day(part)?),
+            };
+            let mut current = first;
+            loop {
+                days |= 1 << current.num_days_from_monday();
+                if current == last {
+                    break;
+                }
+                current = current.succ();
+            }
+        }
+        if days == 0 {
+            return Err("expected at least one day, such as mon-fri".to_string());
+        }
+        Ok(ActiveDays { days })
+    }
+}
+
+impl fmt::Display for ActiveDays {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut day = Weekday::Mon;
+        let mut names = Vec::new();
+        for _ in 0..7 {
+            if self.contains(day) {
+                names.push(format!("{:?}", day));
+            }
+            day = day.succ();
+        }
+        write!(f, "{}", names.join(", "))
+    }
+}
+
+/// One of several thresholds layered on a coin (`--threshold 500,1000,2000`).
+/// Each alerts once, the first time the move reaches it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AlertLevel {
+    pub value: f64,
+    pub triggered: bool,
+}
+
+impl AlertLevel {
+    pub fn new(value: f64) -> Self {
+        AlertLevel { value, triggered: false }
+    }
+}
+
+/// Marks every untriggered level that a move of `moved` has reached as
+/// triggered and returns the highest of them, or `None` if none were new.
+/// `strict` is as for `meets_threshold`.
+pub fn trigger_levels(levels: &mut [AlertLevel], moved: f64, strict: bool) -> Option<f64> {
+    let mut reached = None;
+    for level in levels.iter_mut().filter(|level| !level.triggered && meets_threshold(moved, level.value, strict)) {
+        level.triggered = true;
+        reached = Some(reached.map_or(level.value, |r: f64| r.max(level.value)));
+    }
+    reached
+}
+
+/// The last `capacity` prices, oldest first, for statistics over a fixed
+/// number of checks.
+pub struct RollingWindow {
+    capacity: usize,
+    prices: VecDeque<f64>,
+}
+
+impl RollingWindow {
+    pub fn new(capacity: usize) -> Self {
+        RollingWindow { capacity, prices: VecDeque::with_capacity(capacity) }
+    }
+
+    /// Adds a price, dropping the oldest once the window is full.
+    pub fn push(&mut self, price: f64) {
+        if self.prices.len() == self.capacity {
+            self.prices.pop_front();
+        }
+        self.prices.push_back(price);
+    }
+
+    pub fn len(&self) -> usize {
+        self.prices.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.prices.is_empty()
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.prices.len() == self.capacity
+    }
+
+    /// The prices in the window, oldest first.
+    pub fn prices(&self) -> impl Iterator<Item = f64> + '_ {
+        self.prices.iter().copied()
+    }
+
+    /// The mean of the newest `n` prices, if there are that many.
+    pub fn mean_of_last(&self, n: usize) -> Option<f64> {
+        (n > 0 && n <= self.prices.len()).then(|| self.prices.iter().rev().take(n).sum::<f64>() / n as f64)
+    }
+
+    /// The population standard deviation of the window, once it is full.
+    pub fn std_dev(&self) -> Option<f64> {
+        if !self.is_full() {
+            return None;
+        }
+        let mean = self.mean_of_last(self.len())?;
+        let variance = self.prices.iter().map(|p| (p - mean).powi(2)).sum::<f64>() / self.len() as f64;
+        Some(variance.sqrt())
+    }
+}
+
+/// Recent `(time, price)` samples reaching back at least `span`, for
+/// spotting a price that has stayed in a narrow range. Unlike
+/// `RollingWindow` it covers a stretch of time rather than a number of checks.
+pub struct PriceWindow {
+    span: Duration,
+    samples: VecDeque<(Instant, f64)>,
+}
+
+impl PriceWindow {
+    pub fn new(span: Duration) -> Self {
+        PriceWindow { span, samples: VecDeque::new() }
+    }
+
+    /// Adds a sample, dropping ones that are no longer needed to cover the span.
+    pub fn push(&mut self, at: Instant, price: f64) {
+        self.samples.push_back((at, price));
+        while self.samples.len() > 1 && at.duration_since(self.samples[1].0) >= self.span {
+            self.samples.pop_front();
+        }
+    }
+
+    /// The lowest and highest price over the window, once the samples cover
+    /// the whole span.
+    pub fn range(&self) -> Option<(f64, f64)> {
+        let (first, _) = self.samples.front()?;
+        let (last, _) = self.samples.back()?;
+        if last.duration_since(*first) < self.span {
+            return None;
+        }
+        let prices = self.samples.iter().map(|(_, p)| *p);
+        Some(prices.fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), p| (lo.min(p), hi.max(p))))
+    }
+}
+
+/// Which way the short moving average crossed the long one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Crossover {
+    /// Short above long: a golden cross.
+    Golden,
+    /// Short below long: a death cross.
+    Death,
+}
+
+impl fmt::Display for Crossover {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Crossover::Golden => write!(f, "golden cross"),
+            Crossover::Death => write!(f, "death cross"),
+        }
+    }
+}
+
+/// Simple moving averages over the last `short` and `long` checks, watched
+/// for the short one crossing the long one.
+pub struct SmaCrossover {
+    short: usize,
+    long: usize,
+    prices: RollingWindow,
+    /// Whether the short average was above the long one, once both exist.
+    short_above: Option<bool>,
+}
+
+impl SmaCrossover {
+    /// `short` must be smaller than `long`.
+    pub fn new(short: usize, long: usize) -> Self {
+        SmaCrossover { short, long, prices: RollingWindow::new(long), short_above: None }
+    }
+
+    /// Records a price and reports a crossing if the averages just swapped
+    /// sides. Equal averages count as no change.
+    pub fn push(&mut self, price: f64) -> Option<Crossover> {
+        self.prices.push(price);
+        let (short, long) = self.averages()?;
+        if short == long {
+            return None;
+        }
+        let above = short > long;
+        let previous = self.short_above.replace(above)?;
+        match (previous, above) {
+            (false, true) => Some(Crossover::Golden),
+            (true, false) => Some(Crossover::Death),
+            _ => None,
+        }
+    }
+
+    /// The short and long averages, once there are enough prices for the long one.
+    pub fn averages(&self) -> Option<(f64, f64)> {
+        Some((self.prices.mean_of_last(self.short)?, self.prices.mean_of_last(self.long)?))
+    }
+}
+
+/// An exponential moving average, `alpha * price + (1 - alpha) * ema`,
+/// watched for the price crossing it. It reacts to recent prices faster
+/// than an SMA and needs only the one value.
+pub struct Ema {
+    alpha: f64,
+    value: Option<f64>,
+    /// Whether the price was above the average on the last check.
+    price_above: Option<bool>,
+}
+
+impl Ema {
+    /// `alpha`, the weight of the newest price, must be between 0 and 1.
+    pub fn new(alpha: f64) -> Self {
+        Ema { alpha, value: None, price_above: None }
+    }
+
+    /// Folds in a price, starting from it, and reports a crossing if the
+    /// price just moved to the other side of the average: `true` upward,
+    /// `false` downward. A price equal to the average counts as no change.
+    pub fn push(&mut self, price: f64) -> Option<bool> {
+        let ema = self.value.map_or(price, |ema| self.alpha * price + (1.0 - self.alpha) * ema);
+        self.value = Some(ema);
+        if price == ema {
+            return None;
+        }
+        let above = price > ema;
+        let previous = self.price_above.replace(above)?;
+        (previous != above).then_some(above)
+    }
+
+    pub fn value(&self) -> Option<f64> {
+        self.value
+    }
+}
+
+/// One alert that fired, for the end-of-session list.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct AlertEvent {
+    pub at: DateTime<Utc>,
+    pub ticker: String,
+    /// The alert type name, or `volatility` or `ema` for those alerts.
+    pub kind: &'static str,
+    pub price: f64,
+    /// The change from the baseline when it fired.
+    pub change: f64,
+}
+
+/// The most recent `capacity` alerts, oldest first. Older ones are dropped
+/// and counted so a long run's memory stays bounded.
+pub struct AlertHistory {
+    capacity: usize,
+    events: VecDeque<AlertEvent>,
+    dropped: u64,
+}
+
+impl AlertHistory {
+    pub fn new(capacity: usize) -> Self {
+        AlertHistory { capacity, events: VecDeque::new(), dropped: 0 }
+    }
+
+    pub fn push(&mut self, event: AlertEvent) {
+        if self.events.len() == self.capacity {
+            self.dropped += 1;
+            if self.events.pop_front().is_none() {
+                return;
+            }
+        }
+        self.events.push_back(event);
+    }
+
+    pub fn events(&self) -> impl Iterator<Item = &AlertEvent> {
+        self.events.iter()
+    }
+
+    /// How many alerts were dropped to stay within the capacity.
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn active_days_take_lists_and_wrapping_ranges() {
+        let weekdays: ActiveDays = "mon-fri".parse().unwrap();
+        assert!(weekdays.contains(Weekday::Mon) && weekdays.contains(Weekday::Fri));
+        assert!(!weekdays.contains(Weekday::Sat) && !weekdays.contains(Weekday::Sun));
+        let long_weekend: ActiveDays = "fri-mon".parse().unwrap();
+        assert_eq!(long_weekend.to_string(), "Mon, Fri, Sat, Sun");
+        let some: ActiveDays = "Tue, thursday".parse().unwrap();
+        assert_eq!(some.to_string(), "Tue, Thu");
+        assert!("mon-funday".parse::<ActiveDays>().is_err());
+        assert!("".parse::<ActiveDays>().is_err());
+    }
+
+    #[test]
+    fn each_level_triggers_once() {
+        let mut levels = [500.0, 1000.0, 2000.0].map(AlertLevel::new);
+        assert_eq!(trigger_levels(&mut levels, 400.0, false), None);
+        assert_eq!(trigger_levels(&mut levels, 600.0, false), Some(500.0));
+        assert_eq!(trigger_levels(&mut levels, 700.0, false), None);
+        // Jumping past two levels at once reports the higher one.
+        assert_eq!(trigger_levels(&mut levels, 2500.0, false), Some(2000.0));
+        assert!(levels.iter().all(|level| level.triggered));
+        assert_eq!(trigger_levels(&mut levels, 3000.0, false), None);
+    }
+
+    #[test]
+    fn should_alert_fires_at_the_threshold() {
+        let moved = |moved| Some(AlertMessage { moved });
+        assert_eq!(should_alert(AlertType::Dollar, 100.0, 110.0, 10.0, false), moved(10.0));
+        assert_eq!(should_alert(AlertType::Dollar, 100.0, 109.99, 10.0, false), None);
+        assert_eq!(should_alert(AlertType::Percent, 200.0, 210.0, 5.0, false), moved(5.0));
+        assert_eq!(should_alert(AlertType::Percent, 200.0, 209.98, 5.0, false), None);
+    }
+
+    #[test]
+    fn strict_thresholds_need_a_move_past_the_threshold() {
+        let moved = |moved| Some(AlertMessage { moved });
+        assert_eq!(should_alert(AlertType::Dollar, 100.0, 110.0, 10.0, true), None);
+        assert_eq!(should_alert(AlertType::Dollar, 100.0, 110.01, 10.0, true), moved(110.01 - 100.0));
+        assert_eq!(should_alert(AlertType::Percent, 200.0, 190.0, 5.0, true), None);
+        // The rounding margin works against strict mode: 100 to 100.05 is
+        // 0.04999...%, which the default counts as 0.05% and strict doesn't.
+        assert_eq!(should_alert(AlertType::Percent, 100.0, 100.05, 0.05, true), None);
+        assert!(should_alert(AlertType::Percent, 100.0, 100.05, 0.05, false).is_some());
+        let mut levels = [500.0, 1000.0].map(AlertLevel::new);
+        assert_eq!(trigger_levels(&mut levels, 500.0, true), None);
+        assert_eq!(trigger_levels(&mut levels, 1000.0, true), Some(500.0));
+    }
+
+    #[test]
+    fn targets_are_reached_within_the_tolerance() {
+        let above = |price| Direction::Above.reached(price, 70000.0, 0.005);
+        assert!(above(71500.0));
+        assert!(above(70000.0));
+        assert!(above(69999.996));
+        assert!(!above(69999.99));
+        let below = |price| Direction::Below.reached(price, 0.9950, 0.00005);
+        assert!(below(0.9871));
+        assert!(below(0.99504));
+        assert!(!below(0.996));
+    }
+
+    #[test]
+    fn should_alert_fires_on_drops() {
+        let alert = should_alert(AlertType::Dollar, 100.0, 85.0, 10.0, false).unwrap();
+        assert_eq!((alert.moved, alert.verb()), (-15.0, "dropped"));
+        assert_eq!(should_alert(AlertType::Percent, 200.0, 190.0, 5.0, false), Some(AlertMessage { moved: -5.0 }));
+        assert_eq!(should_alert(AlertType::Percent, 200.0, 191.0, 5.0, false), None);
+    }
+
+    #[test]
+    fn should_alert_guards_zero_baselines_and_nan() {
+        assert_eq!(percent_change(0.0, 10.0), None);
+        assert_eq!(percent_change(-5.0, 10.0), None);
+        assert_eq!(should_alert(AlertType::Percent, 0.0, 10.0, 5.0, false), None);
+        assert_eq!(should_alert(AlertType::Dollar, 0.0, 10.0, 5.0, false), Some(AlertMessage { moved: 10.0 }));
+        assert_eq!(should_alert(AlertType::Dollar, 100.0, f64::NAN, 5.0, false), None);
+        assert_eq!(should_alert(AlertType::Percent, f64::NAN, 100.0, 5.0, false), None);
+        assert_eq!(should_alert(AlertType::Percent, 100.0, f64::INFINITY, 5.0, false), None);
+        assert_eq!(should_alert(AlertType::Target, 100.0, 200.0, 5.0, false), None);
+    }
+
+    #[test]
+    fn alert_types_parse_by_name_or_number() {
+        assert_eq!("percent".parse(), Ok(AlertType::Percent));
+        assert_eq!(" Trailing ".parse(), Ok(AlertType::Trailing));
+        assert_eq!("2".parse(), Ok(AlertType::Percent));
+        assert_eq!("10".parse(), Ok(AlertType::Band));
+        assert_eq!("11".parse(), Ok(AlertType::Range24h));
+        assert!("12".parse::<AlertType>().is_err());
+        for alert_type in AlertType::ALL {
+            assert_eq!(alert_type.name().parse(), Ok(alert_type));
+        }
+    }
+
+    #[test]
+    fn rolling_window_keeps_the_newest_prices() {
+        let mut window = RollingWindow::new(4);
+        for price in [2.0, 4.0, 4.0, 4.0] {
+            window.push(price);
+        }
+        assert_eq!(window.std_dev(), Some(0.8660254037844386));
+        window.push(6.0);
+        // 2.0 has dropped out: [4, 4, 4, 6].
+        assert_eq!(window.mean_of_last(4), Some(4.5));
+        assert_eq!(window.mean_of_last(1), Some(6.0));
+        assert_eq!(window.mean_of_last(5), None);
+        assert!(RollingWindow::new(3).std_dev().is_none());
+    }
+
+    #[test]
+    fn ema_reports_the_price_crossing_it() {
+        let mut ema = Ema::new(0.5);
+        let crosses: Vec<_> = [100.0, 110.0, 120.0, 100.0, 90.0, 110.0].iter().map(|&p| ema.push(p)).collect();
+        // The first price seeds the average; the second only sets the starting side.
+        assert_eq!(crosses, vec![None, None, None, Some(false), None, Some(true)]);
+        assert_eq!(ema.value(), Some(104.0625));
+    }
+
+    #[test]
+    fn sma_crossover_fires_only_when_the_averages_swap() {
+        let mut sma = SmaCrossover::new(2, 4);
+        let crosses: Vec<_> = [10.0, 10.0, 9.0, 8.0, 8.0, 12.0, 14.0, 14.0, 9.0, 7.0]
+            .iter()
+            .map(|&p| sma.push(p))
+            .collect();
+        // The first full window (short 8.5 < long 9.25) only sets the starting side.
+        assert_eq!(
+            crosses,
+            vec![None, None, None, None, None, Some(Crossover::Golden), None, None, Some(Crossover::Death), None]
+        );
+    }
+
+    #[test]
+    fn alert_history_keeps_the_newest_alerts() {
+        let event = |price| AlertEvent { at: Utc::now(), ticker: "bitcoin".to_string(), kind: "dollar", price, change: 0.0 };
+        let mut history = AlertHistory::new(2);
+        for price in [1.0, 2.0, 3.0] {
+            history.push(event(price));
+        }
+        let prices: Vec<f64> = history.events().map(|e| e.price).collect();
+        assert_eq!(prices, [2.0, 3.0]);
+        assert_eq!(history.dropped(), 1);
+        let mut none = AlertHistory::new(0);
+        none.push(event(1.0));
+        assert_eq!((none.events().count(), none.dropped()), (0, 1));
+    }
+
+    #[test]
+    fn price_window_needs_a_full_span() {
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+        let mut window = PriceWindow::new(Duration::from_secs(60));
+        window.push(at(0), 100.0);
+        window.push(at(30), 101.0);
+        assert_eq!(window.range(), None);
+        window.push(at(60), 100.5);
+        assert_eq!(window.range(), Some((100.0, 101.0)));
+        // Once 30s..90s covers the span, the sample at 0s drops out.
+        window.push(at(90), 100.8);
+        assert_eq!(window.range(), Some((100.5, 101.0)));
+    }
+}
@@ -0,0 +1,581 @@
+use crate::coins::Coin;
+use crate::rate_limit::RateLimiter;
+use chrono::{DateTime, NaiveDate, Utc};
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::{Client, Proxy, RequestBuilder, Response, StatusCode};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::error::Category;
+use std::collections::HashMap;
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+
+const PUBLIC_API_BASE: &str = "https://api.coingecko.com/api/v3";
+const PRO_API_BASE: &str = "https://pro-api.coingecko.com/api/v3";
+
+/// Set when requests go through a proxy, so connection failures can be
+/// blamed on the proxy rather than the API.
+static PROXY_IN_USE: AtomicBool = AtomicBool::new(false);
+
+/// CoinGecko plan an API key belongs to.
+///
+/// Demo keys (free tier) are sent as `x-cg-demo-api-key` to the public host,
+/// api.coingecko.com. Pro keys (paid plans) are sent as `x-cg-pro-api-key`
+/// and only work against pro-api.coingecko.com.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApiTier {
+    Demo,
+    Pro,
+}
+
+#[derive(Debug, Clone)]
+pub struct ApiKey {
+    pub tier: ApiTier,
+    pub key: String,
+}
+
+/// CoinGecko API client: the shared HTTP client plus the host and key
+/// every request should use. Clones share one rate limiter.
+#[derive(Clone)]
+pub struct CoinGecko {
+    client: Client,
+    base: String,
+    api_key: Option<ApiKey>,
+    limiter: Arc<RateLimiter>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CoinGeckoPrice {
+    /// Price in the primary quote currency, the first one requested.
+    pub price: f64,
+    /// Prices in the other requested currencies, keyed by currency code.
+    pub other_prices: HashMap<String, f64>,
+    /// Percent change over the last 24 hours (`usd_24h_change` for a USD
+    /// request), when CoinGecko has one.
+    pub change_24h: Option<f64>,
+    /// Trading volume over the last 24 hours, in the primary currency.
+    pub volume_24h: Option<f64>,
+    /// Market capitalization in the primary currency.
+    pub market_cap: Option<f64>,
+    /// When CoinGecko last updated the price.
+    pub last_updated_at: Option<DateTime<Utc>>,
+}
+
+/// A coin's entry in `/coins/markets`: its display details and 24h range.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct CoinInfo {
+    pub id: String,
+    pub symbol: String,
+    pub name: String,
+    /// Unset for coins too small to be ranked.
+    pub market_cap_rank: Option<u32>,
+    /// The highest price over the last 24 hours, in the requested currency.
+    pub high_24h: Option<f64>,
+    pub low_24h: Option<f64>,
+}
+
+/// An NFT collection's floor price from `/nfts/{id}`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NftFloor {
+    /// The floor in the collection's own currency, such as ether.
+    pub native: f64,
+    /// The CoinGecko id of that currency, e.g. `ethereum`.
+    pub native_currency: String,
+    /// The floor converted to US dollars, when CoinGecko has it.
+    pub usd: Option<f64>,
+    /// Percent change of the dollar floor over the last 24 hours.
+    pub change_24h: Option<f64>,
+    /// Trading volume over the last 24 hours, in US dollars.
+    pub volume_24h: Option<f64>,
+    /// Market capitalization in US dollars.
+    pub market_cap: Option<f64>,
+}
+
+#[derive(Error, Debug)]
+pub enum FetchError {
+    #[error("Request error: {0}")]
+    Reqwest(reqwest::Error),
+    #[error("Request timed out")]
+    Timeout,
+    #[error("Could not connect through the proxy: {0}")]
+    Proxy(reqwest::Error),
+    #[error("IO error: {0}")]
+    Io(#[from] io::Error),
+    #[error("Failed to parse price")]
+    ParseError,
+    /// The body wasn't JSON at all, typically an HTML error page served
+    /// with a 200 while the API is overloaded.
+    #[error("The API sent back something other than JSON; it may be overloaded")]
+    InvalidResponseBody,
+    #[error("Price temporarily unavailable")]
+    PriceMissing,
+    #[error("No price data for '{0}'")]
+    UnknownCoin(String),
+    #[error("Not found (HTTP 404); check the coin id, platform or contract address")]
+    NotFound,
+    #[error("Rate limited by the API (HTTP 429){}", retry_after_hint(.retry_after))]
+    RateLimited { retry_after: Option<Duration> },
+    #[error("Server error (HTTP {0}); the API may be down")]
+    ServerError(StatusCode),
+}
+
+fn retry_after_hint(retry_after: &Option<Duration>) -> String {
+    retry_after.map(|wait| format!("; retry after {}s", wait.as_secs())).unwrap_or_default()
+}
+
+impl FetchError {
+    /// Whether trying again could plausibly succeed: timeouts, connection
+    /// failures, rate limiting (429) and server errors (5xx). Anything else,
+    /// such as a 404 for a bad coin id, is treated as permanent.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            // Statuses worth retrying have their own variants.
+            FetchError::Reqwest(e) => e.status().is_none() && (e.is_timeout() || e.is_connect() || e.is_request()),
+            FetchError::Timeout
+            | FetchError::Proxy(_)
+            | FetchError::Io(_)
+            | FetchError::RateLimited { .. }
+            | FetchError::ServerError(_)
+            | FetchError::InvalidResponseBody => true,
+            FetchError::ParseError | FetchError::PriceMissing | FetchError::UnknownCoin(_) | FetchError::NotFound => {
+                false
+            }
+        }
+    }
+
+    /// How long a rate-limited API asked us to wait, from its `Retry-After` header.
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            FetchError::RateLimited { retry_after } => *retry_after,
+            _ => None,
+        }
+    }
+}
+
+/// Passes a successful response through and turns a failed one into the
+/// `FetchError` for its status, so a bad coin, rate limiting and an outage
+/// each get their own message and retry treatment.
+pub fn check_status(response: Response) -> Result<Response, FetchError> {
+    let status = response.status();
+    if status == StatusCode::NOT_FOUND {
+        return Err(FetchError::NotFound);
+    }
+    if status == StatusCode::TOO_MANY_REQUESTS {
+        return Err(FetchError::RateLimited { retry_after: retry_after_header(response.headers()) });
+    }
+    if status.is_server_error() {
+        return Err(FetchError::ServerError(status));
+    }
+    Ok(response.error_for_status()?)
+}
+
+/// The wait a response's `Retry-After` header asks for, if it has a readable one.
+fn retry_after_header(headers: &HeaderMap) -> Option<Duration> {
+    headers.get(RETRY_AFTER).and_then(|value| value.to_str().ok()).and_then(parse_retry_after)
+}
+
+/// Parses a `Retry-After` value, which is either a number of seconds or an
+/// HTTP date. A date in the past means no wait.
+fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let at = DateTime::parse_from_rfc2822(value).ok()?;
+    Some((at.with_timezone(&Utc) - Utc::now()).to_std().unwrap_or_default())
+}
+
+/// Reads a successful response's body as a `T`.
+pub async fn read_json<T: DeserializeOwned>(response: Response) -> Result<T, FetchError> {
+    parse_body(&response.text().await?)
+}
+
+/// Parses a response body. One that isn't JSON at all, such as an HTML
+/// error page, is an `InvalidResponseBody` and worth retrying; JSON of the
+/// wrong shape is a `ParseError`.
+pub fn parse_body<T: DeserializeOwned>(body: &str) -> Result<T, FetchError> {
+    serde_json::from_str(body).map_err(|e| match e.classify() {
+        Category::Data => FetchError::ParseError,
+        _ => {
+            let snippet: String = body.split_whitespace().collect::<Vec<_>>().join(" ").chars().take(120).collect();
+            tracing::debug!("Response body is not JSON: {}", snippet);
+            FetchError::InvalidResponseBody
+        }
+    })
+}
+
+impl From<reqwest::Error> for FetchError {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_timeout() {
+            FetchError::Timeout
+        } else if e.is_connect() && PROXY_IN_USE.load(Ordering::Relaxed) {
+            // Through a proxy, the only connection we make is to the proxy.
+            FetchError::Proxy(e)
+        } else {
+            FetchError::Reqwest(e)
+        }
+    }
+}
+
+/// Builds the HTTP client shared by every request, so a hung server fails
+/// after `timeout` instead of stalling the monitor. `proxy` may be an
+/// `http://`, `https://` or `socks5://` URL; without one, reqwest's usual
+/// `HTTPS_PROXY`/`ALL_PROXY` environment variables apply.
+pub fn build_client(timeout: Duration, proxy: Option<&str>) -> Result<Client, reqwest::Error> {
+    let mut builder = Client::builder().timeout(timeout);
+    let env_proxy = ["HTTPS_PROXY", "https_proxy", "ALL_PROXY", "all_proxy"]
+        .iter()
+        .any(|var| std::env::var_os(var).is_some_and(|v| !v.is_empty()));
+    if let Some(url) = proxy {
+        builder = builder.proxy(Proxy::all(url)?);
+    }
+    PROXY_IN_USE.store(proxy.is_some() || env_proxy, Ordering::Relaxed);
+    builder.build()
+}
+
+impl CoinGecko {
+    /// Without a key this talks to the public host exactly as an anonymous user.
+    /// Requests are kept at least `min_gap` apart.
+    pub fn new(client: Client, api_key: Option<ApiKey>, min_gap: Duration) -> Self {
+        let base = match &api_key {
+            Some(ApiKey { tier: ApiTier::Pro, .. }) => PRO_API_BASE,
+            _ => PUBLIC_API_BASE,
+        };
+        CoinGecko { client, base: base.to_string(), api_key, limiter: Arc::new(RateLimiter::new(min_gap)) }
+    }
+
+    /// Sends every request to `base_url` (a mock server or a mirror, such
+    /// as `http://localhost:8080/api/v3`) instead of CoinGecko's own host.
+    pub fn with_base_url(mut self, base_url: Option<String>) -> Self {
+        if let Some(base_url) = base_url {
+            self.base = base_url.trim_end_matches('/').to_string();
+        }
+        self
+    }
+
+    fn get(&self, path: &str) -> RequestBuilder {
+        let request = self.client.get(format!("{}{}", self.base, path));
+        match &self.api_key {
+            Some(ApiKey { tier: ApiTier::Demo, key }) => request.header("x-cg-demo-api-key", key),
+            Some(ApiKey { tier: ApiTier::Pro, key }) => request.header("x-cg-pro-api-key", key),
+            None => request,
+        }
+    }
+
+    /// Fetches prices for all `tickers` in every one of `vs_currencies` in a
+    /// single request; the first currency is the primary one. Coins missing from
+    /// the response (unknown or delisted ids) are left out of the returned map
+    /// rather than failing the whole batch.
+    pub async fn fetch_prices(
+        &self,
+        tickers: &[String],
+        vs_currencies: &[String],
+    ) -> Result<HashMap<String, Result<CoinGeckoPrice, FetchError>>, FetchError> {
+        let path = format!(
+            "/simple/price?ids={}&vs_currencies={}&include_24hr_change=true&include_24hr_vol=true&include_market_cap=true&include_last_updated_at=true",
+            tickers.join(","),
+            vs_currencies.join(",")
+        );
+        let response = self.get_json(&path).await?;
+        parse_prices(&response, tickers, vs_currencies)
+    }
+
+    /// Fetches the price of a token by contract address on `platform` (an
+    /// asset platform id such as `ethereum` or `polygon-pos`). The result is
+    /// keyed by the address as given, which should be lowercase since that
+    /// is how CoinGecko echoes it back.
+    pub async fn fetch_token_price(
+        &self,
+        platform: &str,
+        address: &str,
+        vs_currencies: &[String],
+    ) -> Result<Result<CoinGeckoPrice, FetchError>, FetchError> {
+        let path = format!(
+            "/simple/token_price/{}?contract_addresses={}&vs_currencies={}&include_24hr_change=true&include_24hr_vol=true&include_market_cap=true&include_last_updated_at=true",
+            platform,
+            address,
+            vs_currencies.join(",")
+        );
+        let response = self.get_json(&path).await?;
+        let mut prices = parse_prices(&response, &[address.to_string()], vs_currencies)?;
+        Ok(prices.remove(address).unwrap_or_else(|| Err(FetchError::UnknownCoin(address.to_string()))))
+    }
+
+    /// Fetches every coin CoinGecko knows about (id, symbol and name).
+    pub async fn fetch_coin_list(&self) -> Result<Vec<Coin>, FetchError> {
+        let _slot = self.limiter.wait().await;
+        let response = self.get("/coins/list").send().await?;
+        // The body runs to megabytes, so only its request is logged.
+        tracing::debug!("GET {} -> {}", response.url(), response.status());
+        read_json(check_status(response)?).await
+    }
+
+    /// Fetches the name, symbol, market-cap rank and 24h range of each of
+    /// `ids` in one request. Unknown ids are simply missing from the result.
+    pub async fn fetch_coin_info(&self, ids: &[String], vs_currency: &str) -> Result<Vec<CoinInfo>, FetchError> {
+        let path = format!("/coins/markets?vs_currency={}&ids={}&per_page=250", vs_currency, ids.join(","));
+        let response = self.get_json(&path).await?;
+        serde_json::from_value(response).map_err(|_| FetchError::ParseError)
+    }
+
+    /// Fetches `id`'s price on `date`, from CoinGecko's daily snapshot at
+    /// 00:00 UTC.
+    pub async fn fetch_historical_price(&self, id: &str, date: NaiveDate, vs_currency: &str) -> Result<f64, FetchError> {
+        // Unlike everywhere else, the history endpoint takes dd-mm-yyyy.
+        let path = format!("/coins/{}/history?date={}&localization=false", id, date.format("%d-%m-%Y"));
+        let response = self.get_json(&path).await?;
+        parse_historical_price(&response, vs_currency)
+            .ok_or_else(|| FetchError::UnknownCoin(format!("{} on {}", id, date)))
+    }
+
+    /// Fetches `id`'s prices over the last `days`, oldest first, from
+    /// `/coins/{id}/market_chart`. CoinGecko picks the spacing: about five
+    /// minutes for a day, hourly up to 90 days.
+    pub async fn fetch_market_chart(
+        &self,
+        id: &str,
+        vs_currency: &str,
+        days: u32,
+    ) -> Result<Vec<(DateTime<Utc>, f64)>, FetchError> {
+        let path = format!("/coins/{}/market_chart?vs_currency={}&days={}", id, vs_currency, days);
+        let response = self.get_json(&path).await?;
+        parse_market_chart(&response).ok_or(FetchError::ParseError)
+    }
+
+    /// Fetches the floor price of the NFT collection `id`. An unknown id gets
+    /// a 404, and a collection with no listings comes back without a floor.
+    pub async fn fetch_nft_floor(&self, id: &str) -> Result<NftFloor, FetchError> {
+        let response = self.get_json(&format!("/nfts/{}", id)).await?;
+        parse_nft_floor(&response).ok_or(FetchError::PriceMissing)
+    }
+
+    /// Fetches every NFT collection CoinGecko tracks, a page of 250 at a
+    /// time; the id, symbol and name are read into a `Coin` each.
+    pub async fn fetch_nft_list(&self) -> Result<Vec<Coin>, FetchError> {
+        const PER_PAGE: usize = 250;
+        let mut collections = Vec::new();
+        for page in 1.. {
+            let response = self.get_json(&format!("/nfts/list?per_page={}&page={}", PER_PAGE, page)).await?;
+            let batch: Vec<Coin> = serde_json::from_value(response).map_err(|_| FetchError::ParseError)?;
+            let last = batch.len() < PER_PAGE;
+            collections.extend(batch);
+            if last {
+                break;
+            }
+        }
+        Ok(collections)
+    }
+
+    /// The quote currency codes `/simple/price` accepts, in lower case:
+    /// fiat such as `usd` and `chf`, and denominations such as `btc` and `sats`.
+    pub async fn fetch_supported_currencies(&self) -> Result<Vec<String>, FetchError> {
+        let response = self.get_json("/simple/supported_vs_currencies").await?;
+        serde_json::from_value(response).map_err(|_| FetchError::ParseError)
+    }
+
+    /// Checks that the API can be reached at all, returning its greeting,
+    /// which names the API version: `(V3) To the Moon!`.
+    pub async fn ping(&self) -> Result<String, FetchError> {
+        let response = self.get_json("/ping").await?;
+        response.get("gecko_says").and_then(serde_json::Value::as_str).map(str::to_string).ok_or(FetchError::ParseError)
+    }
+
+    /// Sends a rate-limited GET for `path` and parses the body as JSON,
+    /// logging the URL and the raw body at debug level (`--verbose`).
+    async fn get_json(&self, path: &str) -> Result<serde_json::Value, FetchError> {
+        let _slot = self.limiter.wait().await;
+        let response = self.get(path).send().await?;
+        tracing::debug!("GET {} -> {}", response.url(), response.status());
+        let body = check_status(response)?.text().await?;
+        tracing::debug!("Response body: {}", body);
+        parse_body(&body)
+    }
+}
+
+/// Extracts each ticker's price from a `simple/price` response body.
+///
+/// A coin that is missing from the body entirely is unknown to CoinGecko
+/// (or delisted). A coin that is present without a price, e.g.
+/// `{"bitcoin":{}}` during maintenance, is only temporarily unavailable.
+/// Only the first of `vs_currencies` has to be present.
+pub fn parse_prices(
+    response: &serde_json::Value,
+    tickers: &[String],
+    vs_currencies: &[String],
+) -> Result<HashMap<String, Result<CoinGeckoPrice, FetchError>>, FetchError> {
+    let (vs_currency, others) = vs_currencies.split_first().ok_or(FetchError::ParseError)?;
+    if !response.is_object() {
+        return Err(FetchError::ParseError);
+    }
+
+    let mut prices = HashMap::new();
+    for ticker in tickers {
+        let price = match response.get(ticker) {
+            None => Err(FetchError::UnknownCoin(ticker.clone())),
+            Some(coin) => coin
+                .get(vs_currency)
+                .and_then(|p| p.as_f64())
+                .map(|price| {
+                    let field = |suffix: &str| coin.get(format!("{}_{}", vs_currency, suffix)).and_then(|v| v.as_f64());
+                    let other_prices = others
+                        .iter()
+                        .filter_map(|currency| Some((currency.clone(), coin.get(currency)?.as_f64()?)))
+                        .collect();
+                    CoinGeckoPrice {
+                        price,
+                        other_prices,
+                        change_24h: field("24h_change"),
+                        volume_24h: field("24h_vol"),
+                        market_cap: field("market_cap"),
+                        last_updated_at: coin
+                            .get("last_updated_at")
+                            .and_then(|v| v.as_i64())
+                            .and_then(|secs| DateTime::from_timestamp(secs, 0)),
+                    }
+                })
+                .ok_or(FetchError::PriceMissing),
+        };
+        prices.insert(ticker.clone(), price);
+    }
+
+    Ok(prices)
+}
+
+/// The price in a `/coins/{id}/history` response. A coin that wasn't
+/// trading yet on the date comes back without `market_data`.
+pub fn parse_historical_price(response: &serde_json::Value, vs_currency: &str) -> Option<f64> {
+    response.get("market_data")?.get("current_price")?.get(vs_currency)?.as_f64()
+}
+
+/// The `[milliseconds, price]` pairs of a `market_chart` response's
+/// `prices`, sorted oldest first. Malformed pairs are skipped.
+pub fn parse_market_chart(response: &serde_json::Value) -> Option<Vec<(DateTime<Utc>, f64)>> {
+    let mut prices: Vec<(DateTime<Utc>, f64)> = response
+        .get("prices")?
+        .as_array()?
+        .iter()
+        .filter_map(|point| {
+            let at = DateTime::from_timestamp_millis(point.get(0)?.as_f64()? as i64)?;
+            Some((at, point.get(1)?.as_f64()?))
+        })
+        .collect();
+    prices.sort_by_key(|(at, _)| *at);
+    Some(prices)
+}
+
+/// The floor price in an `/nfts/{id}` response, which keeps it under
+/// `floor_price` by `native_currency` and `usd` rather than by coin.
+pub fn parse_nft_floor(response: &serde_json::Value) -> Option<NftFloor> {
+    let floor = response.get("floor_price")?;
+    let usd_field = |key: &str| response.get(key)?.get("usd")?.as_f64();
+    Some(NftFloor {
+        native: floor.get("native_currency")?.as_f64()?,
+        native_currency: response.get("native_currency")?.as_str()?.to_string(),
+        usd: floor.get("usd").and_then(|v| v.as_f64()),
+        change_24h: response.get("floor_price_in_usd_24h_percentage_change").and_then(|v| v.as_f64()),
+        volume_24h: usd_field("volume_24h"),
+        market_cap: usd_field("market_cap"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn retry_after_accepts_seconds_or_a_date() {
+        assert_eq!(parse_retry_after("30"), Some(Duration::from_secs(30)));
+        assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"), Some(Duration::ZERO));
+        assert_eq!(parse_retry_after("soon"), None);
+        let in_a_minute = (Utc::now() + chrono::Duration::seconds(60)).to_rfc2822();
+        let wait = parse_retry_after(&in_a_minute).unwrap();
+        assert!(wait > Duration::from_secs(55) && wait <= Duration::from_secs(60));
+        let mut headers = HeaderMap::new();
+        assert_eq!(retry_after_header(&headers), None);
+        headers.insert(RETRY_AFTER, "120".parse().unwrap());
+        assert_eq!(retry_after_header(&headers), Some(Duration::from_secs(120)));
+        let error = FetchError::RateLimited { retry_after: Some(Duration::from_secs(30)) };
+        assert_eq!(error.to_string(), "Rate limited by the API (HTTP 429); retry after 30s");
+        assert!(error.is_retryable());
+        assert!(!FetchError::NotFound.is_retryable());
+    }
+
+    #[test]
+    fn parse_prices_tells_missing_prices_from_unknown_coins() {
+        let response = json!({
+            "bitcoin": {
+                "eur": 61000.5,
+                "eur_24h_change": -1.25,
+                "eur_market_cap": 1.2e12,
+                "usd": 66000.0,
+                "last_updated_at": 1714564800
+            },
+            "ethereum": {}
+        });
+        let tickers = vec!["bitcoin".to_string(), "ethereum".to_string(), "cardano".to_string()];
+        let currencies = ["eur".to_string(), "usd".to_string(), "jpy".to_string()];
+        let prices = parse_prices(&response, &tickers, &currencies).unwrap();
+        let bitcoin = prices["bitcoin"].as_ref().unwrap();
+        assert_eq!(bitcoin.price, 61000.5);
+        assert_eq!(bitcoin.other_prices, HashMap::from([("usd".to_string(), 66000.0)]));
+        assert_eq!(bitcoin.change_24h, Some(-1.25));
+        assert_eq!(bitcoin.market_cap, Some(1.2e12));
+        assert_eq!(bitcoin.volume_24h, None);
+        assert_eq!(bitcoin.last_updated_at.map(|at| at.to_rfc3339()).as_deref(), Some("2024-05-01T12:00:00+00:00"));
+        assert!(matches!(prices["ethereum"], Err(FetchError::PriceMissing)));
+        assert!(matches!(prices["cardano"], Err(FetchError::UnknownCoin(_))));
+    }
+
+    #[test]
+    fn historical_prices_need_market_data() {
+        let response = json!({"id": "bitcoin", "market_data": {"current_price": {"usd": 42280.2, "eur": 38700.0}}});
+        assert_eq!(parse_historical_price(&response, "usd"), Some(42280.2));
+        assert_eq!(parse_historical_price(&response, "jpy"), None);
+        assert_eq!(parse_historical_price(&json!({"id": "bitcoin", "name": "Bitcoin"}), "usd"), None);
+    }
+
+    #[test]
+    fn market_charts_are_sorted_and_skip_bad_points() {
+        let response = json!({"prices": [[1714564800000u64, 64010.5], [1714564500000u64, 64000.0], [1714565100000u64, null]]});
+        let prices = parse_market_chart(&response).unwrap();
+        assert_eq!(prices.iter().map(|(_, price)| *price).collect::<Vec<_>>(), [64000.0, 64010.5]);
+        assert_eq!(prices[0].0.timestamp(), 1714564500);
+        assert_eq!(parse_market_chart(&json!({"error": "coin not found"})), None);
+    }
+
+    #[test]
+    fn nft_floors_are_read_from_their_own_schema() {
+        let response = json!({
+            "id": "pudgy-penguins",
+            "native_currency": "ethereum",
+            "native_currency_symbol": "ETH",
+            "floor_price": {"native_currency": 10.5, "usd": 33600.0},
+            "market_cap": {"native_currency": 93000.0, "usd": 297600000.0},
+            "volume_24h": {"native_currency": 420.0, "usd": 1344000.0},
+            "floor_price_in_usd_24h_percentage_change": -2.5
+        });
+        let floor = parse_nft_floor(&response).unwrap();
+        assert_eq!((floor.native, floor.native_currency.as_str(), floor.usd), (10.5, "ethereum", Some(33600.0)));
+        assert_eq!((floor.change_24h, floor.volume_24h, floor.market_cap), (Some(-2.5), Some(1344000.0), Some(297600000.0)));
+        assert_eq!(parse_nft_floor(&json!({"id": "pudgy-penguins", "native_currency": "ethereum", "floor_price": {}})), None);
+    }
+
+    #[test]
+    fn html_error_pages_are_retried_but_bad_json_is_not() {
+        let page = parse_body::<serde_json::Value>("<!DOCTYPE html>\n<html><body>Service Unavailable</body></html>");
+        assert!(matches!(page, Err(FetchError::InvalidResponseBody)));
+        assert!(page.unwrap_err().is_retryable());
+        assert!(matches!(parse_body::<serde_json::Value>(""), Err(FetchError::InvalidResponseBody)));
+        assert!(matches!(parse_body::<Vec<Coin>>(r#"{"bitcoin": {}}"#), Err(FetchError::ParseError)));
+    }
+
+    #[test]
+    fn parse_prices_rejects_non_object_bodies() {
+        let tickers = vec!["bitcoin".to_string()];
+        assert!(matches!(parse_prices(&json!([1, 2]), &tickers, &["usd".to_string()]), Err(FetchError::ParseError)));
+    }
+}
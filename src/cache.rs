@@ -0,0 +1,62 @@
+use chrono::{DateTime, Utc};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// What a cache file holds: the value and when it was written.
+#[derive(Serialize, Deserialize)]
+struct Entry<T> {
+    saved_at: DateTime<Utc>,
+    value: T,
+}
+
+/// A value cached as JSON in a file, valid for `ttl` after it was written.
+pub struct Cache {
+    path: PathBuf,
+    ttl: Duration,
+}
+
+impl Cache {
+    pub fn new(path: PathBuf, ttl: Duration) -> Self {
+        Cache { path, ttl }
+    }
+
+    /// The cached value, or `None` if the file is missing, unreadable,
+    /// in an older format or past its TTL.
+    pub fn get<T: DeserializeOwned>(&self) -> Option<T> {
+        let contents = fs::read_to_string(&self.path).ok()?;
+        let entry: Entry<T> = serde_json::from_str(&contents).ok()?;
+        let age = (Utc::now() - entry.saved_at).to_std().unwrap_or_default();
+        (age < self.ttl).then_some(entry.value)
+    }
+
+    pub fn put<T: Serialize>(&self, value: &T) -> io::Result<()> {
+        let entry = Entry { saved_at: Utc::now(), value };
+        fs::write(&self.path, serde_json::to_string(&entry)?)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entries_expire_after_the_ttl() {
+        let path = std::env::temp_dir().join(format!("crypto_alerts_cache_test_{}.json", std::process::id()));
+        let fresh = Cache::new(path.clone(), Duration::from_secs(60));
+        fresh.put(&vec![1, 2, 3]).unwrap();
+        assert_eq!(fresh.get::<Vec<i32>>(), Some(vec![1, 2, 3]));
+        assert_eq!(Cache::new(path.clone(), Duration::ZERO).get::<Vec<i32>>(), None);
+        // A file in another shape reads as empty rather than failing.
+        fs::write(&path, "[1, 2, 3]").unwrap();
+        assert_eq!(fresh.get::<Vec<i32>>(), None);
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,205 @@
+use crate::api::CoinGecko;
+use crate::cache::Cache;
+use crate::config;
+use crate::output::log_warn;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// An entry from CoinGecko's `/coins/list`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Coin {
+    pub id: String,
+    pub symbol: String,
+    pub name: String,
+}
+
+/// Well-known coins that resolve directly, without going through the coin
+/// list: each CoinGecko id with its other aliases. Many symbols are shared
+/// by dozens of tokens (there are plenty of coins calling themselves
+/// "btc"), so the big names are pinned here. The id always resolves to
+/// itself and needn't be listed as an alias.
+const PREFERRED: &[(&str, &[&str])] = &[("bitcoin", &["btc"]), ("ethereum", &["eth"]), ("cardano", &["ada"])];
+
+/// `PREFERRED` as a lookup from every pinned name, ids included, to its id.
+fn preferred_lookup() -> HashMap<&'static str, &'static str> {
+    PREFERRED
+        .iter()
+        .flat_map(|&(id, aliases)| std::iter::once(id).chain(aliases.iter().copied()).map(move |name| (name, id)))
+        .collect()
+}
+
+/// What a user-supplied ticker resolved to.
+pub enum Lookup<'a> {
+    Found(&'a str),
+    Ambiguous(Vec<&'a Coin>),
+    Unknown,
+}
+
+/// Symbol and id lookup over the known coins.
+pub struct CoinList {
+    coins: Vec<Coin>,
+    preferred: HashMap<&'static str, &'static str>,
+    by_symbol: HashMap<String, Vec<usize>>,
+    by_id: HashMap<String, usize>,
+}
+
+impl CoinList {
+    pub fn new(coins: Vec<Coin>) -> Self {
+        let mut by_symbol: HashMap<String, Vec<usize>> = HashMap::new();
+        let mut by_id = HashMap::new();
+        for (i, coin) in coins.iter().enumerate() {
+            by_symbol.entry(coin.symbol.to_lowercase()).or_default().push(i);
+            by_id.insert(coin.id.clone(), i);
+        }
+        CoinList { coins, preferred: preferred_lookup(), by_symbol, by_id }
+    }
+
+    /// The built-in list used when the CoinGecko list can't be loaded.
+    pub fn fallback() -> Self {
+        CoinList::new(vec![
+            Coin { id: "bitcoin".into(), symbol: "btc".into(), name: "Bitcoin".into() },
+            Coin { id: "ethereum".into(), symbol: "eth".into(), name: "Ethereum".into() },
+            Coin { id: "cardano".into(), symbol: "ada".into(), name: "Cardano".into() },
+        ])
+    }
+
+    /// Every known coin, in list order.
+    pub fn coins(&self) -> &[Coin] {
+        &self.coins
+    }
+
+    /// Resolves `input` by preferred alias, then exact id, then symbol.
+    pub fn lookup(&self, input: &str) -> Lookup<'_> {
+        let input = input.trim().to_lowercase();
+        if let Some(id) = self.preferred.get(input.as_str()) {
+            return Lookup::Found(id);
+        }
+        if let Some(&i) = self.by_id.get(&input) {
+            return Lookup::Found(&self.coins[i].id);
+        }
+        match self.by_symbol.get(&input).map(Vec::as_slice) {
+            Some([i]) => Lookup::Found(&self.coins[*i].id),
+            Some(matches) if !matches.is_empty() => Lookup::Ambiguous(matches.iter().map(|&i| &self.coins[i]).collect()),
+            _ => Lookup::Unknown,
+        }
+    }
+
+    /// Up to `limit` coins whose symbol or id starts with `input`, falling
+    /// back to ones whose name contains it, as suggestions for a typo.
+    pub fn similar(&self, input: &str, limit: usize) -> Vec<&Coin> {
+        let input = input.trim().to_lowercase();
+        if input.is_empty() {
+            return Vec::new();
+        }
+        let prefixed: Vec<&Coin> = self
+            .coins
+            .iter()
+            .filter(|coin| coin.symbol.to_lowercase().starts_with(&input) || coin.id.starts_with(&input))
+            .take(limit)
+            .collect();
+        if !prefixed.is_empty() {
+            return prefixed;
+        }
+        self.coins.iter().filter(|coin| coin.name.to_lowercase().contains(&input)).take(limit).collect()
+    }
+}
+
+/// How long the cached coin list is trusted. New listings are rare enough
+/// that a day-old list is fine for validating tickers.
+pub const CACHE_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// `~/.crypto_alerts_coins.json`, where the CoinGecko coin list is cached.
+pub fn default_cache_path() -> Option<PathBuf> {
+    config::home_dir().map(|home| home.join(".crypto_alerts_coins.json"))
+}
+
+/// Loads the coin list from the cache if it is fresh, otherwise fetches it
+/// from CoinGecko and refreshes the cache. Falls back to the built-in list
+/// if neither works.
+pub async fn load_coin_list(api: &CoinGecko, cache: Option<&Cache>) -> CoinList {
+    if let Some(coins) = cache.and_then(Cache::get::<Vec<Coin>>) {
+        return CoinList::new(coins);
+    }
+
+    match api.fetch_coin_list().await {
+        Ok(coins) => {
+            if let Some(cache) = cache {
+                if let Err(e) = cache.put(&coins) {
+                    log_warn(&format!("Could not cache the coin list to {}: {}", cache.path().display(), e));
+                }
+            }
+            CoinList::new(coins)
+        }
+        Err(e) => {
+            log_warn(&format!("Could not load the CoinGecko coin list ({}); only btc, eth and ada are available.", e));
+            CoinList::fallback()
+        }
+    }
+}
+
+/// `~/.crypto_alerts_nfts.json`, where the CoinGecko NFT collection list is cached.
+pub fn default_nft_cache_path() -> Option<PathBuf> {
+    config::home_dir().map(|home| home.join(".crypto_alerts_nfts.json"))
+}
+
+/// Loads the NFT collection list, from the cache if it is fresh, for
+/// checking `--nft` ids. It has no built-in fallback, so a list that can't
+/// be loaded is `None` and the id goes unchecked.
+pub async fn load_nft_list(api: &CoinGecko, cache: Option<&Cache>) -> Option<CoinList> {
+    if let Some(collections) = cache.and_then(Cache::get::<Vec<Coin>>) {
+        return Some(CoinList::new(collections));
+    }
+
+    match api.fetch_nft_list().await {
+        Ok(collections) => {
+            if let Some(cache) = cache {
+                if let Err(e) = cache.put(&collections) {
+                    log_warn(&format!("Could not cache the NFT list to {}: {}", cache.path().display(), e));
+                }
+            }
+            Some(CoinList::new(collections))
+        }
+        Err(e) => {
+            log_warn(&format!("Could not load the CoinGecko NFT list ({}); the --nft id can't be checked.", e));
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pinned_aliases_resolve_without_conflicts() {
+        let names = PREFERRED.iter().map(|(_, aliases)| aliases.len() + 1).sum::<usize>();
+        assert_eq!(preferred_lookup().len(), names, "a name is pinned to more than one coin");
+        let coins = CoinList::new(Vec::new());
+        for (id, aliases) in PREFERRED {
+            for name in std::iter::once(id).chain(aliases.iter()) {
+                assert_eq!(*name, name.to_lowercase(), "'{}' would never match the lowercased input", name);
+                match coins.lookup(name) {
+                    Lookup::Found(found) => assert_eq!(found, *id),
+                    _ => panic!("'{}' did not resolve", name),
+                }
+            }
+        }
+        assert!(matches!(coins.lookup(" BTC "), Lookup::Found("bitcoin")));
+    }
+
+    #[test]
+    fn typos_get_suggestions() {
+        let coins = CoinList::new(vec![
+            Coin { id: "solana".into(), symbol: "sol".into(), name: "Solana".into() },
+            Coin { id: "solar".into(), symbol: "sxp".into(), name: "Solar".into() },
+            Coin { id: "wrapped-bitcoin".into(), symbol: "wbtc".into(), name: "Wrapped Bitcoin".into() },
+        ]);
+        let ids = |input| coins.similar(input, 5).iter().map(|coin| coin.id.as_str()).collect::<Vec<_>>();
+        assert_eq!(ids("so"), ["solana", "solar"]);
+        assert_eq!(ids("bitcoin"), ["wrapped-bitcoin"]);
+        assert!(ids("xyz").is_empty());
+        assert_eq!(coins.similar("so", 1).len(), 1);
+    }
+}
@@ -0,0 +1,66 @@
+use crate::alert::{Direction, PercentBaseline, TrailingMode};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// Saved alert settings. Every field is optional so a partial file only
+/// pre-fills what it names and the rest is still prompted for.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct Config {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ticker: Option<Vec<String>>,
+    /// An `AlertType` name such as `percent`, or its legacy number.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alert_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub direction: Option<Direction>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub percent_baseline: Option<PercentBaseline>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trailing: Option<TrailingMode>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub threshold: Option<f64>,
+    /// For $ change alerts, the drop that triggers an alert when it differs from `threshold`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub down_threshold: Option<f64>,
+    /// For band alerts, the `[low, high]` range the price should stay in.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub band: Option<(f64, f64)>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interval: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vs_currency: Option<String>,
+}
+
+#[derive(Error, Debug)]
+pub enum ConfigError {
+    #[error("IO error: {0}")]
+    Io(#[from] io::Error),
+    #[error("Invalid config file: {0}")]
+    Parse(#[from] toml::de::Error),
+    #[error("Failed to serialize config: {0}")]
+    Serialize(#[from] toml::ser::Error),
+}
+
+pub fn home_dir() -> Option<PathBuf> {
+    std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .map(PathBuf::from)
+}
+
+/// `~/.crypto_alerts.toml`, or `None` when the home directory is unknown.
+pub fn default_config_path() -> Option<PathBuf> {
+    home_dir().map(|home| home.join(".crypto_alerts.toml"))
+}
+
+pub fn load_config(path: &Path) -> Result<Config, ConfigError> {
+    let contents = fs::read_to_string(path)?;
+    Ok(toml::from_str(&contents)?)
+}
+
+pub fn save_config(path: &Path, config: &Config) -> Result<(), ConfigError> {
+    fs::write(path, toml::to_string(config)?)?;
+    Ok(())
+}
@@ -0,0 +1,49 @@
+use crate::output::{Movement, PriceSink, PriceUpdate};
+use serde::Serialize;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::path::Path;
+
+#[derive(Serialize)]
+struct CsvRow<'a> {
+    timestamp: String,
+    ticker: &'a str,
+    price: f64,
+    change: f64,
+    percent_change: f64,
+    alert_fired: bool,
+}
+
+/// Appends one row per price check to a CSV file.
+pub struct CsvLog {
+    name: String,
+    writer: csv::Writer<File>,
+}
+
+impl CsvLog {
+    /// Opens `path` for appending, writing the header only if the file is new or empty.
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let is_new = file.metadata()?.len() == 0;
+        let writer = csv::WriterBuilder::new().has_headers(is_new).from_writer(file);
+        Ok(CsvLog { name: path.display().to_string(), writer })
+    }
+}
+
+impl PriceSink for CsvLog {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn record(&mut self, update: &PriceUpdate, _line: &str, _movement: Movement) -> io::Result<()> {
+        self.writer.serialize(CsvRow {
+            timestamp: update.ts.clone(),
+            ticker: update.ticker,
+            price: update.price,
+            change: update.change,
+            percent_change: update.pct,
+            alert_fired: update.alert,
+        })?;
+        self.writer.flush()
+    }
+}
@@ -0,0 +1,198 @@
+use crate::output::{log_warn, Movement, PriceSink, PriceUpdate};
+use chrono::{DateTime, SecondsFormat, Utc};
+use rusqlite::{params, Connection};
+use std::io;
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// Timestamps are RFC 3339 in UTC, which SQLite's date functions read and
+/// which sort in time order as text.
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS price_checks (
+    id INTEGER PRIMARY KEY,
+    checked_at TEXT NOT NULL,
+    ticker TEXT NOT NULL,
+    vs_currency TEXT NOT NULL,
+    price REAL NOT NULL,
+    change REAL NOT NULL,
+    percent_change REAL NOT NULL,
+    alert_fired INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS price_checks_by_coin ON price_checks (ticker, checked_at);
+CREATE TABLE IF NOT EXISTS alerts (
+    id INTEGER PRIMARY KEY,
+    fired_at TEXT NOT NULL,
+    ticker TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    vs_currency TEXT NOT NULL,
+    price REAL NOT NULL,
+    change REAL NOT NULL,
+    message TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS alerts_by_coin ON alerts (ticker, fired_at);
+CREATE INDEX IF NOT EXISTS alerts_by_time ON alerts (fired_at);
+";
+
+/// How long the writer waits for more records before writing what it has,
+/// so a check's coins (and its alerts) go in one transaction.
+const BATCH_DELAY: Duration = Duration::from_secs(1);
+
+enum Record {
+    Check { checked_at: String, ticker: String, price: f64, change: f64, pct: f64, alert: bool },
+    Alert { fired_at: String, ticker: String, kind: &'static str, price: f64, change: f64, message: String },
+}
+
+/// Every price check and alert, kept in a SQLite file (`--db`) for querying
+/// later. Rows are written on a thread of their own, a batch per
+/// transaction, so a slow disk never holds up a check.
+pub struct Database {
+    name: String,
+    records: Mutex<Option<Sender<Record>>>,
+    writer: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl Database {
+    /// Opens or creates the database at `path`, creating the tables if they
+    /// are missing.
+    pub fn open(path: &Path, vs_currency: &str) -> rusqlite::Result<Self> {
+        let conn = Connection::open(path)?;
+        // Lets the history be queried while the monitor is writing to it.
+        conn.pragma_update_and_check(None, "journal_mode", "WAL", |_| Ok(()))?;
+        conn.execute_batch(SCHEMA)?;
+        let name = path.display().to_string();
+        let (records, received) = mpsc::channel();
+        let writer = {
+            let (name, vs_currency) = (name.clone(), vs_currency.to_string());
+            thread::spawn(move || write_batches(conn, received, &name, &vs_currency))
+        };
+        Ok(Database { name, records: Mutex::new(Some(records)), writer: Mutex::new(Some(writer)) })
+    }
+
+    pub fn record_check(&self, update: &PriceUpdate) {
+        self.send(Record::Check {
+            checked_at: update.ts.clone(),
+            ticker: update.ticker.to_string(),
+            price: update.price,
+            change: update.change,
+            pct: update.pct,
+            alert: update.alert,
+        });
+    }
+
+    /// Records an alert; `kind` is the alert type's name, or `volatility`
+    /// and the like for the extra alerts.
+    pub fn record_alert(&self, at: DateTime<Utc>, ticker: &str, kind: &'static str, price: f64, change: f64, message: &str) {
+        self.send(Record::Alert {
+            fired_at: at.to_rfc3339_opts(SecondsFormat::Secs, true),
+            ticker: ticker.to_string(),
+            kind,
+            price,
+            change,
+            message: message.to_string(),
+        });
+    }
+
+    fn send(&self, record: Record) {
+        if let Some(records) = self.records.lock().unwrap().as_ref() {
+            // The writer runs until this sender is dropped, so it is there.
+            let _ = records.send(record);
+        }
+    }
+
+    /// Writes out whatever is still queued and closes the database; anything
+    /// recorded after this is dropped. Also done on drop, which an exiting
+    /// process skips.
+    pub fn close(&self) {
+        self.records.lock().unwrap().take();
+        if let Some(writer) = self.writer.lock().unwrap().take() {
+            let _ = writer.join();
+        }
+    }
+}
+
+impl Drop for Database {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+fn write_batches(mut conn: Connection, records: Receiver<Record>, name: &str, vs_currency: &str) {
+    while let Ok(first) = records.recv() {
+        let until = Instant::now() + BATCH_DELAY;
+        let mut batch = vec![first];
+        // Ends at the deadline, or early when the database is closed.
+        while let Ok(record) = records.recv_timeout(until.saturating_duration_since(Instant::now())) {
+            batch.push(record);
+        }
+        if let Err(e) = write(&mut conn, &batch, vs_currency) {
+            log_warn(&format!("Failed to write {} record(s) to {}: {}", batch.len(), name, e));
+        }
+    }
+}
+
+fn write(conn: &mut Connection, batch: &[Record], vs_currency: &str) -> rusqlite::Result<()> {
+    let tx = conn.transaction()?;
+    for record in batch {
+        match record {
+            Record::Check { checked_at, ticker, price, change, pct, alert } => {
+                tx.prepare_cached(
+                    "INSERT INTO price_checks (checked_at, ticker, vs_currency, price, change, percent_change, alert_fired)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                )?
+                .execute(params![checked_at, ticker, vs_currency, price, change, pct, alert])?;
+            }
+            Record::Alert { fired_at, ticker, kind, price, change, message } => {
+                tx.prepare_cached(
+                    "INSERT INTO alerts (fired_at, ticker, kind, vs_currency, price, change, message)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                )?
+                .execute(params![fired_at, ticker, kind, vs_currency, price, change, message])?;
+            }
+        }
+    }
+    tx.commit()
+}
+
+/// Sends every price check to a `Database`, which the alerts go to as well.
+pub struct DbSink(pub Arc<Database>);
+
+impl PriceSink for DbSink {
+    fn name(&self) -> String {
+        self.0.name.clone()
+    }
+
+    fn record(&mut self, update: &PriceUpdate, _line: &str, _movement: Movement) -> io::Result<()> {
+        self.0.record_check(update);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checks_and_alerts_are_written_by_close() {
+        let path = std::env::temp_dir().join(format!("crypto_alerts_db_test_{}.sqlite", std::process::id()));
+        let db = Database::open(&path, "usd").unwrap();
+        let mut sink = DbSink(Arc::new(db));
+        sink.record(&PriceUpdate::new("bitcoin", 67200.0, 1200.0, 1.8, true), "", Movement::Up).unwrap();
+        sink.record(&PriceUpdate::new("ethereum", 3100.0, -40.0, -1.3, false), "", Movement::Down).unwrap();
+        sink.0.record_alert(Utc::now(), "bitcoin", "dollar", 67200.0, 1200.0, "Alert! bitcoin price rose by $1,200.00.");
+        sink.0.close();
+
+        let conn = Connection::open(&path).unwrap();
+        let checks: i64 = conn.query_row("SELECT count(*) FROM price_checks WHERE vs_currency = 'usd'", [], |row| row.get(0)).unwrap();
+        assert_eq!(checks, 2);
+        let (kind, message): (String, String) =
+            conn.query_row("SELECT kind, message FROM alerts WHERE ticker = 'bitcoin'", [], |row| Ok((row.get(0)?, row.get(1)?))).unwrap();
+        assert_eq!((kind.as_str(), message.as_str()), ("dollar", "Alert! bitcoin price rose by $1,200.00."));
+        drop(conn);
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{}", path.display(), suffix));
+        }
+    }
+}
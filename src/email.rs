@@ -0,0 +1,81 @@
+use crate::output::{log_line, log_warn};
+use lettre::message::{header::ContentType, Mailbox};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use std::sync::Mutex;
+use tokio::time::{Duration, Instant};
+
+/// Where and how to send alert emails, as given on the command line.
+pub struct EmailConfig<'a> {
+    pub to: &'a str,
+    /// Defaults to `to`.
+    pub from: Option<&'a str>,
+    pub host: &'a str,
+    pub port: u16,
+    pub user: Option<&'a str>,
+    pub password: Option<&'a str>,
+    /// The shortest time between two emails.
+    pub min_gap: Duration,
+}
+
+/// Sends alerts by SMTP, upgrading the connection with STARTTLS. At most
+/// one email goes out per `min_gap`, so a price flapping around its
+/// threshold can't flood the inbox; alerts inside the gap are skipped.
+pub struct Mailer {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+    to: Mailbox,
+    min_gap: Duration,
+    last_sent: Mutex<Option<Instant>>,
+}
+
+impl Mailer {
+    /// Checks the addresses and builds the SMTP transport. Nothing is sent
+    /// until the first alert, so a wrong host or password only shows up then.
+    pub fn new(config: &EmailConfig) -> Result<Self, String> {
+        let to: Mailbox = config.to.parse().map_err(|e| format!("Invalid --email-to address '{}': {}", config.to, e))?;
+        let from: Mailbox = match config.from {
+            Some(from) => from.parse().map_err(|e| format!("Invalid --email-from address '{}': {}", from, e))?,
+            None => to.clone(),
+        };
+        let mut transport = AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(config.host)
+            .map_err(|e| format!("Invalid SMTP host '{}': {}", config.host, e))?
+            .port(config.port);
+        if let Some(user) = config.user {
+            let password = config.password.unwrap_or_default();
+            transport = transport.credentials(Credentials::new(user.to_string(), password.to_string()));
+        }
+        Ok(Mailer { transport: transport.build(), from, to, min_gap: config.min_gap, last_sent: Mutex::new(None) })
+    }
+
+    /// Emails an alert unless one went out less than `min_gap` ago. Failures
+    /// are only logged.
+    pub async fn send(&self, subject: &str, body: &str) {
+        {
+            let mut last_sent = self.last_sent.lock().unwrap();
+            if let Some(sent) = *last_sent {
+                if sent.elapsed() < self.min_gap {
+                    log_line(&format!(
+                        "Email skipped; the last one went out {}s ago.",
+                        sent.elapsed().as_secs()
+                    ));
+                    return;
+                }
+            }
+            *last_sent = Some(Instant::now());
+        }
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(self.to.clone())
+            .subject(subject)
+            .header(ContentType::TEXT_PLAIN)
+            .body(body.to_string());
+        let message = match message {
+            Ok(message) => message,
+            Err(e) => return log_warn(&format!("Failed to build alert email: {}", e)),
+        };
+        if let Err(e) = self.transport.send(message).await {
+            log_warn(&format!("Failed to send alert email: {}", e));
+        }
+    }
+}
@@ -0,0 +1,620 @@
+use std::borrow::Cow;
+use std::sync::RwLock;
+use std::time::Duration;
+
+use colored::Colorize;
+use num_format::{Locale, ToFormattedString};
+
+/// How prices, amounts and percentages are written. Library callers hand
+/// one to [`AlertRules`](crate::AlertRules) or call its methods; the free
+/// `format_*` functions below write in the one the CLI sets up for the
+/// whole process from its flags.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NumberFormat {
+    /// Fixed decimal places for every price and percentage (`--decimals`);
+    /// `None` picks them by size.
+    pub decimals: Option<u8>,
+    /// Whether `amount` spells amounts out in full rather than `$1.32T` (`--verbose`).
+    pub full_amounts: bool,
+    /// Whether `price` shortens thousands and up to `$67.2k` (`--compact-numbers`).
+    pub compact_numbers: bool,
+    /// Whether prices show the currency code instead of the symbol (`--coalesce-currency-symbol`).
+    pub currency_codes: bool,
+    /// Whose thousands separator and decimal mark numbers are shown with.
+    pub locale: Locale,
+}
+
+impl NumberFormat {
+    /// Decimals picked by size, no shortening, symbols and English separators.
+    pub const PLAIN: NumberFormat =
+        NumberFormat { decimals: None, full_amounts: false, compact_numbers: false, currency_codes: false, locale: Locale::en };
+}
+
+impl Default for NumberFormat {
+    fn default() -> Self {
+        NumberFormat::PLAIN
+    }
+}
+
+/// The format the free functions write in.
+static PROCESS_FORMAT: RwLock<NumberFormat> = RwLock::new(NumberFormat::PLAIN);
+
+/// The format the CLI set up for this process, `NumberFormat::PLAIN` until it does.
+pub fn number_format() -> NumberFormat {
+    *PROCESS_FORMAT.read().unwrap()
+}
+
+/// Makes `format` the one the free functions write in. For the CLI, which
+/// sets it once from its flags.
+#[doc(hidden)]
+pub fn set_number_format(format: NumberFormat) {
+    *PROCESS_FORMAT.write().unwrap() = format;
+}
+
+/// The number format the environment asks for, from `LC_ALL`,
+/// `LC_NUMERIC` or `LANG`, whichever is set first. English when none is,
+/// or when it names a locale `parse_locale` doesn't know.
+pub fn system_locale() -> Locale {
+    ["LC_ALL", "LC_NUMERIC", "LANG"]
+        .iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|value| !value.is_empty())
+        .and_then(|value| parse_locale(&value))
+        .unwrap_or(Locale::en)
+}
+
+/// Reads a POSIX locale name such as `de_DE.UTF-8`, falling back to its
+/// language when the region has no format of its own. `C` and `POSIX`
+/// are English.
+pub fn parse_locale(name: &str) -> Option<Locale> {
+    let name = name.split(['.', '@']).next().unwrap_or_default().replace('_', "-");
+    if name == "C" || name == "POSIX" {
+        return Some(Locale::en);
+    }
+    Locale::from_name(&name).or_else(|_| Locale::from_name(name.split('-').next().unwrap_or_default())).ok()
+}
+
+/// Which side of the number a currency's symbol is written on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Placement {
+    /// Straight before the number: `$67,200.00`.
+    Prefix,
+    /// After the number and a space: `1,250 sats`.
+    Suffix,
+}
+
+/// How prices in one quote currency are written.
+#[derive(Debug)]
+pub struct CurrencyInfo {
+    pub code: &'static str,
+    pub symbol: &'static str,
+    /// Decimal places for prices of one and up; smaller prices get as many
+    /// as it takes to show four significant digits, and never fewer.
+    pub decimals: usize,
+    pub placement: Placement,
+}
+
+const CURRENCIES: &[CurrencyInfo] = &[
+    CurrencyInfo { code: "usd", symbol: "$", decimals: 2, placement: Placement::Prefix },
+    CurrencyInfo { code: "eur", symbol: "€", decimals: 2, placement: Placement::Prefix },
+    CurrencyInfo { code: "gbp", symbol: "£", decimals: 2, placement: Placement::Prefix },
+    CurrencyInfo { code: "jpy", symbol: "¥", decimals: 0, placement: Placement::Prefix },
+    // Down to the satoshi.
+    CurrencyInfo { code: "btc", symbol: "₿", decimals: 8, placement: Placement::Prefix },
+    CurrencyInfo { code: "sats", symbol: "sats", decimals: 0, placement: Placement::Suffix },
+    CurrencyInfo { code: "bits", symbol: "bits", decimals: 2, placement: Placement::Suffix },
+];
+
+/// What is known about writing `vs_currency`, if anything.
+pub fn currency_info(vs_currency: &str) -> Option<&'static CurrencyInfo> {
+    CURRENCIES.iter().find(|info| info.code == vs_currency)
+}
+
+/// How prices in `vs_currency` are labelled: its symbol and where it goes,
+/// or, for a currency without one (such as the quote coin of a `--pair`) or
+/// with `codes`, the upper-case code after the number.
+fn currency_label(vs_currency: &str, codes: bool) -> (Cow<'static, str>, Placement) {
+    match currency_info(vs_currency) {
+        Some(info) if !codes => (Cow::Borrowed(info.symbol), info.placement),
+        // A bare number, as `format_compact` writes.
+        _ if vs_currency.is_empty() => (Cow::Borrowed(""), Placement::Prefix),
+        _ => (Cow::Owned(vs_currency.to_uppercase()), Placement::Suffix),
+    }
+}
+
+impl NumberFormat {
+    /// The symbol prices in `vs_currency` are shown with, `$` or `sats`, or
+    /// the upper-case code where they are shown with that.
+    pub fn currency_symbol(&self, vs_currency: &str) -> Cow<'static, str> {
+        currency_label(vs_currency, self.currency_codes).0
+    }
+
+    /// A price in `vs_currency` with its symbol, thousands separators and
+    /// enough decimals to be useful at any size: `$67,200.50`,
+    /// `¥9,850,000`, `$0.00002345`, `₿0.05123400`, `1,250 sats`. Fixed
+    /// `decimals` override the choice, and with `compact_numbers` thousands
+    /// and up read `$67.2k`.
+    pub fn price(&self, value: f64, vs_currency: &str) -> String {
+        let fixed = self.decimals.map(usize::from);
+        if self.compact_numbers {
+            if let Some(compact) = self.compact_price(value, vs_currency, fixed.unwrap_or(1)) {
+                return compact;
+            }
+        }
+        let decimals = fixed.unwrap_or_else(|| decimals_for(value.abs(), vs_currency));
+        self.number(value, vs_currency, decimals)
+    }
+
+    /// The smallest difference `price` shows between prices around
+    /// `value`: `0.01` for dollars, `1` for yen, `0.00000001` for bitcoin.
+    pub fn price_step(&self, value: f64, vs_currency: &str) -> f64 {
+        let decimals = self.decimals.map_or_else(|| decimals_for(value.abs(), vs_currency), usize::from);
+        10f64.powi(-(decimals as i32))
+    }
+
+    /// Like `price`, with a leading `+` for gains: `+$1,200.00`.
+    pub fn change(&self, value: f64, vs_currency: &str) -> String {
+        let formatted = self.price(value, vs_currency);
+        if value > 0.0 {
+            format!("+{}", formatted)
+        } else {
+            formatted
+        }
+    }
+
+    /// A price shortened for the dashboard, without its symbol: `66.8k`,
+    /// `1.2M`, or the `price` digits below a thousand.
+    pub fn compact(&self, value: f64) -> String {
+        self.compact_price(value, "", 1).unwrap_or_else(|| self.price(value, ""))
+    }
+
+    /// A large amount in `vs_currency`, such as a market cap or volume,
+    /// abbreviated to three significant digits: `$1.32T`, `$45.6B`, `$230M`,
+    /// `$12.0K`. Amounts under a thousand are written as prices, and
+    /// `full_amounts` spells every amount out in full.
+    pub fn amount(&self, value: f64, vs_currency: &str) -> String {
+        const UNITS: [(f64, &str); 4] = [(1e12, "T"), (1e9, "B"), (1e6, "M"), (1e3, "K")];
+        if self.full_amounts || !value.is_finite() || value.abs() < 1e3 {
+            return self.price(value, vs_currency);
+        }
+        // Rounded before picking the unit, so 999,950 reads 1.00M, not 1000K.
+        let step = 10f64.powi(value.abs().log10().floor() as i32 - 2);
+        let rounded = (value.abs() / step).round() * step;
+        let (size, unit) = UNITS.iter().find(|(size, _)| rounded >= *size).copied().unwrap_or(UNITS[3]);
+        let scaled = rounded / size;
+        let decimals = if scaled >= 100.0 { 0 } else if scaled >= 10.0 { 1 } else { 2 };
+        self.scaled(scaled.copysign(value), vs_currency, decimals, unit)
+    }
+
+    /// A percent change with its sign, `+2.35%` or `-0.40%`. A change that
+    /// rounds to nothing has no sign. Plain text, for messages and lines
+    /// that carry a style of their own.
+    pub fn pct(&self, value: f64) -> String {
+        let digits = self.pct_digits(value);
+        let rounds_to_zero = !digits.bytes().any(|b| (b'1'..=b'9').contains(&b));
+        let sign = if rounds_to_zero {
+            ""
+        } else if value > 0.0 {
+            "+"
+        } else {
+            "-"
+        };
+        format!("{}{}%", sign, digits)
+    }
+
+    /// The size of a percentage without its sign or `%`, to the fixed
+    /// `decimals` if set. Otherwise it gets two, or under 0.01% enough for
+    /// two significant digits (up to six), so a small move doesn't show as `0.00`.
+    pub fn pct_digits(&self, value: f64) -> String {
+        let magnitude = value.abs();
+        let decimals = match self.decimals {
+            None if magnitude > 0.0 && magnitude < 0.01 => (1 - magnitude.log10().floor() as i32).clamp(2, 6) as usize,
+            None => 2,
+            Some(fixed) => fixed as usize,
+        };
+        format!("{:.*}", decimals, magnitude)
+    }
+
+    fn number(&self, value: f64, vs_currency: &str, decimals: usize) -> String {
+        self.scaled(value, vs_currency, decimals, "")
+    }
+
+    /// `number` with `unit`, such as the `k` of `67.2k`, written straight
+    /// after the digits.
+    fn scaled(&self, value: f64, vs_currency: &str, decimals: usize, unit: &str) -> String {
+        let locale = &self.locale;
+        let digits = format!("{:.*}", decimals, value.abs());
+        let (whole, fraction) = match digits.split_once('.') {
+            Some((whole, fraction)) => (whole, Some(fraction)),
+            None => (digits.as_str(), None),
+        };
+        let whole = whole.parse::<u64>().map_or_else(|_| whole.to_string(), |w| w.to_formatted_string(locale));
+        // Don't show "-$0.00" for a change that rounds to nothing.
+        let sign = if value < 0.0 && digits.bytes().any(|b| (b'1'..=b'9').contains(&b)) { "-" } else { "" };
+        let (label, placement) = currency_label(vs_currency, self.currency_codes);
+        let (symbol, suffix) = match placement {
+            Placement::Prefix => (label, Cow::Borrowed("")),
+            Placement::Suffix => (Cow::Borrowed(""), Cow::Owned(format!(" {}", label))),
+        };
+        match fraction {
+            Some(fraction) => format!("{}{}{}{}{}{}{}", sign, symbol, whole, locale.decimal(), fraction, unit, suffix),
+            None => format!("{}{}{}{}{}", sign, symbol, whole, unit, suffix),
+        }
+    }
+
+    /// `value` in `vs_currency` shortened to its unit with `decimals`
+    /// places: `$67.2k`. `None` under a thousand, which `price` spells out.
+    fn compact_price(&self, value: f64, vs_currency: &str, decimals: usize) -> Option<String> {
+        let (size, unit) = compact_unit(value, decimals)?;
+        Some(self.scaled(value / size, vs_currency, decimals, unit))
+    }
+}
+
+/// The largest of thousands (`k`), millions, billions and trillions that
+/// `value` is at least one of once rounded to `decimals` places, so
+/// 999,950 reads `1.0M` rather than `1000.0k`. `None` under a thousand.
+fn compact_unit(value: f64, decimals: usize) -> Option<(f64, &'static str)> {
+    const UNITS: [(f64, &str); 4] = [(1e12, "T"), (1e9, "B"), (1e6, "M"), (1e3, "k")];
+    let scale = 10f64.powi(decimals.min(18) as i32);
+    if !value.is_finite() || value.abs() < 1e3 {
+        return None;
+    }
+    UNITS.iter().copied().find(|(size, _)| (value.abs() / size * scale).round() / scale >= 1.0)
+}
+
+/// [`NumberFormat::currency_symbol`] in the process's format.
+pub fn currency_symbol(vs_currency: &str) -> Cow<'static, str> {
+    number_format().currency_symbol(vs_currency)
+}
+
+/// [`NumberFormat::price`] in the process's format.
+pub fn format_price(value: f64, vs_currency: &str) -> String {
+    number_format().price(value, vs_currency)
+}
+
+/// [`NumberFormat::price_step`] in the process's format.
+pub fn price_step(value: f64, vs_currency: &str) -> f64 {
+    number_format().price_step(value, vs_currency)
+}
+
+/// [`NumberFormat::change`] in the process's format.
+pub fn format_change(value: f64, vs_currency: &str) -> String {
+    number_format().change(value, vs_currency)
+}
+
+/// [`NumberFormat::compact`] in the process's format.
+pub fn format_compact(value: f64) -> String {
+    number_format().compact(value)
+}
+
+/// [`NumberFormat::amount`] in the process's format.
+pub fn format_amount(value: f64, vs_currency: &str) -> String {
+    number_format().amount(value, vs_currency)
+}
+
+/// [`NumberFormat::pct`] in the process's format.
+pub fn format_pct(value: f64) -> String {
+    number_format().pct(value)
+}
+
+/// [`NumberFormat::pct_digits`] in the process's format.
+pub fn pct_digits(value: f64) -> String {
+    number_format().pct_digits(value)
+}
+
+/// A one-line chart of `values`, one block character each, scaled so the
+/// lowest reads `▁` and the highest `█`. A window with no spread is flat.
+pub fn sparkline(values: &[f64]) -> String {
+    const BLOCKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    let low = values.iter().copied().fold(f64::INFINITY, f64::min);
+    let high = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let span = high - low;
+    values
+        .iter()
+        .map(|value| {
+            let level = if span > 0.0 { ((value - low) / span * 7.0).round() as usize } else { 0 };
+            BLOCKS[level.min(7)]
+        })
+        .collect()
+}
+
+/// How loudly a percent change is colored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PctBand {
+    /// Under 1%.
+    Neutral,
+    /// From 1% up to 5%.
+    Notable,
+    /// Beyond 5%.
+    Large,
+}
+
+pub fn pct_band(value: f64) -> PctBand {
+    match value.abs() {
+        m if m < 1.0 => PctBand::Neutral,
+        m if m <= 5.0 => PctBand::Notable,
+        _ => PctBand::Large,
+    }
+}
+
+/// `format_pct` colored by size, for a percent standing on its own (a table
+/// cell): plain under 1%, yellow up to 5% and green or red beyond.
+/// `--no-color` leaves it plain.
+pub fn colored_pct(value: f64) -> String {
+    let text = format_pct(value);
+    match pct_band(value) {
+        PctBand::Neutral => text,
+        PctBand::Notable => text.yellow().to_string(),
+        PctBand::Large if value > 0.0 => text.green().to_string(),
+        PctBand::Large => text.red().to_string(),
+    }
+}
+
+/// Formats a duration as `HH:MM:SS`.
+pub fn format_elapsed(elapsed: Duration) -> String {
+    let secs = elapsed.as_secs();
+    format!("{:02}:{:02}:{:02}", secs / 3600, (secs % 3600) / 60, secs % 60)
+}
+
+/// `text` without terminal color codes, for places that aren't a terminal:
+/// the log file and notifications.
+pub fn strip_ansi(text: &str) -> Cow<'_, str> {
+    if !text.contains('\x1b') {
+        return Cow::Borrowed(text);
+    }
+    let mut plain = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // A CSI sequence: ESC [ parameters, then a final letter.
+            if chars.next() == Some('[') {
+                chars.by_ref().find(|c| ('@'..='~').contains(c));
+            }
+        } else {
+            plain.push(c);
+        }
+    }
+    Cow::Owned(plain)
+}
+
+/// `template` with each `{name}` placeholder replaced by its value from
+/// `values`. Unknown placeholders and unmatched braces are left as written.
+pub fn fill_template(template: &str, values: &[(&str, String)]) -> String {
+    let mut filled = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        filled.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let value = after
+            .find('}')
+            .and_then(|end| values.iter().find(|(name, _)| *name == &after[..end]).map(|(_, value)| (end, value)));
+        match value {
+            Some((end, value)) => {
+                filled.push_str(value);
+                rest = &after[end + 1..];
+            }
+            None => {
+                filled.push('{');
+                rest = after;
+            }
+        }
+    }
+    filled.push_str(rest);
+    filled
+}
+
+/// `text` cut to `width` visible characters, not counting terminal color
+/// codes, so a line redrawn in place fits the terminal instead of wrapping.
+/// A colored line that is cut ends with a reset, in case the cut fell
+/// inside a colored span.
+pub fn truncate_visible(text: &str, width: usize) -> Cow<'_, str> {
+    if strip_ansi(text).chars().count() <= width {
+        return Cow::Borrowed(text);
+    }
+    let mut cut = String::with_capacity(text.len());
+    let mut visible = 0;
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            cut.push(c);
+            if let Some(next) = chars.next() {
+                cut.push(next);
+                if next == '[' {
+                    for c in chars.by_ref() {
+                        cut.push(c);
+                        if ('@'..='~').contains(&c) {
+                            break;
+                        }
+                    }
+                }
+            }
+        } else if visible == width {
+            break;
+        } else {
+            cut.push(c);
+            visible += 1;
+        }
+    }
+    if text.contains('\x1b') {
+        cut.push_str("\x1b[0m");
+    }
+    Cow::Owned(cut)
+}
+
+/// The currency's own decimals (two if it has none) from 1 up; below that,
+/// four significant digits if that takes more.
+fn decimals_for(magnitude: f64, vs_currency: &str) -> usize {
+    let decimals = currency_info(vs_currency).map_or(2, |info| info.decimals);
+    if !(magnitude > 0.0 && magnitude < 1.0) {
+        return decimals;
+    }
+    ((3 - magnitude.log10().floor() as i32).clamp(0, 12) as usize).max(decimals)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn in_locale(locale: Locale) -> NumberFormat {
+        NumberFormat { locale, ..NumberFormat::PLAIN }
+    }
+
+    #[test]
+    fn sparklines_scale_to_the_window() {
+        assert_eq!(sparkline(&[100.0, 101.0, 102.0, 103.0, 104.0, 105.0, 106.0, 107.0]), "▁▂▃▄▅▆▇█");
+        assert_eq!(sparkline(&[5.0, 1.0, 3.0]), "█▁▅");
+        assert_eq!(sparkline(&[2.0, 2.0]), "▁▁");
+        assert_eq!(sparkline(&[]), "");
+    }
+
+    #[test]
+    fn templates_fill_known_placeholders_only() {
+        let values = [("ticker", "bitcoin".to_string()), ("price", "$67,200.00".to_string())];
+        assert_eq!(fill_template("{ticker} broke resistance at {price}!", &values), "bitcoin broke resistance at $67,200.00!");
+        assert_eq!(fill_template("{tickr} {ticker} {} {price", &values), "{tickr} bitcoin {} {price");
+        assert_eq!(fill_template("no placeholders", &values), "no placeholders");
+    }
+
+    #[test]
+    fn truncation_skips_color_codes() {
+        assert_eq!(truncate_visible("bitcoin $67,200.00", 7), "bitcoin");
+        assert_eq!(truncate_visible("\x1b[32m▲0.8%\x1b[0m | eth", 4), "\x1b[32m▲0.8\x1b[0m");
+        assert!(matches!(truncate_visible("\x1b[32m▲0.8%\x1b[0m", 5), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn btc_scale_prices_get_separators_and_cents() {
+        assert_eq!(format_price(67200.5, "usd"), "$67,200.50");
+        assert_eq!(format_price(1234567.891, "eur"), "€1,234,567.89");
+        assert_eq!(format_price(9850000.4, "jpy"), "¥9,850,000");
+        assert_eq!(format_price(-2000.0, "gbp"), "-£2,000.00");
+    }
+
+    #[test]
+    fn bitcoin_denominations_get_their_own_decimals_and_labels() {
+        assert_eq!(format_price(0.051234, "btc"), "₿0.05123400");
+        assert_eq!(format_price(1.5, "btc"), "₿1.50000000");
+        assert_eq!(format_price(0.0000001234, "btc"), "₿0.0000001234");
+        assert_eq!(format_price(1250.4, "sats"), "1,250 sats");
+        assert_eq!(format_price(0.25, "sats"), "0.2500 sats");
+        assert_eq!(format_price(-12.5, "bits"), "-12.50 bits");
+        assert_eq!(format_amount(2_500_000.0, "sats"), "2.50M sats");
+        assert_eq!(NumberFormat::PLAIN.compact_price(67200.0, "sats", 1).as_deref(), Some("67.2k sats"));
+    }
+
+    #[test]
+    fn currency_symbols_go_before_or_after_the_number() {
+        assert_eq!(NumberFormat::PLAIN.number(67200.5, "gbp", 2), "£67,200.50");
+        assert_eq!(NumberFormat::PLAIN.number(-1250.0, "sats", 0), "-1,250 sats");
+        assert_eq!(format_price(9850000.0, "jpy"), "¥9,850,000");
+        assert_eq!(format_price(0.0, "jpy"), "¥0");
+        // A currency without a symbol, such as a --pair's quote coin, is
+        // written by its code.
+        assert_eq!(NumberFormat::PLAIN.number(0.0512, "eth", 4), "0.0512 ETH");
+        assert_eq!(format_price(15.0, "sol"), "15.00 SOL");
+        assert_eq!(currency_symbol("eth"), "ETH");
+        assert_eq!(currency_label("usd", false), (Cow::Borrowed("$"), Placement::Prefix));
+        assert_eq!(currency_label("usd", true), (Cow::Borrowed("USD"), Placement::Suffix));
+        assert_eq!(currency_info("bits").map(|info| info.decimals), Some(2));
+    }
+
+    #[test]
+    fn shib_scale_prices_keep_significant_digits() {
+        assert_eq!(format_price(0.00002345, "usd"), "$0.00002345");
+        assert_eq!(format_price(0.5, "usd"), "$0.5000");
+        assert_eq!(format_price(0.0123, "eur"), "€0.01230");
+        assert_eq!(format_price(0.0, "usd"), "$0.00");
+        assert_eq!(format_price(-0.001, "usd"), "-$0.001000");
+    }
+
+    #[test]
+    fn fixed_decimals_override_the_automatic_choice() {
+        assert_eq!(NumberFormat::PLAIN.number(67200.6, "usd", 0), "$67,201");
+        assert_eq!(NumberFormat::PLAIN.number(0.00002345, "usd", 4), "$0.0000");
+        assert_eq!(NumberFormat::PLAIN.number(1.5, "jpy", 18), "¥1.500000000000000000");
+    }
+
+    #[test]
+    fn thousands_are_grouped_at_every_magnitude() {
+        let english = NumberFormat::PLAIN;
+        assert_eq!(english.number(999.0, "usd", 2), "$999.00");
+        assert_eq!(english.number(1000.0, "usd", 2), "$1,000.00");
+        assert_eq!(english.number(67200.0, "usd", 2), "$67,200.00");
+        assert_eq!(english.number(-1_234_567.891, "usd", 2), "-$1,234,567.89");
+        assert_eq!(english.number(2.5e12, "jpy", 0), "¥2,500,000,000,000");
+        assert_eq!(in_locale(Locale::de).number(1_234_567.5, "eur", 1), "€1.234.567,5");
+        assert_eq!(in_locale(Locale::en_IN).number(1_234_567.0, "usd", 0), "$12,34,567");
+        assert_eq!(parse_locale("de_DE.UTF-8"), Some(Locale::de));
+        assert_eq!(parse_locale("en_GB"), Some(Locale::en_GB));
+        assert_eq!(parse_locale("C.UTF-8"), Some(Locale::en));
+        assert_eq!(parse_locale("xx_YY"), None);
+    }
+
+    #[test]
+    fn compact_numbers_keep_the_symbol_and_decimals() {
+        let english = NumberFormat::PLAIN;
+        assert_eq!(english.compact_price(67200.0, "usd", 1).as_deref(), Some("$67.2k"));
+        assert_eq!(english.compact_price(-1_234_567.0, "eur", 2).as_deref(), Some("-€1.23M"));
+        assert_eq!(english.compact_price(999_950.0, "usd", 1).as_deref(), Some("$1.0M"));
+        assert_eq!(english.compact_price(3.2e12, "usd", 0).as_deref(), Some("$3T"));
+        assert_eq!(in_locale(Locale::de).compact_price(67200.0, "eur", 1).as_deref(), Some("€67,2k"));
+        assert_eq!(english.compact_price(999.0, "usd", 1), None);
+    }
+
+    #[test]
+    fn percent_bands_change_at_one_and_five_percent() {
+        assert_eq!(pct_band(0.99), PctBand::Neutral);
+        assert_eq!(pct_band(-0.99), PctBand::Neutral);
+        assert_eq!(pct_band(1.0), PctBand::Notable);
+        assert_eq!(pct_band(-5.0), PctBand::Notable);
+        assert_eq!(pct_band(5.01), PctBand::Large);
+        assert_eq!(format_pct(0.99), "+0.99%");
+        assert_eq!(format_pct(-5.0), "-5.00%");
+        assert_eq!(format_pct(-0.001), "-0.0010%");
+        assert_eq!(format_pct(0.0), "0.00%");
+        colored::control::set_override(true);
+        let colored = [colored_pct(0.5), colored_pct(1.0), colored_pct(-7.5)];
+        colored::control::unset_override();
+        assert_eq!(colored, ["+0.50%", "\x1b[33m+1.00%\x1b[0m", "\x1b[31m-7.50%\x1b[0m"]);
+    }
+
+    #[test]
+    fn large_amounts_abbreviate_to_three_digits() {
+        assert_eq!(format_amount(1_320_000_000_000.0, "usd"), "$1.32T");
+        assert_eq!(format_amount(45_612_345_678.0, "usd"), "$45.6B");
+        assert_eq!(format_amount(230_400_000.0, "eur"), "€230M");
+        assert_eq!(format_amount(12_000.0, "usd"), "$12.0K");
+        assert_eq!(format_amount(-2_500_000.0, "usd"), "-$2.50M");
+    }
+
+    #[test]
+    fn amounts_change_unit_at_each_thousand() {
+        assert_eq!(format_amount(999.99, "usd"), "$999.99");
+        assert_eq!(format_amount(1_000.0, "usd"), "$1.00K");
+        assert_eq!(format_amount(999_499.0, "usd"), "$999K");
+        assert_eq!(format_amount(999_950.0, "usd"), "$1.00M");
+        assert_eq!(format_amount(1e9, "usd"), "$1.00B");
+        assert_eq!(format_amount(999.5e9, "usd"), "$1.00T");
+        assert_eq!(format_amount(2.5e15, "usd"), "$2,500T");
+        assert_eq!(format_amount(850.4, "jpy"), "¥850");
+    }
+
+    #[test]
+    fn a_format_applies_its_own_options() {
+        let fixed = NumberFormat { decimals: Some(4), currency_codes: true, ..NumberFormat::PLAIN };
+        assert_eq!(fixed.price(1.5, "usd"), "1.5000 USD");
+        assert_eq!(fixed.pct(2.0), "+2.0000%");
+        assert_eq!(fixed.price_step(67200.0, "usd"), 0.0001);
+        let short = NumberFormat { compact_numbers: true, full_amounts: true, ..NumberFormat::PLAIN };
+        assert_eq!(short.price(67200.0, "usd"), "$67.2k");
+        assert_eq!(short.amount(2_500_000.0, "usd"), "$2.5M");
+        assert_eq!(NumberFormat::PLAIN.amount(2_500_000.0, "usd"), "$2.50M");
+    }
+
+    #[test]
+    fn compact_prices_shorten_thousands() {
+        assert_eq!(format_compact(66812.0), "66.8k");
+        assert_eq!(format_compact(1_234_567.0), "1.2M");
+        assert_eq!(format_compact(512.345), "512.35");
+    }
+}
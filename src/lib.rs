@@ -0,0 +1,84 @@
+//! Price fetching and alert logic behind the `cryptoPriceTracker` CLI, for
+//! building other frontends on the same pieces.
+//!
+//! Prices come from a [`PriceProvider`]: CoinGecko through [`CoinGecko`]
+//! (whose [`CoinGecko::fetch_prices`] batches many coins in one request),
+//! Binance, Kraken or a replay file. [`fetch_with_retry`] adds the CLI's
+//! retry and backoff. [`should_alert`] decides $ and % change alerts from a
+//! baseline and a current price without any I/O. For every alert type,
+//! [`Watch`] keeps a coin's alert state from check to check and
+//! [`evaluate_alert`] runs a new quote against it under [`AlertRules`].
+//!
+//! Those, the modules documented here and their re-exports are the stable
+//! surface. The CLI's own modules (its output, logging, settings file,
+//! notifications and metrics) are public only for the binary, are hidden
+//! from these docs and may change in any release.
+//!
+//! ```no_run
+//! use crypto_price_tracker::{api, should_alert, AlertType, CoinGecko, CoinGeckoProvider, PriceProvider};
+//! use std::time::Duration;
+//!
+//! # async fn demo() -> Result<(), crypto_price_tracker::FetchError> {
+//! let client = api::build_client(Duration::from_secs(10), None).unwrap();
+//! let provider = CoinGeckoProvider::new(CoinGecko::new(client, None, Duration::ZERO), "usd", &[]);
+//! let quote = provider.fetch("bitcoin").await?;
+//! if let Some(alert) = should_alert(AlertType::Percent, 60_000.0, quote.price, 5.0, false) {
+//!     println!("bitcoin {} {:.2}%", alert.verb(), alert.moved.abs());
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+/// Alert types and the state behind alerts that span several checks.
+pub mod alert;
+/// The CoinGecko API client and its errors.
+pub mod api;
+/// Small JSON files that expire after a TTL.
+pub mod cache;
+/// The coin list used to turn tickers into CoinGecko ids.
+pub mod coins;
+/// The saved settings file.
+#[doc(hidden)]
+pub mod config;
+/// Appending price checks to a CSV file.
+#[doc(hidden)]
+pub mod csv_log;
+/// Price checks and alerts kept in a SQLite database.
+pub mod db;
+/// Alert emails over SMTP.
+#[doc(hidden)]
+pub mod email;
+/// Price formatting.
+pub mod format;
+/// Console and log file output through `tracing`.
+#[doc(hidden)]
+pub mod logging;
+/// Prometheus metrics served over HTTP.
+#[doc(hidden)]
+pub mod metrics;
+/// Alert delivery: bell, desktop, webhook, Telegram and email.
+#[doc(hidden)]
+pub mod notify;
+/// Console output helpers and the JSON output format.
+#[doc(hidden)]
+pub mod output;
+/// Price providers and the retrying fetch.
+pub mod provider;
+/// Spacing requests to an API.
+pub mod rate_limit;
+/// Alert sounds.
+#[doc(hidden)]
+pub mod sound;
+/// Baselines that persist between runs.
+#[doc(hidden)]
+pub mod state;
+/// A monitored coin's alert state and the check that decides whether it alerts.
+pub mod watch;
+/// Coins and per-coin alert settings read from a file.
+pub mod watchlist;
+
+pub use alert::{percent_change, should_alert, AlertMessage, AlertType, Direction, PercentBaseline, TrailingMode};
+pub use api::{CoinGecko, FetchError};
+pub use format::NumberFormat;
+pub use provider::{fetch_with_retry, CoinGeckoProvider, PriceBatch, PriceProvider, Quote};
+pub use watch::{evaluate_alert, AlertRules, Watch};
@@ -0,0 +1,156 @@
+use crate::format::strip_ansi;
+use crate::output;
+use chrono::{SecondsFormat, Utc};
+use colored::Colorize;
+use std::fmt;
+use std::fs::OpenOptions;
+use std::io;
+use std::path::Path;
+use std::sync::Mutex;
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormatFields};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{EnvFilter, Layer};
+
+/// Sends log events to the console and, with `--log-file`, appends them to
+/// `log_file` too. Both honor `RUST_LOG` and default to `info`, or to
+/// `debug` for this crate and the binary crate `bin` when `verbose` is set.
+pub fn init(log_file: Option<&Path>, verbose: bool, bin: &str) -> io::Result<()> {
+    let console = tracing_subscriber::fmt::layer()
+        .event_format(ConsoleFormat)
+        .with_writer(console_writer)
+        .with_filter(env_filter(verbose, bin));
+    let file = match log_file {
+        Some(path) => {
+            let file = OpenOptions::new().create(true).append(true).open(path)?;
+            let layer = tracing_subscriber::fmt::layer()
+                .event_format(FileFormat)
+                .with_writer(Mutex::new(file))
+                .with_filter(env_filter(verbose, bin));
+            Some(layer)
+        }
+        None => None,
+    };
+    tracing_subscriber::registry().with(console).with(file).init();
+    Ok(())
+}
+
+fn env_filter(verbose: bool, bin: &str) -> EnvFilter {
+    let default = if verbose { format!("info,{}=debug,{}=debug", env!("CARGO_CRATE_NAME"), bin) } else { "info".to_string() };
+    EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(default))
+}
+
+/// Console logs go to stdout, or to stderr in JSON mode so stdout stays
+/// pure NDJSON.
+fn console_writer() -> Box<dyn io::Write> {
+    if output::is_json() {
+        Box::new(io::stderr())
+    } else if output::is_dashboard() {
+        Box::new(FlushOnDrop(io::stdout()))
+    } else {
+        Box::new(io::stdout())
+    }
+}
+
+/// Stdout that flushes once an event is written, since the dashboard line
+/// redrawn after it has no newline to trigger the flush.
+struct FlushOnDrop(io::Stdout);
+
+impl io::Write for FlushOnDrop {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+impl Drop for FlushOnDrop {
+    fn drop(&mut self) {
+        let _ = io::Write::flush(&mut self.0);
+    }
+}
+
+/// The message of an event plus the optional `style` hint the output
+/// helpers attach for coloring.
+#[derive(Default)]
+struct Fields {
+    message: String,
+    style: String,
+}
+
+impl Visit for Fields {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        match field.name() {
+            "message" => self.message = value.to_string(),
+            "style" => self.style = value.to_string(),
+            _ => {}
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            self.message = format!("{:?}", value);
+        }
+    }
+}
+
+/// `[2024-05-01 14:03:22] message`, colored by style. Routine price lines
+/// are left off the console in quiet, JSON, table and dashboard modes. With a
+/// dashboard line showing, each message replaces it and it is redrawn below.
+struct ConsoleFormat;
+
+impl<S, N> FormatEvent<S, N> for ConsoleFormat
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(&self, _ctx: &FmtContext<'_, S, N>, mut writer: Writer<'_>, event: &Event<'_>) -> fmt::Result {
+        let mut fields = Fields::default();
+        event.record(&mut fields);
+        let message = match fields.style.as_str() {
+            "up" | "down" | "flat"
+                if output::is_json() || output::is_quiet() || output::is_dashboard() || output::is_table() =>
+            {
+                return Ok(())
+            }
+            "up" => fields.message.green(),
+            "down" => fields.message.red(),
+            "alert" => fields.message.yellow().bold(),
+            _ if *event.metadata().level() == Level::DEBUG => fields.message.dimmed(),
+            _ => fields.message.normal(),
+        };
+        let status = output::status_line();
+        if status.is_empty() {
+            writeln!(writer, "[{}] {}", output::timestamp(), message)
+        } else {
+            write!(writer, "{}[{}] {}\n{}", output::CLEAR_LINE, output::timestamp(), message, status)
+        }
+    }
+}
+
+/// `2024-05-01T14:03:22Z  INFO message`, uncolored, for the log file.
+struct FileFormat;
+
+impl<S, N> FormatEvent<S, N> for FileFormat
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(&self, _ctx: &FmtContext<'_, S, N>, mut writer: Writer<'_>, event: &Event<'_>) -> fmt::Result {
+        let mut fields = Fields::default();
+        event.record(&mut fields);
+        writeln!(
+            writer,
+            "{} {:>5} {}",
+            Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+            event.metadata().level(),
+            strip_ansi(&fields.message)
+        )
+    }
+}
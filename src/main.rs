@@ -1,119 +1,3860 @@
-use reqwest;
-use serde::Deserialize;
-use std::collections::HashMap;
-use std::io::{self, Write};
-use tokio::time::{sleep, Duration};
+use crypto_price_tracker::{
+    alert, api, cache, coins, config, csv_log, db, email, format, logging, metrics, notify, output, provider, rate_limit, sound,
+    state, watch, watchlist,
+};
+use alert::{
+    percent_change, should_alert, ActiveDays, AlertEvent, AlertHistory, AlertType, Direction, Ema, PercentBaseline,
+    RollingWindow, TrailingMode,
+};
+use api::{ApiKey, ApiTier, CoinGecko, CoinInfo, FetchError};
+use cache::Cache;
+use chrono::{DateTime, Datelike, Local, NaiveDate, NaiveTime, Utc};
+use clap::{Parser, ValueEnum};
+use colored::Colorize;
+use coins::{Coin, CoinList, Lookup};
+use config::Config;
+use csv_log::CsvLog;
+use db::{Database, DbSink};
+use email::{EmailConfig, Mailer};
+use metrics::Metrics;
+use format::{
+    colored_pct, currency_symbol, fill_template, format_amount, format_change, format_compact, format_elapsed, format_pct,
+    format_price, pct_digits, sparkline, strip_ansi, NumberFormat,
+};
+use notify::{Notifier, QuietHours, TelegramSink};
+use output::{
+    log_alert, log_error, log_line, log_warn, report, CoinSummary, ConsoleSink, JsonLinesSink,
+    Min24hChangeSink, Movement, OutputFormat, PriceSink, PriceUpdate, RunSummary,
+};
+use provider::{
+    fetch_with_retry, Aggregate, CachedQuotes, NftProvider, PortfolioProvider, PriceBatch, PriceProvider, ProviderKind, Quote, RatioProvider, ReplayProvider,
+    TokenProvider,
+};
+use state::{Baseline, State};
+use watch::{evaluate_alert, range_distance, AlertRules, Watch};
+use watchlist::WatchlistEntry;
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, IsTerminal, Write};
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 use thiserror::Error;
+use tokio::signal;
+use tokio::sync::mpsc;
+use tokio::time::{sleep_until, Duration, Instant};
 
-#[derive(Deserialize, Debug)]
-struct CoinGeckoPrice {
-    usd: f64,
+/// Command-line options. The key settings can also come from `ALERT_*`
+/// environment variables; a flag beats the variable, which beats the config
+/// file. A ticker can also be piped in on stdin, ahead of the config file.
+/// Anything left unset is asked for interactively.
+#[derive(Parser, Debug)]
+#[command(
+    version,
+    about = "Monitor cryptocurrency prices and alert on changes",
+    after_help = "While monitoring, type p and press enter to pause fetching, r to resume, or a to re-arm \
+                  alerts that have fired. add COIN TYPE THRESHOLD (add sol percent 5, or add btc dollar 500,1000 down \
+                  300 for levels and a separate drop) starts monitoring another coin with the other settings as \
+                  given, remove COIN stops one, list shows them all and quit stops monitoring.\n\n\
+                  Settings are taken from flags first, then the ALERT_TICKER, ALERT_TYPE, ALERT_THRESHOLD and \
+                  ALERT_INTERVAL environment variables, then the config file; anything still unset is asked \
+                  for. Without --ticker, tickers piped to stdin (echo btc | cryptoPriceTracker) come before the \
+                  config file.\n\n\
+                  Exit status: 0 on a normal stop, 1 on a runtime error, 2 on invalid configuration \
+                  (including an API that can't be reached at startup), 3 when --once saw an alert."
+)]
+struct Args {
+    /// Comma-separated tickers to monitor (e.g. btc,eth,ada)
+    #[arg(long, value_delimiter = ',')]
+    ticker: Option<Vec<String>>,
+    /// Monitor a token by contract address instead of by ticker
+    #[arg(long, conflicts_with = "ticker")]
+    contract: Option<String>,
+    /// Monitor an NFT collection's floor price instead, by its CoinGecko NFT
+    /// id (e.g. pudgy-penguins); alerts apply to the floor
+    #[arg(
+        long,
+        value_name = "ID",
+        conflicts_with_all = ["ticker", "contract", "pair", "depeg", "holdings", "watchlist", "extra_currencies", "backfill", "baseline_date"]
+    )]
+    nft: Option<String>,
+    /// Monitor the price ratio of two coins instead of a price (e.g. eth/btc,
+    /// how much bitcoin one ether is worth); alerts apply to the ratio
+    #[arg(long, value_name = "BASE/QUOTE", value_parser = parse_pair, conflicts_with_all = ["ticker", "contract", "extra_currencies"])]
+    pair: Option<(String, String)>,
+    /// Watch a stablecoin for losing its $1.00 peg: a band alert from 1 - X%
+    /// to 1 + X%, where X is --threshold (default 0.5)
+    #[arg(
+        long,
+        value_name = "COIN",
+        conflicts_with_all = ["ticker", "contract", "pair", "alert_type", "down_threshold", "band"]
+    )]
+    depeg: Option<String>,
+    /// Watch the total value of a basket instead of single coins, as
+    /// comma-separated COIN=AMOUNT holdings (e.g. btc=0.5,eth=4); alerts apply to the total
+    #[arg(
+        long,
+        value_name = "COIN=AMOUNT",
+        value_delimiter = ',',
+        value_parser = parse_holding,
+        conflicts_with_all = ["ticker", "contract", "pair", "depeg", "extra_currencies"]
+    )]
+    holdings: Option<Vec<(String, f64)>>,
+    /// Monitor the coins in this file, one `ticker,alert_type,threshold` line
+    /// each (band alerts take LOW-HIGH as the threshold), optionally followed
+    /// by `,SECONDS` to check that coin on its own interval and `,MESSAGE` for
+    /// its own --message template
+    #[arg(long, conflicts_with_all = ["ticker", "contract", "pair", "depeg", "holdings", "alert_type", "threshold", "down_threshold", "band"])]
+    watchlist: Option<PathBuf>,
+    /// CoinGecko asset platform the contract lives on (ethereum, polygon-pos, solana, ...)
+    #[arg(long, default_value = "ethereum", requires = "contract")]
+    platform: String,
+    /// Name to show for the --contract token (defaults to a shortened address)
+    #[arg(long, requires = "contract")]
+    label: Option<String>,
+    /// Quote currency: usd, eur, gbp, jpy, or btc, sats or bits to price
+    /// coins in bitcoin
+    #[arg(long)]
+    currency: Option<String>,
+    /// Also show prices in these currencies, comma-separated (CoinGecko only);
+    /// alerts still use --currency
+    #[arg(long, value_delimiter = ',', value_parser = parse_currency_flag)]
+    extra_currencies: Vec<String>,
+    /// Alert on dollar (absolute change), percent (percent change), target (a price
+    /// target), trailing (a trailing stop or buy), change24h (the 24-hour percent change),
+    /// volume (24-hour volume), mcap (market cap), flat (a flat price), sma (a
+    /// moving-average crossover), band (the price leaving a band) or range24h
+    /// (the price nearing its 24h high or low). The numbers 1-11, in that
+    /// order, also work
+    #[arg(long)]
+    alert_type: Option<AlertType>,
+    /// For target, volume and market-cap alerts, whether to alert above or below the threshold
+    #[arg(long, value_enum)]
+    direction: Option<Direction>,
+    /// For percent alerts, measure the change from the starting price or the previous check
+    #[arg(long, value_enum)]
+    percent_baseline: Option<PercentBaseline>,
+    /// For trailing alerts, alert on a fall from the session high
+    /// (trailing-stop) or a rise from the session low (trailing-buy)
+    #[arg(long, value_enum)]
+    trailing: Option<TrailingMode>,
+    /// Alert threshold (the target price for type 3), applied to every monitored coin.
+    /// A change alerts once it reaches the threshold (>=; see --strict-threshold).
+    /// $ and % change alerts take several, comma-separated (500,1000,2000), each
+    /// alerting once as the move reaches it
+    #[arg(long, value_parser = parse_threshold, value_delimiter = ',')]
+    threshold: Option<Vec<f64>>,
+    /// For $ change alerts, alert on drops of this size instead (--threshold then only covers rises)
+    #[arg(long, value_parser = parse_threshold)]
+    down_threshold: Option<f64>,
+    /// For band alerts, the price range to stay in, as LOW-HIGH: --band 65000-70000
+    #[arg(long, value_parser = parse_band)]
+    band: Option<(f64, f64)>,
+    /// Polling interval in seconds
+    #[arg(long, value_parser = clap::value_parser!(u64).range(MIN_INTERVAL_SECS..))]
+    interval: Option<u64>,
+    /// Vary each wait by a random amount of up to this many seconds either
+    /// way, so instances started together don't poll in lockstep
+    #[arg(long, value_name = "SECS", default_value_t = 0)]
+    jitter: u64,
+    /// For flat-price alerts, how many seconds the price must stay within the threshold
+    #[arg(long, default_value_t = 600, value_parser = clap::value_parser!(u64).range(1..))]
+    flat_window: u64,
+    /// For SMA crossover alerts, how many checks the short moving average covers
+    #[arg(long, default_value_t = 5, value_parser = clap::value_parser!(u64).range(1..))]
+    sma_short: u64,
+    /// For SMA crossover alerts, how many checks the long moving average covers
+    #[arg(long, default_value_t = 20, value_parser = clap::value_parser!(u64).range(2..))]
+    sma_long: u64,
+    /// How many checks the volatility (standard deviation) readout covers
+    #[arg(long, default_value_t = 20, value_parser = clap::value_parser!(u64).range(2..))]
+    volatility_window: u64,
+    /// Also alert when volatility rises above this percentage of the
+    /// average price over the volatility window
+    #[arg(long, value_name = "PCT", value_parser = parse_threshold)]
+    volatility_alert: Option<f64>,
+    /// Show an exponential moving average on each price line, giving the
+    /// newest price this weight (between 0 and 1; higher reacts faster)
+    #[arg(long, value_name = "ALPHA", value_parser = parse_ema_alpha)]
+    ema_alpha: Option<f64>,
+    /// Also alert when the price crosses the --ema-alpha average
+    #[arg(long, requires = "ema_alpha")]
+    ema_alert: bool,
+    /// Show a sparkline of the last N prices on each price line
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(2..))]
+    sparkline: Option<u64>,
+    /// At startup, fill the SMA, EMA, volatility and sparkline windows from
+    /// the last day of CoinGecko prices, so they mean something from the first check
+    #[arg(long, conflicts_with_all = ["replay", "contract", "pair", "holdings"])]
+    backfill: bool,
+    /// Seconds to suppress repeats of an alert after it fires
+    #[arg(long, default_value_t = 300)]
+    cooldown: u64,
+    /// Remind every this many seconds while an alert's condition stays met
+    /// after it fired (off unless set)
+    #[arg(long, value_name = "SECS", value_parser = clap::value_parser!(u64).range(1..))]
+    remind_every: Option<u64>,
+    /// Only alert on a $ or % change (or a 24h change, or a level) that goes
+    /// past the threshold; by default one that reaches it exactly alerts too
+    #[arg(long)]
+    strict_threshold: bool,
+    /// How far short of a target alert's price still counts as reaching it,
+    /// in the --currency; by default half the smallest step prices are shown
+    /// in, so a price that displays as the target reaches it
+    #[arg(long, value_name = "AMOUNT", value_parser = parse_tolerance)]
+    target_tolerance: Option<f64>,
+    /// Only alert once the alert condition has held for N checks in a row,
+    /// so a single noisy tick doesn't trigger it (SMA crossovers aren't held back)
+    #[arg(long, value_name = "N", default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..), conflicts_with = "once")]
+    confirm_cycles: u32,
+    /// Warn when a price comes back exactly the same this many checks in a
+    /// row, which usually means a frozen feed (0 to never warn)
+    #[arg(long, default_value_t = 10)]
+    stale_after: u32,
+    /// Warn when the provider says a price was last updated more than this
+    /// many seconds ago (0 to never warn)
+    #[arg(long, value_name = "SECS", default_value_t = 300)]
+    max_price_age: u64,
+    /// After an alert fires, measure further changes from the price at that moment
+    #[arg(long)]
+    reset_baseline: bool,
+    /// Also append every price check to this file: JSON lines if it ends in
+    /// .jsonl or .ndjson, CSV otherwise. Repeat for several files
+    #[arg(long, value_name = "PATH")]
+    output: Vec<PathBuf>,
+    /// Also keep every price check and alert in this SQLite database,
+    /// created if missing, to query the history later. Rows are written in
+    /// batches on a thread of their own
+    #[arg(long, value_name = "PATH")]
+    db: Option<PathBuf>,
+    /// Only write a check to the --output files when its 24h change is at
+    /// least this many percent either way. Checks that fire an alert are
+    /// always written; otherwise a check must pass --diff-only as well
+    #[arg(long, value_name = "PCT", value_parser = parse_threshold, requires = "output")]
+    min_change_to_log: Option<f64>,
+    /// When monitoring stops, write a JSON summary of the run (duration,
+    /// checks, prices and alert counts) to this file
+    #[arg(long, value_name = "PATH")]
+    summary_json: Option<PathBuf>,
+    /// Serve Prometheus metrics (each coin's price, alerts fired and fetch
+    /// errors) at http://127.0.0.1:PORT/metrics; see --metrics-address
+    #[arg(long, value_name = "PORT")]
+    metrics_port: Option<u16>,
+    /// The address to serve --metrics-port on; 0.0.0.0 lets other machines
+    /// scrape it
+    #[arg(long, value_name = "IP", default_value = "127.0.0.1", requires = "metrics_port")]
+    metrics_address: IpAddr,
+    /// How many of the latest alerts to list when monitoring stops (and in
+    /// --summary-json); older ones are dropped
+    #[arg(long, value_name = "N", default_value_t = 1000)]
+    alert_history: usize,
+    /// Only print a price check (and write it to the --output files) once the
+    /// price has moved at least this much since the last one shown; alerts always show
+    #[arg(long, value_name = "AMOUNT", value_parser = parse_threshold)]
+    diff_only: Option<f64>,
+    /// Disable colored output (also off automatically when stdout isn't a terminal)
+    #[arg(long)]
+    no_color: bool,
+    /// Output format for price checks
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    format: OutputFormat,
+    /// Only print alerts, startup messages and errors, not every price check
+    #[arg(long, short)]
+    quiet: bool,
+    /// Redraw a single status line in place each check instead of printing
+    /// a new line; alerts and warnings still scroll above it
+    #[arg(long, conflicts_with_all = ["quiet", "format"])]
+    dashboard: bool,
+    /// Log each API request URL and the raw response body, for debugging
+    /// coin lookups (the same as
+    /// RUST_LOG=info,crypto_price_tracker=debug,cryptoPriceTracker=debug)
+    #[arg(long, short, conflicts_with = "quiet")]
+    verbose: bool,
+    /// Also append log messages to this file (levels are set with RUST_LOG)
+    #[arg(long)]
+    log_file: Option<PathBuf>,
+    /// Timestamp console output in UTC instead of local time
+    #[arg(long)]
+    utc: bool,
+    /// Show prices and percentages with exactly this many decimal places
+    /// (0-18) instead of choosing them by size: 2 from 1 up (from 0.01% for
+    /// percentages), more below that
+    #[arg(long, value_parser = clap::value_parser!(u8).range(0..=18))]
+    decimals: Option<u8>,
+    /// Shorten prices of a thousand and up to 67.2k, 1.5M and so on (to
+    /// --decimals places if set, else one). Thousands separators otherwise
+    /// follow LC_NUMERIC or LANG
+    #[arg(long)]
+    compact_numbers: bool,
+    /// Write prices with the currency's code after them, 67,200.00 USD,
+    /// rather than its symbol, as currencies without a symbol already are
+    #[arg(long)]
+    coalesce_currency_symbol: bool,
+    /// Play back prices from this file, one per check, instead of fetching them;
+    /// exits when the file runs out
+    #[arg(long)]
+    replay: Option<PathBuf>,
+    /// Where to fetch prices from. Give several, comma-separated, to fall back
+    /// to the next when one fails: --provider coingecko,kraken
+    #[arg(long, value_enum, value_delimiter = ',', default_value = "coingecko")]
+    provider: Vec<ProviderKind>,
+    /// With several --provider sources, ask them all each check and use the
+    /// mean or median of their prices instead of falling back in turn
+    #[arg(long, value_enum, value_name = "METHOD")]
+    aggregate: Option<Aggregate>,
+    /// Ask every --provider each check and show their prices side by side,
+    /// with the spread between them as a percent of the lowest. With a single
+    /// --provider, coingecko, binance and kraken are compared. Alerts go by
+    /// the --aggregate price, the median unless that is set
+    #[arg(long)]
+    compare: bool,
+    /// With --compare, also alert when a coin's prices spread by more than
+    /// this percent of the lowest
+    #[arg(long, value_name = "PCT", value_parser = parse_threshold, requires = "compare")]
+    spread_alert: Option<f64>,
+    /// CoinGecko API key; without one the public, heavily rate-limited API is used
+    #[arg(long, env = "COINGECKO_API_KEY", hide_env_values = true)]
+    api_key: Option<String>,
+    /// CoinGecko plan the API key belongs to
+    #[arg(long, value_enum, default_value_t = ApiTier::Demo)]
+    api_tier: ApiTier,
+    /// Send CoinGecko requests to this base URL instead of
+    /// https://api.coingecko.com/api/v3, e.g. a mock server or a mirror
+    #[arg(long, value_name = "URL", value_parser = parse_base_url)]
+    base_url: Option<String>,
+    /// Send requests through this proxy (http://, https:// or socks5://); without it HTTPS_PROXY is honored
+    #[arg(long)]
+    proxy: Option<String>,
+    /// Seconds to wait for an API response before giving up
+    #[arg(long, default_value_t = 10, value_parser = clap::value_parser!(u64).range(1..))]
+    timeout: u64,
+    /// Minimum milliseconds between outbound API requests
+    #[arg(long, default_value_t = 1000)]
+    min_request_gap: u64,
+    /// Never send more than this many API requests in any minute, waiting
+    /// when the budget runs out; the default is CoinGecko's free-tier limit
+    /// (0 for no cap)
+    #[arg(long, default_value_t = 30)]
+    max_requests_per_minute: usize,
+    /// Never have more than this many API requests open at once, however
+    /// many coins come due together. Requests waiting for a slot still keep
+    /// --min-request-gap apart and count against --max-requests-per-minute
+    /// when they go out; this only bounds the connections held open (1-1024)
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = 4,
+        value_parser = clap::value_parser!(u64).range(1..=rate_limit::MAX_CONCURRENT_FETCHES)
+    )]
+    max_concurrent_fetches: u64,
+    /// How many times to retry a failed fetch, at startup or in a cycle, before giving up on it
+    #[arg(long, default_value_t = 3)]
+    max_retries: u32,
+    /// After this many failed checks in a row, treat the provider as down and
+    /// only probe it every --backoff-interval seconds until it recovers (0 never backs off)
+    #[arg(long, value_name = "CHECKS", default_value_t = 5)]
+    failures_before_backoff: u32,
+    /// Seconds between checks while backed off after repeated failures
+    #[arg(long, value_name = "SECS", default_value_t = 300, value_parser = clap::value_parser!(u64).range(1..))]
+    backoff_interval: u64,
+    /// Check prices once and exit: status 0 if no alert fired, 3 if one did.
+    /// Target alerts check whether the price is past the target right now.
+    #[arg(long)]
+    once: bool,
+    /// Look up each coin's name, symbol and market-cap rank at startup and
+    /// show them when monitoring begins
+    #[arg(long)]
+    coin_info: bool,
+    /// Print the coins that can be monitored, with their CoinGecko ids, and exit
+    #[arg(long)]
+    list_coins: bool,
+    /// Validate this config file, print what it would monitor and exit:
+    /// status 0 if it is valid, 2 if not
+    #[arg(long, value_name = "PATH")]
+    check_config: Option<PathBuf>,
+    /// Run the alert checks and price parsing over TICKS generated prices,
+    /// without the network, print how fast they went and exit: status 0
+    /// if every expected alert fired, 1 if not
+    #[arg(long, value_name = "TICKS", num_args = 0..=1, default_missing_value = "1000000")]
+    self_test: Option<usize>,
+    /// Don't show desktop notifications for alerts
+    #[arg(long)]
+    no_desktop: bool,
+    /// Don't ring the terminal bell when an alert fires
+    #[arg(long)]
+    no_bell: bool,
+    /// Play this WAV file instead of ringing the bell (needs the `sound` build feature)
+    #[arg(long)]
+    sound: Option<PathBuf>,
+    /// Also post alerts to this Discord or Slack incoming webhook URL
+    #[arg(long)]
+    webhook: Option<String>,
+    /// Run this shell command on every alert, with the details in the
+    /// ALERT_TICKER, ALERT_PRICE, ALERT_CHANGE and ALERT_MESSAGE environment variables
+    #[arg(long, value_name = "COMMAND")]
+    on_alert: Option<String>,
+    /// Alert text to use instead of the built-in message, e.g. "{ticker} broke
+    /// resistance at {price}!". Fills in {ticker}, {price}, {change}, {pct} and
+    /// {message} (the built-in text); a watchlist's fifth column overrides it
+    #[arg(long, value_name = "TEMPLATE")]
+    message: Option<String>,
+    /// Also email alerts to this address (needs --smtp-host)
+    #[arg(long, requires = "smtp_host")]
+    email_to: Option<String>,
+    /// Sender address for alert emails; defaults to --email-to
+    #[arg(long, requires = "email_to")]
+    email_from: Option<String>,
+    /// SMTP server to send alert emails through, using STARTTLS
+    #[arg(long, requires = "email_to")]
+    smtp_host: Option<String>,
+    /// SMTP server port (587 for STARTTLS submission)
+    #[arg(long, default_value_t = 587)]
+    smtp_port: u16,
+    /// SMTP login, if the server needs one
+    #[arg(long, env = "SMTP_USER")]
+    smtp_user: Option<String>,
+    /// Password for --smtp-user
+    #[arg(long, env = "SMTP_PASSWORD", hide_env_values = true)]
+    smtp_password: Option<String>,
+    /// Send at most one alert email per this many seconds
+    #[arg(long, default_value_t = 900)]
+    email_gap: u64,
+    /// Local-time window, such as 22:00-07:00, when alerts are only logged and
+    /// not pushed to the desktop, webhook, Telegram or email
+    #[arg(long, value_name = "HH:MM-HH:MM")]
+    quiet_hours: Option<QuietHours>,
+    /// When quiet hours end, push one summary of the alerts they held back
+    #[arg(long, requires = "quiet_hours")]
+    quiet_hours_summary: bool,
+    /// Push alerts that fire within this many seconds of each other as one
+    /// notification, so a market-wide move doesn't send one per coin; the
+    /// console still shows each alert (0 sends each on its own)
+    #[arg(long, value_name = "SECS", default_value_t = 0)]
+    batch_window: u64,
+    /// Only check alerts on these days, in local time, such as mon-fri or
+    /// mon,wed,fri; prices are still checked and shown every day
+    #[arg(long, value_name = "DAYS")]
+    active_days: Option<ActiveDays>,
+    /// Also notify when monitoring starts and when it stops normally, so a
+    /// missing stop notice shows the monitor died
+    #[arg(long, conflicts_with = "once")]
+    notify_lifecycle: bool,
+    /// Also send alerts through this Telegram bot (needs --telegram-chat-id)
+    #[arg(long, env = "TELEGRAM_BOT_TOKEN", hide_env_values = true, requires = "telegram_chat_id")]
+    telegram_token: Option<String>,
+    /// Telegram chat the bot sends alerts to
+    #[arg(long, requires = "telegram_token")]
+    telegram_chat_id: Option<String>,
+    /// Stop after this many checks
+    #[arg(long)]
+    max_checks: Option<u64>,
+    /// Stop after this many seconds
+    #[arg(long)]
+    max_duration: Option<u64>,
+    /// Reuse starting prices fetched up to this many seconds ago by an earlier
+    /// run, mostly so --once calls in quick succession share a request (0 to never reuse)
+    #[arg(long, default_value_t = 10)]
+    price_cache_ttl: u64,
+    /// Measure changes from the price on this date (YYYY-MM-DD, at 00:00 UTC)
+    /// instead of the price when monitoring starts
+    #[arg(long, value_name = "DATE", value_parser = parse_baseline_date, conflicts_with_all = ["contract", "pair", "holdings", "replay"])]
+    baseline_date: Option<NaiveDate>,
+    /// Use this as the starting price instead of fetching one, to try out
+    /// thresholds against a known baseline (one coin only)
+    #[arg(long, value_name = "PRICE", value_parser = parse_threshold, conflicts_with_all = ["baseline_date", "state"])]
+    starting_price: Option<f64>,
+    /// Keep baseline prices in this file so they survive restarts
+    #[arg(long)]
+    state: Option<PathBuf>,
+    /// Settings file to load defaults from (defaults to ~/.crypto_alerts.toml)
+    #[arg(long)]
+    config: Option<PathBuf>,
 }
 
+/// Prints `prompt` and reads one trimmed line. A closed stdin is an error
+/// rather than an empty answer, so prompts can't loop forever.
+fn prompt_user(prompt: &str) -> io::Result<String> {
+    print!("{}", prompt);
+    io::stdout().flush()?;
+    let mut input = String::new();
+    if io::stdin().read_line(&mut input)? == 0 {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "stdin closed while waiting for an answer"));
+    }
+    Ok(input.trim().to_string())
+}
+
+fn prompt_for_f64(prompt: &str) -> io::Result<f64> {
+    loop {
+        let input = prompt_user(prompt)?;
+        match parse_number(&input) {
+            Some(value) => return Ok(value),
+            None => println!("Invalid input. Please enter a number such as 1500 or 2.5."),
+        }
+    }
+}
+
+/// Parses a number the way people tend to type it: an optional leading
+/// currency sign or trailing `%`, thousands separators (`1,500` or
+/// `1.500,25`) and comma decimals (`1,5`). A lone comma followed by exactly
+/// three digits reads as a thousands separator.
+fn parse_number(input: &str) -> Option<f64> {
+    let trimmed = input.trim();
+    let trimmed = trimmed.strip_prefix(['$', '€', '£', '¥']).unwrap_or(trimmed);
+    let trimmed = trimmed.strip_suffix('%').unwrap_or(trimmed).trim();
+    let digits: String = trimmed.chars().filter(|c| !matches!(c, ' ' | '_' | '\'')).collect();
+
+    let normalized = match (digits.rfind(','), digits.rfind('.')) {
+        // Whichever separator comes last is the decimal point.
+        (Some(comma), Some(dot)) if comma > dot => digits.replace('.', "").replacen(',', ".", 1),
+        (Some(_), Some(_)) => digits.replace(',', ""),
+        (Some(comma), None) => {
+            let is_grouping = digits.matches(',').count() > 1 || digits.len() - comma - 1 == 3;
+            if is_grouping {
+                digits.replace(',', "")
+            } else {
+                digits.replacen(',', ".", 1)
+            }
+        }
+        (None, _) => digits,
+    };
+    normalized.parse().ok()
+}
+
+/// Asks for a threshold, rejecting values that aren't positive. For percent
+/// alerts a value above 100 is probably a typo, so it needs confirming.
+fn prompt_for_threshold(prompt: &str, is_percent: bool) -> io::Result<f64> {
+    loop {
+        let value = prompt_for_f64(prompt)?;
+        if value <= 0.0 {
+            println!("The threshold must be greater than zero.");
+            continue;
+        }
+        if is_percent && value > 100.0 {
+            let answer = prompt_user(&format!("{}% is an unusually large move. Use it anyway? (y/N): ", value))?;
+            if !answer.eq_ignore_ascii_case("y") {
+                continue;
+            }
+        }
+        return Ok(value);
+    }
+}
+
+/// Asks for the drop that should trigger a $ change alert. An empty answer
+/// keeps it the same as the rise threshold `up`.
+fn prompt_for_down_threshold(ticker: &str, up: f64, vs_currency: &str) -> io::Result<f64> {
+    loop {
+        let input = prompt_user(&format!(
+            "Enter the drop threshold for {} (press enter for {}): ",
+            ticker,
+            format_price(up, vs_currency)
+        ))?;
+        if input.is_empty() {
+            return Ok(up);
+        }
+        match parse_number(&input) {
+            Some(value) if value > 0.0 => return Ok(value),
+            Some(_) => println!("The threshold must be greater than zero."),
+            None => println!("Invalid input. Please enter a number such as 1500 or 2.5."),
+        }
+    }
+}
+
+/// Fills in what the flags left unset from the ALERT_TICKER, ALERT_TYPE,
+/// ALERT_THRESHOLD and ALERT_INTERVAL variables, read through `var`. This runs
+/// after parsing so an exported variable is only a fallback: one that would
+/// conflict with a flag given (ALERT_TYPE with --watchlist) is ignored.
+fn apply_env_fallbacks(args: &mut Args, var: impl Fn(&str) -> Option<String>) -> Result<(), RunError> {
+    let var = |name: &str| var(name).filter(|value| !value.is_empty());
+    let invalid = |name: &str, e: String| RunError::Config(format!("Invalid {}: {}", name, e));
+    let other_coins = args.contract.is_some()
+        || args.nft.is_some()
+        || args.pair.is_some()
+        || args.depeg.is_some()
+        || args.holdings.is_some()
+        || args.watchlist.is_some();
+    if let (None, false, Some(value)) = (&args.ticker, other_coins, var("ALERT_TICKER")) {
+        args.ticker = Some(value.split(',').map(str::to_string).collect());
+    }
+    if let (None, false, Some(value)) = (args.alert_type, args.depeg.is_some() || args.watchlist.is_some(), var("ALERT_TYPE")) {
+        args.alert_type = Some(value.parse().map_err(|e| invalid("ALERT_TYPE", e))?);
+    }
+    if let (None, false, Some(value)) = (&args.threshold, args.watchlist.is_some(), var("ALERT_THRESHOLD")) {
+        let thresholds = value.split(',').map(parse_threshold).collect::<Result<_, _>>();
+        args.threshold = Some(thresholds.map_err(|e| invalid("ALERT_THRESHOLD", e))?);
+    }
+    if let (None, Some(value)) = (args.interval, var("ALERT_INTERVAL")) {
+        match value.parse::<u64>() {
+            Ok(secs) if secs >= MIN_INTERVAL_SECS => args.interval = Some(secs),
+            Ok(_) => return Err(invalid("ALERT_INTERVAL", format!("the interval must be at least {} seconds", MIN_INTERVAL_SECS))),
+            Err(e) => return Err(invalid("ALERT_INTERVAL", e.to_string())),
+        }
+    }
+    Ok(())
+}
+
+/// Clap parser for thresholds: any finite number greater than zero.
+fn parse_threshold(input: &str) -> Result<f64, String> {
+    match input.parse::<f64>() {
+        Ok(value) if value > 0.0 && value.is_finite() => Ok(value),
+        Ok(_) => Err("the threshold must be a finite number greater than zero".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Clap parser for `--target-tolerance`: any amount from zero up.
+fn parse_tolerance(input: &str) -> Result<f64, String> {
+    match input.parse::<f64>() {
+        Ok(value) if value >= 0.0 && value.is_finite() => Ok(value),
+        Ok(_) => Err("the tolerance can't be negative".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Clap parser for `--base-url`: an absolute http:// or https:// URL.
+fn parse_base_url(input: &str) -> Result<String, String> {
+    match reqwest::Url::parse(input.trim()) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") && url.has_host() => Ok(input.trim().to_string()),
+        Ok(url) => Err(format!("'{}' is not an http:// or https:// URL", url)),
+        Err(e) => Err(format!("'{}' is not a valid URL: {}", input, e)),
+    }
+}
+
+/// Clap parser for `--ema-alpha`: a weight strictly between 0 and 1, since
+/// 0 would never move and 1 would just be the price.
+fn parse_ema_alpha(input: &str) -> Result<f64, String> {
+    match input.parse::<f64>() {
+        Ok(alpha) if alpha > 0.0 && alpha < 1.0 => Ok(alpha),
+        Ok(_) => Err("the smoothing factor must be between 0 and 1".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Clap parser for `--baseline-date`: an ISO date that isn't in the future.
+fn parse_baseline_date(input: &str) -> Result<NaiveDate, String> {
+    let date = NaiveDate::parse_from_str(input.trim(), "%Y-%m-%d")
+        .map_err(|_| format!("'{}' is not a date; expected YYYY-MM-DD, such as 2024-01-01", input))?;
+    if date > Utc::now().date_naive() {
+        return Err(format!("{} is in the future", date));
+    }
+    Ok(date)
+}
+
+/// Clap parser for `--pair BASE/QUOTE`. The two tickers are resolved
+/// against the coin list later, like `--ticker`.
+fn parse_pair(input: &str) -> Result<(String, String), String> {
+    match input.split_once('/').map(|(base, quote)| (base.trim().to_lowercase(), quote.trim().to_lowercase())) {
+        Some((base, quote)) if !base.is_empty() && !quote.is_empty() && base != quote => Ok((base, quote)),
+        Some(_) => Err("the pair needs two different coins".to_string()),
+        None => Err(format!("'{}' is not a pair; expected BASE/QUOTE, such as eth/btc", input)),
+    }
+}
+
+/// Clap parser for one `--holdings` entry, `COIN=AMOUNT`.
+fn parse_holding(input: &str) -> Result<(String, f64), String> {
+    let (coin, amount) =
+        input.split_once('=').ok_or_else(|| format!("'{}' is not a holding; expected COIN=AMOUNT, such as btc=0.5", input))?;
+    match parse_number(amount) {
+        Some(amount) if amount > 0.0 => Ok((coin.trim().to_lowercase(), amount)),
+        _ => Err(format!("'{}' is not an amount greater than zero", amount.trim())),
+    }
+}
+
+/// Clap parser for `--band LOW-HIGH`. Each bound may use thousands
+/// separators, like any other number.
+fn parse_band(input: &str) -> Result<(f64, f64), String> {
+    let (low, high) = input.split_once('-').ok_or("expected LOW-HIGH, such as 65000-70000")?;
+    match (parse_number(low), parse_number(high)) {
+        (Some(low), Some(high)) if low > 0.0 && low < high => Ok((low, high)),
+        (Some(_), Some(_)) => Err("the low bound must be above zero and below the high bound".to_string()),
+        _ => Err("expected LOW-HIGH, such as 65000-70000".to_string()),
+    }
+}
+
+/// Asks for the low and high bounds of a band alert.
+fn prompt_for_band(ticker: &str) -> io::Result<(f64, f64)> {
+    loop {
+        let low = prompt_for_threshold(&format!("Enter the bottom of the price band for {}: ", ticker), false)?;
+        let high = prompt_for_threshold(&format!("Enter the top of the price band for {}: ", ticker), false)?;
+        if low < high {
+            return Ok((low, high));
+        }
+        println!("The top of the band must be above the bottom.");
+    }
+}
+
+/// The quote currencies prices can be shown and alerted in.
+const CURRENCIES: [&str; 7] = ["usd", "eur", "gbp", "jpy", "btc", "sats", "bits"];
+
+fn parse_currency(input: &str) -> Option<String> {
+    let currency = input.trim().to_lowercase();
+    if CURRENCIES.contains(&currency.as_str()) {
+        Some(currency)
+    } else {
+        None
+    }
+}
+
+/// Clap parser for currency flags that must name a supported currency.
+fn parse_currency_flag(input: &str) -> Result<String, String> {
+    parse_currency(input).ok_or_else(|| format!("'{}' is not one of {}", input, CURRENCIES.join(", ")))
+}
+
+fn get_valid_currency() -> io::Result<String> {
+    loop {
+        let currency = prompt_user(&format!("Enter the quote currency ({}; press enter for usd): ", CURRENCIES.join(", ")))?;
+        if currency.is_empty() {
+            return Ok("usd".to_string());
+        }
+        match parse_currency(&currency) {
+            Some(currency) => return Ok(currency),
+            None => println!("Invalid currency. Please enter one of the following: {}.", CURRENCIES.join(", ")),
+        }
+    }
+}
+
+/// What a `--holdings` basket is monitored as.
+const PORTFOLIO_LABEL: &str = "portfolio";
+
+/// How far (%) from $1.00 a `--depeg` coin may drift without an alert.
+const DEFAULT_DEPEG_PCT: f64 = 0.5;
+
+const DEFAULT_INTERVAL_SECS: u64 = 30;
+const MIN_INTERVAL_SECS: u64 = 10;
+
+/// Asks how often to poll, in seconds. An empty answer keeps the default.
+fn prompt_for_interval() -> io::Result<Duration> {
+    loop {
+        let input = prompt_user(&format!(
+            "Enter the polling interval in seconds (minimum {}, press enter for {}): ",
+            MIN_INTERVAL_SECS, DEFAULT_INTERVAL_SECS
+        ))?;
+        if input.is_empty() {
+            return Ok(Duration::from_secs(DEFAULT_INTERVAL_SECS));
+        }
+        match input.parse::<u64>() {
+            Ok(secs) if secs >= MIN_INTERVAL_SECS => return Ok(Duration::from_secs(secs)),
+            Ok(_) => println!("The interval must be at least {} seconds.", MIN_INTERVAL_SECS),
+            Err(_) => println!("Invalid input. Please enter a whole number of seconds."),
+        }
+    }
+}
+
+/// Resolves a comma-separated list of tickers to CoinGecko ids, dropping
+/// duplicates. When `interactive`, the user picks between coins sharing a
+/// symbol; tickers from flags and files can't be asked about, so there an
+/// ambiguous symbol is an error. The inner error describes the first
+/// ticker that didn't resolve; the outer one is a failure to read the
+/// user's choice.
+fn resolve_tickers(input: &str, coins: &CoinList, interactive: bool) -> io::Result<Result<Vec<String>, String>> {
+    let mut tickers: Vec<String> = Vec::new();
+    for ticker in input.split(',').map(|t| t.trim().to_lowercase()).filter(|t| !t.is_empty()) {
+        let id = match coins.lookup(&ticker) {
+            Lookup::Found(id) => id.to_string(),
+            Lookup::Ambiguous(matches) if interactive => choose_coin(&ticker, &matches)?,
+            Lookup::Ambiguous(matches) => {
+                let ids: Vec<&str> = matches.iter().take(5).map(|coin| coin.id.as_str()).collect();
+                return Ok(Err(format!(
+                    "'{}' is the symbol of {} coins; use a CoinGecko id instead, such as {}.",
+                    ticker,
+                    matches.len(),
+                    ids.join(", ")
+                )));
+            }
+            Lookup::Unknown => return Ok(Err(unknown_ticker(&ticker, coins))),
+        };
+        if !tickers.contains(&id) {
+            tickers.push(id);
+        }
+    }
+    Ok(Ok(tickers))
+}
+
+/// Says that `ticker` isn't a known coin, with what it might have meant: the
+/// whole list when only the built-in coins are loaded, otherwise coins
+/// with a similar symbol or id.
+fn unknown_ticker(ticker: &str, coins: &CoinList) -> String {
+    let known: Vec<&Coin> = if coins.coins().len() <= 10 { coins.coins().iter().collect() } else { coins.similar(ticker, 5) };
+    let known: Vec<String> = known.iter().map(|coin| format!("{} ({})", coin.id, coin.symbol)).collect();
+    match known.as_slice() {
+        [] => format!("'{}' is not a known coin symbol or CoinGecko id; --list-coins shows them all.", ticker),
+        _ if coins.coins().len() <= 10 => {
+            format!("'{}' is not a known coin. The coins available are {}.", ticker, known.join(", "))
+        }
+        _ => format!(
+            "'{}' is not a known coin symbol or CoinGecko id. Did you mean {}? --list-coins shows them all.",
+            ticker,
+            known.join(", ")
+        ),
+    }
+}
+
+/// Checks the currencies prices will be fetched in against the ones
+/// CoinGecko quotes, so an unsupported one fails at startup rather than as
+/// a missing price on every check. If the list can't be fetched, that is
+/// left to the first check.
+async fn check_currencies(api: &CoinGecko, currencies: &[&str]) -> Result<(), RunError> {
+    let supported = match api.fetch_supported_currencies().await {
+        Ok(supported) => supported,
+        Err(e) => {
+            tracing::debug!("Could not fetch the supported currencies: {}", e);
+            return Ok(());
+        }
+    };
+    match currencies.iter().find(|currency| !supported.iter().any(|s| s == *currency)) {
+        Some(currency) => Err(RunError::Config(format!("CoinGecko doesn't quote prices in '{}'.", currency))),
+        None => Ok(()),
+    }
+}
+
+/// Checks `id` against CoinGecko's NFT list, so a typo fails at startup
+/// with suggestions. If the list can't be loaded, the first fetch's 404 is
+/// left to catch it.
+async fn check_nft_id(api: &CoinGecko, id: &str) -> Result<(), RunError> {
+    let cache = coins::default_nft_cache_path().map(|path| Cache::new(path, coins::CACHE_TTL));
+    let Some(collections) = coins::load_nft_list(api, cache.as_ref()).await else {
+        return Ok(());
+    };
+    if collections.coins().iter().any(|collection| collection.id == id) {
+        return Ok(());
+    }
+    let similar: Vec<String> =
+        collections.similar(id, 5).iter().map(|collection| format!("{} ({})", collection.id, collection.name)).collect();
+    Err(RunError::Config(match similar.as_slice() {
+        [] => format!("--nft: '{}' is not a CoinGecko NFT collection id.", id),
+        _ => format!("--nft: '{}' is not a CoinGecko NFT collection id. Did you mean {}?", id, similar.join(", ")),
+    }))
+}
+
+/// Reads the `--watchlist` file and resolves each coin, warning about and
+/// skipping lines that are malformed, name an unknown coin or repeat one.
+fn load_watchlist(path: &Path, coins: &CoinList) -> Result<Vec<WatchlistEntry>, RunError> {
+    let (entries, problems) =
+        watchlist::load(path).map_err(|e| RunError::Config(format!("Failed to read {}: {}", path.display(), e)))?;
+    for problem in &problems {
+        log_warn(&format!("Skipping {} {}", path.display(), problem));
+    }
+    let mut resolved: Vec<WatchlistEntry> = Vec::new();
+    for mut entry in entries {
+        match resolve_tickers(&entry.ticker, coins, false)? {
+            Ok(ids) => entry.ticker = ids.into_iter().next().unwrap_or_default(),
+            Err(problem) => {
+                log_warn(&format!("Skipping a coin in {}: {}", path.display(), problem));
+                continue;
+            }
+        }
+        if let Some(secs) = entry.interval.filter(|&secs| secs < MIN_INTERVAL_SECS) {
+            log_warn(&format!(
+                "{} in {}: raising its {}s interval to the minimum of {}s.",
+                entry.ticker,
+                path.display(),
+                secs,
+                MIN_INTERVAL_SECS
+            ));
+            entry.interval = Some(MIN_INTERVAL_SECS);
+        }
+        if resolved.iter().any(|other| other.ticker == entry.ticker) {
+            log_warn(&format!("Skipping {} in {}: it is listed more than once.", entry.ticker, path.display()));
+            continue;
+        }
+        resolved.push(entry);
+    }
+    if resolved.is_empty() {
+        return Err(RunError::Config(format!("{} lists no coins that can be monitored.", path.display())));
+    }
+    Ok(resolved)
+}
+
+fn choose_coin(symbol: &str, matches: &[&Coin]) -> io::Result<String> {
+    println!("Several coins use the symbol '{}':", symbol);
+    for (i, coin) in matches.iter().enumerate() {
+        println!("  {}) {} ({})", i + 1, coin.id, coin.name);
+    }
+    loop {
+        let input = prompt_user(&format!("Pick one (1-{}): ", matches.len()))?;
+        match input.parse::<usize>() {
+            Ok(n) if (1..=matches.len()).contains(&n) => return Ok(matches[n - 1].id.clone()),
+            _ => println!("Invalid choice."),
+        }
+    }
+}
+
+/// The first line of stdin when it's piped or redirected rather than a
+/// terminal, as in `echo btc | cryptoPriceTracker`. `None` for a terminal,
+/// or when the input is empty, so the ticker is looked for elsewhere.
+fn read_piped_tickers() -> io::Result<Option<String>> {
+    if io::stdin().is_terminal() {
+        return Ok(None);
+    }
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    let input = input.trim();
+    Ok((!input.is_empty()).then(|| input.to_string()))
+}
+
+fn get_valid_tickers(coins: &CoinList) -> io::Result<Vec<String>> {
+    loop {
+        let input = prompt_user("Enter the cryptocurrency tickers, comma-separated (e.g., btc,eth,ada): ")?;
+        match resolve_tickers(&input, coins, true)? {
+            Ok(tickers) if !tickers.is_empty() => return Ok(tickers),
+            Ok(_) => println!("Please enter at least one ticker."),
+            Err(problem) => println!("{}", problem),
+        }
+    }
+}
+
+/// Returns the threshold shared by every coin, or `None` if they differ.
+fn uniform_threshold<T: Copy + PartialEq>(thresholds: &HashMap<String, T>) -> Option<T> {
+    let mut values = thresholds.values();
+    let first = values.next().copied();
+    first.filter(|&t| values.all(|&other| other == t))
+}
+
+/// Asks whether to write the chosen settings to `path`.
+fn offer_to_save_config(path: &Path, config: &Config) -> io::Result<()> {
+    let answer = prompt_user(&format!("Save these settings to {}? (y/N): ", path.display()))?;
+    if !answer.eq_ignore_ascii_case("y") {
+        return Ok(());
+    }
+
+    // The file holds a single threshold, so per-coin values and layered
+    // levels can't be saved.
+    if config.threshold.is_none() && config.band.is_none() {
+        println!("Thresholds differ between coins or have several levels, so no threshold was saved.");
+    }
+
+    match config::save_config(path, config) {
+        Ok(()) => println!("Settings saved to {}", path.display()),
+        Err(e) => println!("Failed to save settings: {}", e),
+    }
+    Ok(())
+}
+
+fn prompt_for_percent_baseline() -> io::Result<PercentBaseline> {
+    loop {
+        let input = prompt_user("Measure the % change from (1) the starting price or (2) the previous check? Enter 1 or 2: ")?.to_lowercase();
+        match input.as_str() {
+            "1" | "start" => return Ok(PercentBaseline::Start),
+            "2" | "previous" => return Ok(PercentBaseline::Previous),
+            _ => println!("Invalid input. Please enter 1 or 2."),
+        }
+    }
+}
+
+fn prompt_for_direction() -> io::Result<Direction> {
+    loop {
+        let input = prompt_user("Alert when the price goes (1) above or (2) below the target? Enter 1 or 2: ")?.to_lowercase();
+        match input.as_str() {
+            "1" | "above" => return Ok(Direction::Above),
+            "2" | "below" => return Ok(Direction::Below),
+            _ => println!("Invalid input. Please enter 1 or 2."),
+        }
+    }
+}
+
+fn prompt_for_trailing_mode() -> io::Result<TrailingMode> {
+    loop {
+        let input = prompt_user("Alert on a (1) fall from the session high or (2) rise from the session low? Enter 1 or 2: ")?
+            .to_lowercase();
+        match input.as_str() {
+            "1" | "stop" | "trailing-stop" => return Ok(TrailingMode::TrailingStop),
+            "2" | "buy" | "trailing-buy" => return Ok(TrailingMode::TrailingBuy),
+            _ => println!("Invalid input. Please enter 1 or 2."),
+        }
+    }
+}
+
+fn prompt_for_alert_type(symbol: &str) -> io::Result<AlertType> {
+    println!("Alert types:");
+    for alert_type in AlertType::ALL {
+        let label = match alert_type {
+            AlertType::Dollar => format!("{} change", symbol),
+            _ => alert_type.label().to_string(),
+        };
+        println!("  {:>2}  {:<10} {}", alert_type.number(), alert_type.name(), label);
+    }
+    loop {
+        match prompt_user("Which alert do you want? Enter a name or number: ")?.parse() {
+            Ok(alert_type) => return Ok(alert_type),
+            Err(e) => println!("Invalid input: {}.", e),
+        }
+    }
+}
+
+/// Alert settings shared by every watch in a session.
+struct AlertSettings {
+    /// What decides whether a check alerts.
+    rules: AlertRules,
+    cooldown: Duration,
+    reset_baseline: bool,
+    /// Unchanged checks before warning of a stale price; 0 never warns.
+    stale_after: u32,
+    /// Price age that triggers a warning; zero never warns.
+    max_price_age: Duration,
+    /// When monitoring began, for the elapsed time on each price line.
+    started: Instant,
+    /// Currencies shown alongside `vs_currency` on each price line.
+    extra_currencies: Vec<String>,
+    /// Volatility, as a percentage of the average price, that alerts.
+    volatility_alert: Option<f64>,
+    /// Whether the price crossing its EMA alerts.
+    ema_alert: bool,
+    /// Whether each check shows every provider's price side by side.
+    compare: bool,
+    /// How far apart the providers' prices may get, as a percent of the
+    /// lowest, before it alerts.
+    spread_alert: Option<f64>,
+    /// The smallest move since the last printed check worth printing.
+    diff_only: Option<f64>,
+    /// How often to remind that a fired alert's condition still holds.
+    remind_every: Option<Duration>,
+    /// Checks in a row the alert condition must hold before it alerts.
+    confirm_cycles: u32,
+    /// The days of the week alerts are checked on; unset means every day.
+    active_days: Option<ActiveDays>,
+    notifier: Notifier,
+    /// Every alert fired so far, for the shutdown summary.
+    history: Mutex<AlertHistory>,
+    /// What `--metrics-port` serves, if it is set.
+    metrics: Option<Arc<Metrics>>,
+    /// The `--db` that alerts are kept in, along with the price checks.
+    db: Option<Arc<Database>>,
+}
+
+/// A coin being monitored: its alert state, and what the CLI keeps around
+/// it from check to check for its other alerts, reminders, warnings and
+/// schedule.
+struct Monitored {
+    watch: Watch,
+    /// Price on the last check that was printed, for `--diff-only`.
+    last_shown: f64,
+    alerts_fired: u32,
+    /// How many of `alerts_fired` were `--volatility-alert`s.
+    volatility_alerts: u32,
+    /// How many of `alerts_fired` were `--ema-alert`s.
+    ema_alerts: u32,
+    /// How many of `alerts_fired` were `--spread-alert`s.
+    spread_alerts: u32,
+    /// Recent prices for the volatility readout.
+    volatility: Option<RollingWindow>,
+    /// Whether volatility was above `--volatility-alert` on the last check.
+    volatile: bool,
+    /// Whether the providers' prices were further apart than `--spread-alert`
+    /// on the last check.
+    spread_wide: bool,
+    /// The `--ema-alpha` moving average.
+    ema: Option<Ema>,
+    /// Recent prices for the `--sparkline` chart.
+    sparkline: Option<RollingWindow>,
+    /// Checks in a row that returned exactly the previous price.
+    unchanged_checks: u32,
+    /// Whether the last price was older than `--max-price-age`.
+    price_too_old: bool,
+    last_alert: Option<Instant>,
+    /// When the alert fired for the condition that still holds, if it does;
+    /// cleared once the condition stops being met.
+    triggered_at: Option<DateTime<Utc>>,
+    /// When the alert or its last `--remind-every` reminder went out.
+    reminded_at: Option<Instant>,
+    /// Checks in a row the alert condition has been met, towards `--confirm-cycles`.
+    confirmations: u32,
+    /// The `--message` or watchlist template the alert text is made from.
+    message: Option<String>,
+    /// Time between this coin's checks: a watchlist interval, or else `--interval`.
+    interval: Duration,
+    /// When this coin is next checked.
+    next_due: Instant,
+}
+
+impl Monitored {
+    fn new(watch: Watch) -> Self {
+        Monitored {
+            last_shown: watch.last_price(),
+            watch,
+            alerts_fired: 0,
+            volatility_alerts: 0,
+            ema_alerts: 0,
+            spread_alerts: 0,
+            volatility: None,
+            volatile: false,
+            spread_wide: false,
+            ema: None,
+            sparkline: None,
+            unchanged_checks: 0,
+            price_too_old: false,
+            last_alert: None,
+            triggered_at: None,
+            reminded_at: None,
+            confirmations: 0,
+            message: None,
+            interval: Duration::ZERO,
+            next_due: Instant::now(),
+        }
+    }
+
+    /// Clears the cooldown and crossing state left by fired alerts, so the
+    /// next qualifying check alerts again. Returns whether anything was tripped.
+    fn rearm(&mut self, cooldown: Duration) -> bool {
+        let tripped = self.in_cooldown(cooldown) | self.watch.rearm() | self.volatile | self.spread_wide;
+        self.last_alert = None;
+        self.triggered_at = None;
+        self.confirmations = 0;
+        self.volatile = false;
+        self.spread_wide = false;
+        tripped
+    }
+
+    /// Whether an alert fired less than `cooldown` ago and repeats should be suppressed.
+    fn in_cooldown(&self, cooldown: Duration) -> bool {
+        self.last_alert.is_some_and(|fired| fired.elapsed() < cooldown)
+    }
+
+    fn record_price(&mut self, price: f64) {
+        self.watch.record_price(price);
+        for window in [&mut self.volatility, &mut self.sparkline].into_iter().flatten() {
+            window.push(price);
+        }
+    }
+
+    /// The standard deviation over the volatility window, and that as a
+    /// percentage of the window's average price.
+    fn volatility(&self) -> Option<(f64, f64)> {
+        let window = self.volatility.as_ref()?;
+        let std_dev = window.std_dev()?;
+        let mean = window.mean_of_last(window.len()).filter(|&mean| mean > 0.0)?;
+        Some((std_dev, std_dev / mean * 100.0))
+    }
+}
+
+impl std::ops::Deref for Monitored {
+    type Target = Watch;
+
+    fn deref(&self) -> &Watch {
+        &self.watch
+    }
+}
+
+impl std::ops::DerefMut for Monitored {
+    fn deref_mut(&mut self) -> &mut Watch {
+        &mut self.watch
+    }
+}
+
+/// How close the watch is to its alert, e.g. `+$1,200.00 / $2,000.00 (60%)`,
+/// for the alert types that measure a distance to a threshold.
+fn alert_progress(watch: &Monitored, quote: &Quote, settings: &AlertSettings) -> Option<String> {
+    let fmt = |value: f64| format_price(value, &settings.rules.vs_currency);
+    let price = quote.price;
+    let share = |moved: f64, limit: f64| (moved.abs() / limit * 100.0).round();
+    match watch.alert_type() {
+        AlertType::Dollar => {
+            let change = price - watch.initial_price();
+            let limit = if change < 0.0 { watch.down_threshold() } else { watch.next_threshold() };
+            Some(format!(
+                "{} / {} ({}%)",
+                format_change(change, &settings.rules.vs_currency),
+                fmt(limit),
+                share(change, limit)
+            ))
+        }
+        AlertType::Percent => {
+            let base = match settings.rules.percent_baseline {
+                PercentBaseline::Start => watch.initial_price(),
+                PercentBaseline::Previous => watch.last_price(),
+            };
+            let change = percent_change(base, price)?;
+            let limit = watch.next_threshold();
+            Some(format!("{} / {}% ({}%)", format_pct(change), limit, share(change, limit)))
+        }
+        AlertType::Target => {
+            let gap = watch.threshold() - price;
+            let tolerance = settings.rules.tolerance_at(watch.threshold());
+            (!settings.rules.direction.reached(price, watch.threshold(), tolerance)).then(|| format!("{} to target", fmt(gap.abs())))
+        }
+        AlertType::Trailing => {
+            let level = settings.rules.trailing.level(watch.min_price(), watch.max_price(), watch.threshold());
+            let name = if settings.rules.trailing == TrailingMode::TrailingStop { "stop" } else { "buy" };
+            (!settings.rules.trailing.is_breached(price, level))
+                .then(|| format!("{} {} ({} away)", name, fmt(level), fmt((price - level).abs())))
+        }
+        AlertType::Band => watch.band().map(|(low, high)| format!("band {} - {}", fmt(low), fmt(high))),
+        AlertType::Change24h => {
+            let change = quote.change_24h?;
+            Some(format!("24h {} / {}% ({}%)", format_pct(change), watch.threshold(), share(change, watch.threshold())))
+        }
+        AlertType::Range24h => {
+            let (side, distance) = range_distance(quote)?;
+            Some(format!("{}% from the 24h {} / {}%", pct_digits(distance.max(0.0)), side, watch.threshold()))
+        }
+        _ => None,
+    }
+}
+
+/// Reports a new price for `watch`, fires its alert if due and logs the
+/// check. Returns whether an alert fired.
+async fn check_price(watch: &mut Monitored, quote: &Quote, settings: &AlertSettings, sinks: &mut [Box<dyn PriceSink>]) -> bool {
+    let fmt = |value: f64| format_price(value, &settings.rules.vs_currency);
+    let price = quote.price;
+    watch.record_price(price);
+    let ema_cross = watch.ema.as_mut().and_then(|ema| ema.push(price));
+    let others: Vec<String> = settings
+        .extra_currencies
+        .iter()
+        .filter_map(|currency| Some(format_price(*quote.other_prices.get(currency)?, currency)))
+        .collect();
+    let others = if others.is_empty() { String::new() } else { format!(" ({})", others.join(", ")) };
+    let mut line = format!(
+        "Current {} price: {}{} | low {} | high {} | avg {}",
+        watch.ticker(), fmt(price), others, fmt(watch.min_price()), fmt(watch.max_price()), fmt(watch.avg_price())
+    );
+    if let Some(change) = quote.change_24h {
+        line.push_str(&format!(" | 24h {}", format_pct(change)));
+    }
+    if let Some(volume) = quote.volume_24h {
+        line.push_str(&format!(" | vol {}", format_amount(volume, &settings.rules.vs_currency)));
+    }
+    if let Some(market_cap) = quote.market_cap {
+        line.push_str(&format!(" | mcap {}", format_amount(market_cap, &settings.rules.vs_currency)));
+    }
+    if !quote.breakdown.is_empty() && price > 0.0 {
+        let parts: Vec<String> = quote
+            .breakdown
+            .iter()
+            .map(|(coin, value)| format!("{} {} ({:.1}%)", coin, fmt(*value), value / price * 100.0))
+            .collect();
+        line.push_str(&format!(" | {}", parts.join(", ")));
+    }
+    if !quote.sources.is_empty() {
+        let parts: Vec<String> = quote.sources.iter().map(|(name, value)| format!("{} {}", name, fmt(*value))).collect();
+        line.push_str(&format!(" | from {}", parts.join(", ")));
+        let low = quote.sources.iter().map(|(_, value)| *value).fold(f64::INFINITY, f64::min);
+        let high = quote.sources.iter().map(|(_, value)| *value).fold(f64::NEG_INFINITY, f64::max);
+        if let Some(spread) = quote.spread() {
+            line.push_str(&format!(" (spread {}, {}%)", fmt(high - low), pct_digits(spread)));
+        }
+    }
+    if let Some(name) = &quote.served_by {
+        line.push_str(&format!(" | via {}", name));
+    }
+    if let Some(age) = quote.age() {
+        line.push_str(&format!(" | price age: {}", format_age(age)));
+    }
+    if let Some((std_dev, pct)) = watch.volatility() {
+        line.push_str(&format!(" | σ {} ({}%)", fmt(std_dev), pct_digits(pct)));
+    }
+    if let Some(ema) = watch.ema.as_ref().and_then(Ema::value) {
+        line.push_str(&format!(" | EMA {}", fmt(ema)));
+    }
+    if let Some(window) = &watch.sparkline {
+        line.push_str(&format!(" | {}", sparkline(&window.prices().collect::<Vec<_>>())));
+    }
+    if let Some(progress) = alert_progress(watch, quote, settings) {
+        line.push_str(&format!(" | {}", progress));
+    }
+    let moved_enough = settings.diff_only.is_none_or(|min| (price - watch.last_shown).abs() >= min);
+    let price_change = price - watch.initial_price();
+    // CSV and JSON need a number; a baseline of zero reads as no change.
+    let percent_change = percent_change(watch.initial_price(), price).unwrap_or(0.0);
+
+    let crossings_before = watch.crossings();
+    let triggered = |watch: &Monitored| watch.levels().iter().filter(|level| level.triggered).count();
+    let triggered_before = triggered(watch);
+    // Off the active days nothing is evaluated, so a move or crossing
+    // carries over and alerts on the next active day if it still holds.
+    let active_day = settings.active_days.is_none_or(|days| days.contains(Local::now().weekday()));
+    let alert = if active_day { evaluate_alert(watch, quote, &settings.rules) } else { None };
+    watch.confirmations = if alert.is_some() { watch.confirmations + 1 } else { 0 };
+    // A crossover is over in one check, so there is nothing to confirm.
+    let confirmed = watch.alert_type() == AlertType::Sma || watch.confirmations >= settings.confirm_cycles;
+    let alert = if alert.is_none() || confirmed {
+        alert
+    } else {
+        line.push_str(&format!(" | confirming {}/{}", watch.confirmations, settings.confirm_cycles));
+        // Until then a crossing is put back as not crossed, so the next
+        // check sees it again.
+        watch.restore_crossings(crossings_before.clone());
+        None
+    };
+    line.push_str(&format!(" (+{})", format_elapsed(settings.started.elapsed())));
+    // Each level only ever alerts once, so the cooldown doesn't apply to them.
+    let from_level = triggered(watch) > triggered_before;
+    let alert = if alert.is_some() && !from_level && watch.in_cooldown(settings.cooldown) {
+        // Held back rather than lost: a crossing is put back as not crossed,
+        // so it alerts once the cooldown is over if the price is still past.
+        watch.restore_crossings(crossings_before);
+        None
+    } else {
+        alert
+    };
+    let volatility = if active_day { volatility_alert(watch, price, settings) } else { None };
+    let spread = if active_day { spread_alert(watch, quote, settings) } else { None };
+    let ema_alert = ema_cross.filter(|_| settings.ema_alert && active_day).map(|upward| {
+        format!(
+            "Alert! {} price crossed {} its EMA ({}). Current price: {}",
+            watch.ticker(),
+            if upward { "above" } else { "below" },
+            fmt(watch.ema.as_ref().and_then(Ema::value).unwrap_or(price)),
+            fmt(price)
+        )
+    });
+    let alert_fired = alert.is_some() || volatility.is_some() || spread.is_some() || ema_alert.is_some();
+
+    // The price line goes out before its alerts, so they read in order.
+    if moved_enough || alert_fired {
+        watch.last_shown = price;
+        let mut update = PriceUpdate::new(watch.ticker(), price, price_change, percent_change, alert_fired);
+        update.change_24h = quote.change_24h;
+        update.volume_24h = quote.volume_24h;
+        update.market_cap = quote.market_cap;
+        update.other_prices = (!quote.other_prices.is_empty()).then_some(&quote.other_prices);
+        let movement = Movement::between(watch.last_price(), price);
+        for sink in sinks.iter_mut() {
+            if let Err(e) = sink.record(&update, &line, movement) {
+                log_warn(&format!("Failed to write the price check to {}: {}", sink.name(), e));
+            }
+        }
+    }
+
+    if let Some(message) = alert {
+        let message = match &watch.message {
+            Some(template) => custom_message(template, &message, watch, price, settings),
+            None => message,
+        };
+        announce(settings, "Crypto price alert", watch.alert_type().name(), &message, watch, price).await;
+        watch.alerts_fired += 1;
+        watch.last_alert = Some(Instant::now());
+        watch.triggered_at = Some(Utc::now());
+        watch.reminded_at = watch.last_alert;
+        if settings.reset_baseline && matches!(watch.alert_type(), AlertType::Dollar | AlertType::Percent) {
+            watch.reset_baseline(price);
+            log_line(&format!("{} baseline reset to {}", watch.ticker(), fmt(price)));
+        }
+    }
+    if let Some(message) = volatility {
+        announce(settings, "Crypto volatility alert", "volatility", &message, watch, price).await;
+        watch.alerts_fired += 1;
+        watch.volatility_alerts += 1;
+    }
+    if let Some(message) = spread {
+        announce(settings, "Crypto spread alert", "spread", &message, watch, price).await;
+        watch.alerts_fired += 1;
+        watch.spread_alerts += 1;
+    }
+    if let Some(message) = ema_alert {
+        announce(settings, "Crypto EMA alert", "ema", &message, watch, price).await;
+        watch.alerts_fired += 1;
+        watch.ema_alerts += 1;
+    }
+    if active_day {
+        remind_if_still_triggered(watch, price, settings).await;
+    }
+
+    track_staleness(watch, price, settings);
+    track_price_age(watch, quote, settings);
+    watch.end_check(price);
+    alert_fired
+}
+
+/// The alert text from a `--message` template, with `message` the built-in
+/// text it replaces. Changes are measured from the baseline.
+fn custom_message(template: &str, message: &str, watch: &Monitored, price: f64, settings: &AlertSettings) -> String {
+    let pct = percent_change(watch.initial_price(), price).map_or_else(String::new, format_pct);
+    fill_template(
+        template,
+        &[
+            ("ticker", watch.ticker().to_string()),
+            ("price", format_price(price, &settings.rules.vs_currency)),
+            ("change", format_change(price - watch.initial_price(), &settings.rules.vs_currency)),
+            ("pct", pct),
+            ("message", message.to_string()),
+        ],
+    )
+}
+
+/// Logs an alert, adds it to the alert history and sends it on to every
+/// notification channel and the `--on-alert` command. `kind` names the
+/// alert in the history.
+async fn announce(settings: &AlertSettings, title: &str, kind: &'static str, message: &str, watch: &Monitored, price: f64) {
+    log_alert(message);
+    let at = Utc::now();
+    settings.history.lock().unwrap().push(AlertEvent {
+        at,
+        ticker: watch.ticker().to_string(),
+        kind,
+        price,
+        change: price - watch.initial_price(),
+    });
+    if let Some(db) = &settings.db {
+        db.record_alert(at, watch.ticker(), kind, price, price - watch.initial_price(), &strip_ansi(message));
+    }
+    settings.notifier.send_notification(title, message).await;
+    settings.notifier.run_command(watch.ticker(), price, price - watch.initial_price(), message);
+}
+
+/// Whether the watch's alert condition is met right now, as opposed to
+/// having just been crossed. Alerts on a single check's move or on a
+/// crossover have no lasting condition and never count.
+fn still_triggered(watch: &Monitored, price: f64, settings: &AlertSettings) -> bool {
+    match watch.alert_type() {
+        AlertType::Dollar => {
+            let limit = if price < watch.initial_price() { watch.down_threshold() } else { watch.threshold() };
+            should_alert(AlertType::Dollar, watch.initial_price(), price, limit, settings.rules.strict_threshold).is_some()
+        }
+        AlertType::Percent => {
+            settings.rules.percent_baseline == PercentBaseline::Start
+                && should_alert(AlertType::Percent, watch.initial_price(), price, watch.threshold(), settings.rules.strict_threshold).is_some()
+        }
+        AlertType::Range24h => watch.range_side().is_some(),
+        AlertType::Sma => false,
+        _ => watch.level_breached(),
+    }
+}
+
+/// Sends a `--remind-every` reminder when an alert's condition has held
+/// since it fired and the last reminder is that long ago. Reminders aren't
+/// counted as alerts or kept in the alert history.
+async fn remind_if_still_triggered(watch: &mut Monitored, price: f64, settings: &AlertSettings) {
+    let Some(every) = settings.remind_every else {
+        return;
+    };
+    if !still_triggered(watch, price, settings) {
+        watch.triggered_at = None;
+        return;
+    }
+    let Some(since) = watch.triggered_at else {
+        return;
+    };
+    if watch.reminded_at.is_some_and(|at| at.elapsed() < every) {
+        return;
+    }
+    watch.reminded_at = Some(Instant::now());
+    let message = format!(
+        "Reminder: the {} {} alert is still triggered, since {}. Current price: {}",
+        watch.ticker(),
+        watch.alert_type().label(),
+        output::format_time(since),
+        format_price(price, &settings.rules.vs_currency)
+    );
+    log_alert(&message);
+    settings.notifier.send_notification("Crypto alert reminder", &message).await;
+}
+
+/// Returns the `--volatility-alert` message when volatility rises above it.
+/// Like a target alert it fires on crossing, so it needs to drop back below
+/// before alerting again.
+fn volatility_alert(watch: &mut Monitored, price: f64, settings: &AlertSettings) -> Option<String> {
+    let limit = settings.volatility_alert?;
+    let (std_dev, pct) = watch.volatility()?;
+    let was_volatile = std::mem::replace(&mut watch.volatile, pct > limit);
+    if !watch.volatile || was_volatile {
+        return None;
+    }
+    let window = watch.volatility.as_ref().map_or(0, RollingWindow::len);
+    Some(format!(
+        "Alert! {} volatility rose to {}% (σ {} over the last {} checks). Current price: {}",
+        watch.ticker(),
+        pct_digits(pct),
+        format_price(std_dev, &settings.rules.vs_currency),
+        window,
+        format_price(price, &settings.rules.vs_currency)
+    ))
+}
+
+/// Returns the `--spread-alert` message when the providers' prices for the
+/// coin move further apart than it allows. Fires on crossing, like a
+/// volatility alert.
+fn spread_alert(watch: &mut Monitored, quote: &Quote, settings: &AlertSettings) -> Option<String> {
+    let limit = settings.spread_alert?;
+    let spread = quote.spread()?;
+    let was_wide = std::mem::replace(&mut watch.spread_wide, spread > limit);
+    if !watch.spread_wide || was_wide {
+        return None;
+    }
+    let by_price = |a: &&(String, f64), b: &&(String, f64)| a.1.total_cmp(&b.1);
+    let (low, high) = (quote.sources.iter().min_by(by_price)?, quote.sources.iter().max_by(by_price)?);
+    Some(format!(
+        "Alert! {} prices are {}% apart, from {} on {} to {} on {}. Current price: {}",
+        watch.ticker(),
+        pct_digits(spread),
+        format_price(low.1, &settings.rules.vs_currency),
+        low.0,
+        format_price(high.1, &settings.rules.vs_currency),
+        high.0,
+        format_price(quote.price, &settings.rules.vs_currency)
+    ))
+}
+
+/// Counts checks that return exactly the previous price and warns once the
+/// count reaches `stale_after`, then again when the price finally moves.
+fn track_staleness(watch: &mut Monitored, price: f64, settings: &AlertSettings) {
+    let stale_after = settings.stale_after;
+    if price == watch.last_price() {
+        watch.unchanged_checks += 1;
+        if stale_after > 0 && watch.unchanged_checks == stale_after {
+            log_warn(&format!(
+                "{} price has been {} for {} checks in a row; the price feed may be stale.",
+                watch.ticker(),
+                format_price(price, &settings.rules.vs_currency),
+                stale_after
+            ));
+        }
+    } else {
+        if stale_after > 0 && watch.unchanged_checks >= stale_after {
+            log_line(&format!("{} price is moving again.", watch.ticker()));
+        }
+        watch.unchanged_checks = 0;
+    }
+}
+
+/// Warns once when the provider's last update of a price is older than
+/// `max_price_age`, and again when a fresh price arrives.
+fn track_price_age(watch: &mut Monitored, quote: &Quote, settings: &AlertSettings) {
+    let Some(age) = quote.age().filter(|_| !settings.max_price_age.is_zero()) else {
+        return;
+    };
+    let too_old = age > settings.max_price_age;
+    if too_old && !watch.price_too_old {
+        log_warn(&format!(
+            "{} price was last updated {} ago; the data may be stale.",
+            watch.ticker(),
+            format_age(age)
+        ));
+    } else if !too_old && watch.price_too_old {
+        log_line(&format!("{} price is fresh again.", watch.ticker()));
+    }
+    watch.price_too_old = too_old;
+}
+
+/// Validates every field of the config file at `path`, reporting all
+/// problems at once, and prints a summary of the settings. Fields the file
+/// leaves out are fine; they would be asked for at startup.
+fn check_config(path: &Path, coins: &CoinList) -> Result<(), RunError> {
+    let config = config::load_config(path).map_err(|e| RunError::Config(format!("{}: {}", path.display(), e)))?;
+    let mut problems = Vec::new();
+    let unset = || "not set (asked for at startup)".to_string();
+
+    let mut tickers = Vec::new();
+    for ticker in config.ticker.iter().flatten() {
+        match coins.lookup(ticker) {
+            Lookup::Found(id) => tickers.push(id.to_string()),
+            Lookup::Ambiguous(matches) => problems.push(format!(
+                "ticker '{}' matches {} coins; use a CoinGecko id such as '{}'",
+                ticker,
+                matches.len(),
+                matches[0].id
+            )),
+            Lookup::Unknown => problems.push(format!("ticker '{}' is not a known coin", ticker)),
+        }
+    }
+    if config.ticker.as_ref().is_some_and(|t| t.is_empty()) {
+        problems.push("ticker lists no coins".to_string());
+    }
+    let currency = match &config.vs_currency {
+        Some(currency) => match parse_currency(currency) {
+            Some(currency) => currency,
+            None => {
+                problems.push(format!("vs_currency '{}' is not one of {}", currency, CURRENCIES.join(", ")));
+                currency.clone()
+            }
+        },
+        None => unset(),
+    };
+    let alert_type = match config.alert_type.as_deref().map(str::parse::<AlertType>) {
+        Some(Ok(t)) => format!("{} ({})", t, t.label()),
+        Some(Err(e)) => {
+            problems.push(format!("alert_type: {}", e));
+            config.alert_type.clone().unwrap_or_default()
+        }
+        None => unset(),
+    };
+    for (name, value) in [("threshold", config.threshold), ("down_threshold", config.down_threshold)] {
+        if value.is_some_and(|v| v <= 0.0) {
+            problems.push(format!("{} must be greater than zero", name));
+        }
+    }
+    if config.band.is_some_and(|(low, high)| low <= 0.0 || low >= high) {
+        problems.push("band must be [low, high] with 0 < low < high".to_string());
+    }
+    if let Some(interval) = config.interval.filter(|&secs| secs < MIN_INTERVAL_SECS) {
+        problems.push(format!("interval {}s is below the minimum of {}s", interval, MIN_INTERVAL_SECS));
+    }
+
+    report(&format!("Config file: {}", path.display()));
+    report(&format!("  coins:       {}", if tickers.is_empty() { unset() } else { tickers.join(", ") }));
+    report(&format!("  currency:    {}", currency));
+    report(&format!("  alert type:  {}", alert_type));
+    if let Some(direction) = config.direction {
+        report(&format!("  direction:   {}", direction));
+    }
+    if let Some(baseline) = config.percent_baseline {
+        report(&format!("  % baseline:  {}", format!("{:?}", baseline).to_lowercase()));
+    }
+    if let Some(trailing) = config.trailing {
+        report(&format!("  trailing:    {}", trailing));
+    }
+    report(&format!("  threshold:   {}", config.threshold.map_or_else(unset, |t| t.to_string())));
+    if let Some(down) = config.down_threshold {
+        report(&format!("  drop:        {}", down));
+    }
+    if let Some((low, high)) = config.band {
+        report(&format!("  band:        {}-{}", low, high));
+    }
+    report(&format!("  interval:    {}", config.interval.map_or_else(unset, |secs| format!("{}s", secs))));
+
+    if problems.is_empty() {
+        report("The config is valid.");
+        return Ok(());
+    }
+    for problem in &problems {
+        log_error(&format!("{}: {}", path.display(), problem));
+    }
+    Err(RunError::Config(format!("{} problem(s) found in {}", problems.len(), path.display())))
+}
+
+/// How many coins each synthetic `simple/price` body in `--self-test` holds.
+const SELF_TEST_COINS: usize = 100;
+
+/// A `--self-test` price walk for a $ or % change alert, in whole cents,
+/// with the checks that should alert worked out in integers as it goes.
+struct SelfTestWalk {
+    prices: Vec<f64>,
+    /// The indexes into `prices` that alert, the baseline moving to each.
+    alerts: Vec<usize>,
+    /// How many of `alerts` moved exactly the threshold.
+    exact: usize,
+}
+
+/// Walks `ticks` prices from $100 for a `threshold` in whole cents ($
+/// alerts) or whole percent (% alerts). Steps are random, with every
+/// eighth or so landing on the smallest move from the baseline that
+/// reaches the threshold, or the cent short of it, since a rounding slip
+/// shows at that edge. An xorshift with a fixed seed means every run and
+/// every version sees the same prices.
+fn self_test_walk(alert_type: AlertType, threshold: i64, ticks: usize) -> SelfTestWalk {
+    let mut seed: u64 = 0x2545_f491_4f6c_dd1d;
+    let mut next = move || {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        seed >> 11
+    };
+    let reaching = |baseline: i64| match alert_type {
+        AlertType::Dollar => threshold,
+        _ => (threshold * baseline + 99) / 100,
+    };
+    let (mut baseline, mut cents) = (10_000_i64, 10_000_i64);
+    let mut walk = SelfTestWalk { prices: Vec::with_capacity(ticks), alerts: Vec::new(), exact: 0 };
+    for i in 0..ticks {
+        if i > 0 {
+            let roll = next();
+            let next_cents = match roll % 16 {
+                edge @ 0..=1 => {
+                    let moved = reaching(baseline) - edge as i64;
+                    if roll & 1 << 40 != 0 { baseline + moved } else { baseline - moved }
+                }
+                _ => {
+                    // Up to about 0.3% either way.
+                    let span = (cents / 300).max(1);
+                    cents + (roll >> 4) as i64 % (2 * span + 1) - span
+                }
+            };
+            cents = next_cents.max(100);
+        }
+        let moved = (cents - baseline).abs();
+        let (reached, exact) = match alert_type {
+            AlertType::Dollar => (moved >= threshold, moved == threshold),
+            _ => (moved * 100 >= threshold * baseline, moved * 100 == threshold * baseline),
+        };
+        if reached {
+            walk.alerts.push(i);
+            walk.exact += exact as usize;
+            baseline = cents;
+        }
+        walk.prices.push(cents as f64 / 100.0);
+    }
+    walk
+}
+
+/// Runs `should_alert`, `evaluate_alert` and the `simple/price` parsing
+/// over `ticks` generated prices and reports their throughput. Fails if
+/// an alert fires on any other check than `self_test_walk` worked out, or
+/// a parsed price differs from the one written into the body.
+fn self_test(ticks: usize) -> Result<(), RunError> {
+    if ticks == 0 {
+        return Err(RunError::config("--self-test needs at least one tick."));
+    }
+    let rate = |count: usize, elapsed: std::time::Duration| format_compact(count as f64 / elapsed.as_secs_f64().max(1e-9));
+    let mut failures = Vec::new();
+    report(&format!("Self-test over {} generated prices:", ticks));
+    let rules = AlertRules {
+        direction: Direction::Above,
+        percent_baseline: PercentBaseline::Start,
+        trailing: TrailingMode::TrailingStop,
+        flat_window: Duration::ZERO,
+        vs_currency: "usd".to_string(),
+        strict_threshold: false,
+        target_tolerance: None,
+        format: NumberFormat::PLAIN,
+    };
+    let mut prices = Vec::new();
+
+    // $2.00 and 2%.
+    for (alert_type, threshold) in [(AlertType::Dollar, 200), (AlertType::Percent, 2)] {
+        let walk = self_test_walk(alert_type, threshold, ticks);
+        let threshold_value = match alert_type {
+            AlertType::Dollar => threshold as f64 / 100.0,
+            _ => threshold as f64,
+        };
+        let started = std::time::Instant::now();
+        let mut baseline = walk.prices[0];
+        let mut alerts = Vec::new();
+        for (i, &price) in walk.prices.iter().enumerate() {
+            if should_alert(alert_type, baseline, price, threshold_value, false).is_some() {
+                alerts.push(i);
+                baseline = price;
+            }
+        }
+        let elapsed = started.elapsed();
+
+        let mut watch = Watch::new("self-test".into(), alert_type, threshold_value, walk.prices[0]);
+        let mut evaluated = Vec::new();
+        for (i, &price) in walk.prices.iter().enumerate() {
+            if evaluate_alert(&mut watch, &Quote::price(price), &rules).is_some() {
+                evaluated.push(i);
+                watch.reset_baseline(price);
+            }
+        }
+        report(&format!(
+            "  {} alerts: {} checks in {:.3}s ({} checks/s), {} alerts fired, {} expected ({} exactly on the threshold)",
+            alert_type,
+            walk.prices.len(),
+            elapsed.as_secs_f64(),
+            rate(walk.prices.len(), elapsed),
+            alerts.len(),
+            walk.alerts.len(),
+            walk.exact
+        ));
+        for (name, fired) in [("should_alert", &alerts), ("evaluate_alert", &evaluated)] {
+            if let Some(problem) = first_mismatch(fired, &walk.alerts) {
+                failures.push(format!("{} {} alerts {}", name, alert_type, problem));
+            }
+        }
+        if alert_type == AlertType::Dollar {
+            prices = walk.prices;
+        }
+    }
+
+    let coins: Vec<String> = (0..SELF_TEST_COINS).map(|i| format!("coin-{}", i)).collect();
+    let vs_currencies = ["usd".to_string()];
+    let bodies: Vec<String> = prices
+        .chunks(SELF_TEST_COINS)
+        .map(|chunk| {
+            let fields: Vec<String> = chunk
+                .iter()
+                .zip(&coins)
+                .map(|(price, id)| format!("\"{}\":{{\"usd\":{},\"usd_24h_change\":-1.25}}", id, price))
+                .collect();
+            format!("{{{}}}", fields.join(","))
+        })
+        .collect();
+    let bytes: usize = bodies.iter().map(String::len).sum();
+    let started = std::time::Instant::now();
+    let mut wrong = 0;
+    for (body, chunk) in bodies.iter().zip(prices.chunks(SELF_TEST_COINS)) {
+        let parsed = api::parse_body::<serde_json::Value>(body)
+            .and_then(|value| api::parse_prices(&value, &coins[..chunk.len()], &vs_currencies));
+        let Ok(parsed) = parsed else {
+            wrong += chunk.len();
+            continue;
+        };
+        wrong += chunk
+            .iter()
+            .zip(&coins)
+            .filter(|(expected, id)| {
+                !parsed.get(*id).is_some_and(|p| p.as_ref().is_ok_and(|p| (p.price - **expected).abs() <= **expected * 1e-12))
+            })
+            .count();
+    }
+    let elapsed = started.elapsed();
+    report(&format!(
+        "  parsing: {} prices in {} bodies ({}B) in {:.3}s ({} prices/s), {} wrong",
+        prices.len(),
+        bodies.len(),
+        format_compact(bytes as f64),
+        elapsed.as_secs_f64(),
+        rate(prices.len(), elapsed),
+        wrong
+    ));
+    if wrong > 0 {
+        failures.push(format!("{} parsed prices were missing or wrong", wrong));
+    }
+
+    if failures.is_empty() {
+        report("Self-test passed.");
+        return Ok(());
+    }
+    Err(RunError::Runtime(format!("Self-test failed: {}", failures.join("; "))))
+}
+
+/// Where the checks that alerted first differ from the expected ones, if they do.
+fn first_mismatch(fired: &[usize], expected: &[usize]) -> Option<String> {
+    let same = fired.iter().zip(expected).take_while(|(a, b)| a == b).count();
+    match (fired.get(same), expected.get(same)) {
+        (None, None) => None,
+        (Some(check), Some(wanted)) => Some(format!("fired at check {} instead of {}", check, wanted)),
+        (Some(check), None) => Some(format!("fired at check {} when none was expected", check)),
+        (None, Some(wanted)) => Some(format!("missed the alert at check {}", wanted)),
+    }
+}
+
+/// Logs the settings in effect once flags, the config file and prompts
+/// are merged, so precedence surprises show up before the first check.
+fn log_settings(watches: &[Monitored], settings: &AlertSettings, price_source: &str, interval: Duration) {
+    log_line("Settings in effect:");
+    for watch in watches {
+        let own_interval = if watch.interval != interval { format!(", every {}s", watch.interval.as_secs()) } else { String::new() };
+        log_line(&format!(
+            "  {:<14} {} ({}), {}{}",
+            format!("{}:", watch.ticker()),
+            watch.alert_type(),
+            watch.alert_type().label(),
+            describe_threshold(watch, &settings.rules.vs_currency),
+            own_interval
+        ));
+    }
+    log_line(&format!("  prices from:   {}", price_source));
+    log_line(&format!("  currency:      {}", settings.rules.vs_currency));
+    let interval = if interval.is_zero() { "none (replaying)".to_string() } else { format!("{}s", interval.as_secs()) };
+    log_line(&format!("  interval:      {}", interval));
+    log_line(&format!("  cooldown:      {}s", settings.cooldown.as_secs()));
+    if settings.confirm_cycles > 1 {
+        log_line(&format!("  confirmation:  {} checks in a row", settings.confirm_cycles));
+    }
+    if let Some(days) = settings.active_days {
+        log_line(&format!("  alert days:    {}", days));
+    }
+    let channels = settings.notifier.channels();
+    log_line(&format!("  notifications: {}", if channels.is_empty() { "console only".to_string() } else { channels.join(", ") }));
+}
+
+/// A watch's threshold, or each of its layered levels, in its alert type's units.
+fn describe_threshold(watch: &Monitored, vs_currency: &str) -> String {
+    let fmt = |value: f64| match watch.alert_type() {
+        AlertType::Dollar | AlertType::Target => format_price(value, vs_currency),
+        AlertType::Volume | AlertType::MarketCap => format_amount(value, vs_currency),
+        _ => format!("{}%", value),
+    };
+    match watch.alert_type() {
+        AlertType::Sma => "no threshold".to_string(),
+        AlertType::Band => watch.band().map_or_else(String::new, |(low, high)| {
+            format!("band {} - {}", format_price(low, vs_currency), format_price(high, vs_currency))
+        }),
+        _ if !watch.levels().is_empty() => {
+            let levels: Vec<String> = watch.levels().iter().map(|level| fmt(level.value)).collect();
+            format!("levels {}", levels.join(", "))
+        }
+        AlertType::Dollar if watch.down_threshold() != watch.threshold() => {
+            format!("up {} / down {}", fmt(watch.threshold()), fmt(watch.down_threshold()))
+        }
+        _ => format!("threshold {}", fmt(watch.threshold())),
+    }
+}
+
+/// Prints every coin as `symbol  id  name`, sorted by symbol, or as a JSON
+/// array in JSON mode.
+fn print_coin_list(coins: &CoinList) {
+    let mut list: Vec<_> = coins.coins().iter().collect();
+    list.sort_by(|a, b| a.symbol.cmp(&b.symbol).then_with(|| a.id.cmp(&b.id)));
+    if output::is_json() {
+        println!("{}", serde_json::to_string(&list).unwrap_or_default());
+        return;
+    }
+    let symbol_width = list.iter().map(|c| c.symbol.len()).max().unwrap_or(0).max("SYMBOL".len());
+    let id_width = list.iter().map(|c| c.id.len()).max().unwrap_or(0).max("ID".len());
+    println!("{:<symbol_width$}  {:<id_width$}  NAME", "SYMBOL", "ID");
+    for coin in list {
+        println!("{:<symbol_width$}  {:<id_width$}  {}", coin.symbol, coin.id, coin.name);
+    }
+}
+
+/// Fetches fresh prices for `watches`, the coins due a check, and checks
+/// their alerts. Returns the number of alerts that fired.
+async fn run_check(
+    provider: &dyn PriceProvider,
+    watches: Vec<&mut Monitored>,
+    settings: &AlertSettings,
+    sinks: &mut [Box<dyn PriceSink>],
+    max_retries: u32,
+) -> Result<u32, FetchError> {
+    let tickers: Vec<String> = watches.iter().map(|w| w.ticker().to_string()).collect();
+    let prices = fetch_with_retry(provider, &tickers, max_retries).await?;
+
+    let mut fired = 0;
+    for watch in watches {
+        match prices.get(watch.ticker()) {
+            Some(Ok(quote)) => {
+                if check_price(watch, quote, settings, sinks).await {
+                    fired += 1;
+                }
+            }
+            Some(Err(FetchError::PriceMissing)) => {
+                log_warn(&format!("{} price temporarily unavailable, skipping this check.", watch.ticker()))
+            }
+            Some(Err(e)) => log_warn(&format!("No price for {} this cycle: {}", watch.ticker(), e)),
+            None => log_warn(&format!("No price returned for {} this cycle.", watch.ticker())),
+        }
+        if let Some(metrics) = &settings.metrics {
+            match prices.get(watch.ticker()) {
+                Some(Ok(_)) => metrics.set_coin(watch.ticker(), watch.last_price(), watch.alerts_fired.into()),
+                _ => metrics.add_fetch_error(),
+            }
+        }
+    }
+    let quotes: Vec<_> = tickers.iter().map(|ticker| (ticker.as_str(), prices.get(ticker).and_then(|result| result.as_ref().ok()))).collect();
+    show_comparison(&quotes, settings);
+    Ok(fired)
+}
+
+/// Prints the `--compare` table, if it is on. The live displays redraw over
+/// whatever is printed between them, so it is left out with those.
+fn show_comparison(quotes: &[(&str, Option<&Quote>)], settings: &AlertSettings) {
+    if settings.compare && !output::is_quiet() && !output::is_dashboard() && !output::is_table() {
+        report(&comparison_table(quotes, &settings.rules.vs_currency));
+    }
+}
+
+/// The `--compare` table: a row per coin with each provider's price and
+/// the spread between them, `-` where a provider had no price.
+fn comparison_table(quotes: &[(&str, Option<&Quote>)], vs_currency: &str) -> String {
+    let mut names: Vec<&str> = Vec::new();
+    for (name, _) in quotes.iter().filter_map(|(_, quote)| *quote).flat_map(|quote| &quote.sources) {
+        if !names.contains(&name.as_str()) {
+            names.push(name);
+        }
+    }
+    let mut header = vec!["COIN".to_string()];
+    header.extend(names.iter().map(|name| name.to_uppercase()));
+    header.push("SPREAD".to_string());
+    let mut rows = vec![header];
+    for &(ticker, quote) in quotes {
+        let mut row = vec![ticker.to_string()];
+        for name in &names {
+            let price = quote.and_then(|quote| quote.sources.iter().find(|(source, _)| source == name));
+            row.push(price.map_or_else(|| "-".to_string(), |(_, price)| format_price(*price, vs_currency)));
+        }
+        row.push(quote.and_then(Quote::spread).map_or_else(|| "-".to_string(), |spread| format!("{}%", pct_digits(spread))));
+        rows.push(row);
+    }
+    let widths: Vec<usize> = (0..rows[0].len()).map(|col| rows.iter().map(|row| row[col].chars().count()).max().unwrap_or(0)).collect();
+    let lines: Vec<String> = rows
+        .iter()
+        .map(|row| {
+            let cells: Vec<String> = row.iter().zip(&widths).map(|(cell, &w)| format!("{:<w$}", cell, w = w)).collect();
+            cells.join("  ").trim_end().to_string()
+        })
+        .collect();
+    lines.join("\n")
+}
+
+/// Writes new or reset baselines to the `--state` file, if one is in use.
+fn save_baselines(path: Option<&Path>, state: &mut State, watches: &[Monitored], vs_currency: &str) {
+    let Some(path) = path else {
+        return;
+    };
+    let mut changed = false;
+    for watch in watches {
+        let baseline = Baseline {
+            vs_currency: vs_currency.to_string(),
+            price: watch.initial_price(),
+            set_at: watch.baseline_set_at(),
+        };
+        changed |= state.set_baseline(watch.ticker(), baseline);
+    }
+    if changed {
+        if let Err(e) = state.save(path) {
+            log_warn(&format!("Failed to write state file {}: {}", path.display(), e));
+        }
+    }
+}
+
+/// Starts a watch's rolling windows from `seed`, its past prices oldest
+/// first and then the current one.
+fn seed_windows(watch: &mut Monitored, seed: &[f64], args: &Args) {
+    let mut volatility = RollingWindow::new(args.volatility_window as usize);
+    seed.iter().for_each(|&p| volatility.push(p));
+    watch.volatility = Some(volatility);
+    // Volatile already, from the backfill, doesn't count as rising.
+    watch.volatile = args.volatility_alert.is_some_and(|limit| watch.volatility().is_some_and(|(_, pct)| pct > limit));
+    if let Some(alpha) = args.ema_alpha {
+        let mut ema = Ema::new(alpha);
+        seed.iter().for_each(|&p| {
+            ema.push(p);
+        });
+        watch.ema = Some(ema);
+    }
+    if let Some(size) = args.sparkline {
+        let mut window = RollingWindow::new(size as usize);
+        seed.iter().for_each(|&p| window.push(p));
+        watch.sparkline = Some(window);
+    }
+}
+
+/// A command typed while monitoring.
+#[derive(Debug, PartialEq)]
+enum Command {
+    Pause,
+    Resume,
+    Rearm,
+    /// Start monitoring another coin.
+    Add(NewCoin),
+    Remove(String),
+    List,
+    Quit,
+}
+
+/// A coin typed with `add`, written like a watchlist line, with the
+/// levels when it lists several thresholds and its own drop for a $
+/// change alert if given.
+#[derive(Debug, PartialEq)]
+struct NewCoin {
+    entry: WatchlistEntry,
+    levels: Vec<f64>,
+    down_threshold: Option<f64>,
+}
+
+const COMMAND_HELP: &str = "Type p to pause, r to resume, a to re-arm alerts, add COIN TYPE THRESHOLD (such as \
+                            add sol percent 5, or add btc dollar 500,1000 down 300), remove COIN, list or quit.";
+
+/// Reads one typed command line; `Ok(None)` for a blank one.
+fn parse_command(line: &str) -> Result<Option<Command>, String> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let command = match words.as_slice() {
+        [] => return Ok(None),
+        ["p" | "pause"] => Command::Pause,
+        ["r" | "resume"] => Command::Resume,
+        ["a" | "rearm"] => Command::Rearm,
+        ["add", coin, alert_type, rest @ ..] => {
+            let (threshold, down) = match rest {
+                [] => ("", None),
+                [threshold] => (*threshold, None),
+                [threshold, "down", down] => (*threshold, Some(parse_threshold(down)?)),
+                _ => return Err("add takes a coin, an alert type and a threshold, then down AMOUNT if wanted".to_string()),
+            };
+            let mut levels = if threshold.contains(',') {
+                threshold.split(',').map(parse_threshold).collect::<Result<Vec<_>, _>>()?
+            } else {
+                Vec::new()
+            };
+            levels.sort_by(f64::total_cmp);
+            levels.dedup();
+            let first = levels.first().map_or_else(|| threshold.to_string(), f64::to_string);
+            let entry = watchlist::parse_line(&format!("{},{},{}", coin, alert_type, first))?;
+            if levels.len() > 1 && !matches!(entry.alert_type, AlertType::Dollar | AlertType::Percent) {
+                return Err("several thresholds only work with $ and % change alerts".to_string());
+            }
+            if down.is_some() && entry.alert_type != AlertType::Dollar {
+                return Err("down only works with $ change alerts".to_string());
+            }
+            Command::Add(NewCoin { entry, levels: if levels.len() > 1 { levels } else { Vec::new() }, down_threshold: down })
+        }
+        ["remove" | "rm", coin] => Command::Remove(coin.to_lowercase()),
+        ["remove" | "rm", ..] => return Err("remove takes one coin".to_string()),
+        ["list" | "ls"] => Command::List,
+        ["quit" | "q" | "exit"] => Command::Quit,
+        _ => return Err(format!("unknown command '{}'", line.trim())),
+    };
+    Ok(Some(command))
+}
+
+/// Starts monitoring `coin` from its current price, as if it had been
+/// given at startup with the global settings and the startup `interval`.
+/// Its thresholds are only the ones typed: the startup levels and
+/// `--down-threshold` are in another coin's amounts. Returns the watch, or
+/// why it can't be added.
+async fn new_watch(
+    coin: &NewCoin,
+    watches: &[Monitored],
+    coins: &CoinList,
+    provider: &dyn PriceProvider,
+    settings: &AlertSettings,
+    args: &Args,
+    interval: Duration,
+) -> Result<Monitored, String> {
+    let entry = &coin.entry;
+    if coin.levels.len() > 1 && entry.alert_type == AlertType::Percent && settings.rules.percent_baseline != PercentBaseline::Start {
+        return Err("several thresholds only work with % change alerts measured from the starting price".to_string());
+    }
+    let id = match resolve_tickers(&entry.ticker, coins, false) {
+        Ok(Ok(ids)) => ids.into_iter().next().ok_or_else(|| "add needs a coin".to_string())?,
+        Ok(Err(problem)) => return Err(problem),
+        Err(e) => return Err(e.to_string()),
+    };
+    if watches.iter().any(|watch| watch.ticker() == id) {
+        return Err(format!("{} is already monitored", id));
+    }
+    let quote = provider.fetch(&id).await.map_err(|e| format!("no price for {} ({})", id, e))?;
+    let price = quote.price;
+    if entry.alert_type == AlertType::Percent && !(price > 0.0 && price.is_finite()) {
+        return Err(format!("{}'s price is {}, so a % change can't be measured from it", id, format_price(price, &settings.rules.vs_currency)));
+    }
+    let threshold = entry.threshold.unwrap_or_default();
+    let mut watch =
+        Watch::new(id, entry.alert_type, threshold, price).with_down_threshold(coin.down_threshold.unwrap_or(threshold));
+    if let Some(band) = entry.band {
+        watch = watch.with_band(band);
+    }
+    let mut watch = set_up_watch(watch, &quote, &[price], &coin.levels, &settings.rules, args);
+    watch.interval = interval;
+    watch.next_due = Instant::now() + interval;
+    watch.message = args.message.clone();
+    Ok(watch)
+}
+
+/// Sets up a new watch the same way at startup and for `add`: the
+/// `levels`, the rolling windows seeded with `seed` and, for an alert on a
+/// level, whether `quote` is already past it, so a level already passed
+/// alerts only once crossed again. With a single check in --once mode
+/// there is no previous price to cross from, so there a level alerts
+/// whenever it is breached.
+fn set_up_watch(watch: Watch, quote: &Quote, seed: &[f64], levels: &[f64], rules: &AlertRules, args: &Args) -> Monitored {
+    let (direction, vs_currency) = (rules.direction, &rules.vs_currency);
+    let fmt = |value: f64| format_price(value, vs_currency);
+    let price = quote.price;
+    let mut watch = watch.with_levels(levels);
+    if let Some((low, high)) = watch.band().filter(|_| !args.once) {
+        watch = watch.with_level_breached(price < low || price > high);
+        if watch.level_breached() {
+            log_line(&format!(
+                "{} is already outside {} - {}; it will alert after moving back in and leaving again.",
+                watch.ticker(),
+                fmt(low),
+                fmt(high)
+            ));
+        }
+    }
+    if watch.alert_type() == AlertType::Flat {
+        watch = watch.with_flat_window(Duration::from_secs(args.flat_window));
+    }
+    if watch.alert_type() == AlertType::Sma {
+        watch = watch.with_sma(args.sma_short as usize, args.sma_long as usize, seed);
+    }
+    if watch.alert_type() == AlertType::Target && !args.once {
+        let reached = direction.reached(price, watch.threshold(), rules.tolerance_at(watch.threshold()));
+        watch = watch.with_level_breached(reached);
+        if watch.level_breached() {
+            log_line(&format!(
+                "{} is already {} {}; it will alert after moving back and crossing again.",
+                watch.ticker(),
+                direction,
+                fmt(watch.threshold())
+            ));
+        }
+    }
+    let level = match watch.alert_type() {
+        AlertType::Volume => quote.volume_24h,
+        AlertType::MarketCap => quote.market_cap,
+        _ => None,
+    };
+    if let Some(level) = level.filter(|_| !args.once) {
+        let breached = direction.is_breached(level, watch.threshold());
+        watch = watch.with_level_breached(breached);
+    }
+    let mut watch = Monitored::new(watch);
+    seed_windows(&mut watch, seed, args);
+    watch
+}
+
+/// Logs each monitored coin's alert, latest price and alerts so far, for `list`.
+fn list_watches(watches: &[Monitored], vs_currency: &str) {
+    for watch in watches {
+        log_line(&format!(
+            "  {:<14} {}, {}; {} now, {} alert(s) fired",
+            format!("{}:", watch.ticker()),
+            watch.alert_type(),
+            describe_threshold(watch, vs_currency),
+            format_price(watch.last_price(), vs_currency),
+            watch.alerts_fired
+        ));
+    }
+}
+
+/// Reads commands typed while monitoring, one per line. The reader runs on
+/// its own thread because a blocking stdin read can't be cancelled, and
+/// would otherwise hold up shutdown; the channel closes at end of input.
+fn spawn_command_reader() -> mpsc::UnboundedReceiver<String> {
+    let (sender, receiver) = mpsc::unbounded_channel();
+    std::thread::spawn(move || {
+        for line in io::stdin().lines() {
+            let Ok(line) = line else { break };
+            if sender.send(line).is_err() {
+                break;
+            }
+        }
+    });
+    receiver
+}
+
+/// Sends a message each time the terminal is resized (SIGWINCH), so the
+/// dashboard or table can be redrawn to fit. Off Unix the channel just
+/// closes, and those displays scroll instead of redrawing in place.
+fn spawn_resize_watcher() -> mpsc::UnboundedReceiver<()> {
+    let (sender, receiver) = mpsc::unbounded_channel();
+    #[cfg(unix)]
+    tokio::spawn(async move {
+        use signal::unix::{signal as unix_signal, SignalKind};
+        let Ok(mut resizes) = unix_signal(SignalKind::window_change()) else { return };
+        while resizes.recv().await.is_some() {
+            if sender.send(()).is_err() {
+                break;
+            }
+        }
+    });
+    #[cfg(not(unix))]
+    drop(sender);
+    receiver
+}
+
+/// Each coin's name, symbol and rank, keyed by id. A failed lookup only
+/// costs the banner its details.
+async fn load_coin_info(api: &CoinGecko, ids: &[String], vs_currency: &str) -> HashMap<String, CoinInfo> {
+    match api.fetch_coin_info(ids, vs_currency).await {
+        Ok(infos) => infos.into_iter().map(|info| (info.id.clone(), info)).collect(),
+        Err(e) => {
+            log_warn(&format!("Could not look up coin details ({}); showing ids only.", e));
+            HashMap::new()
+        }
+    }
+}
+
+/// The banner's coin details, e.g. ` (Bitcoin, BTC, rank #1)`.
+fn describe_coin(info: &CoinInfo) -> String {
+    let rank = info.market_cap_rank.map_or_else(String::new, |rank| format!(", rank #{}", rank));
+    format!(" ({}, {}{})", info.name, info.symbol.to_uppercase(), rank)
+}
+
+/// What to try when the startup ping to CoinGecko fails.
+fn connection_hint(error: &FetchError) -> &'static str {
+    match error {
+        FetchError::Proxy(_) => "Check the --proxy URL and that the proxy is running.",
+        FetchError::RateLimited { .. } => {
+            "CoinGecko is rate limiting this address; wait a minute, or use --api-key for a higher limit."
+        }
+        FetchError::ServerError(_) | FetchError::InvalidResponseBody => "CoinGecko may be down; try again later.",
+        FetchError::Reqwest(e) if e.status().is_some() => "If you use --api-key, check the key and its --api-tier.",
+        _ => "Check your internet connection and DNS; behind a proxy, pass it with --proxy.",
+    }
+}
+
+/// Opens an `--output` file, picking the format from its extension.
+fn open_output(path: &Path) -> io::Result<Box<dyn PriceSink>> {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("jsonl" | "ndjson") => Ok(Box::new(JsonLinesSink::open(path)?)),
+        _ => Ok(Box::new(CsvLog::open(path)?)),
+    }
+}
+
+/// Resolves at `deadline`, or never if there isn't one.
+async fn wait_until(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => sleep_until(deadline).await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Resolves on Ctrl-C or, on Unix, SIGTERM (what `systemctl stop` sends),
+/// so both stop through the same summary and cleanup.
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use signal::unix::{signal as unix_signal, SignalKind};
+        match unix_signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                tokio::select! {
+                    _ = signal::ctrl_c() => {}
+                    _ = terminate.recv() => log_line("Received SIGTERM."),
+                }
+                return;
+            }
+            Err(e) => log_warn(&format!("Failed to listen for SIGTERM: {}", e)),
+        }
+    }
+    let _ = signal::ctrl_c().await;
+}
+
+/// Exit status for a failure while running, such as stdin closing mid-prompt.
+const EXIT_RUNTIME: i32 = 1;
+/// Exit status for invalid flags or settings, or an API that can't be
+/// reached at startup. Clap uses the same code for unparseable flags.
+const EXIT_CONFIG: i32 = 2;
+/// Exit status of `--once` when an alert condition was met.
+const EXIT_ALERT: i32 = 3;
+
+/// Why monitoring couldn't start or continue; picks the exit status.
 #[derive(Error, Debug)]
-enum FetchError {
-    #[error("Request error: {0}")]
-    Reqwest(#[from] reqwest::Error),
-    #[error("IO error: {0}")]
-    Io(#[from] io::Error),
-    #[error("Failed to parse price")]
-    ParseError,
+enum RunError {
+    #[error("{0}")]
+    Config(String),
+    #[error("{0}")]
+    Runtime(String),
 }
 
-async fn fetch_prices(ticker: &str) -> Result<CoinGeckoPrice, FetchError> {
-    let api_url = format!("https://api.coingecko.com/api/v3/simple/price?ids={}&vs_currencies=usd", ticker);
-    let response = reqwest::get(&api_url).await?.json::<serde_json::Value>().await?;
+impl RunError {
+    fn config(e: impl fmt::Display) -> Self {
+        RunError::Config(e.to_string())
+    }
 
-    let price = response
-        .get(ticker)
-        .and_then(|c| c.get("usd"))
-        .and_then(|usd| usd.as_f64())
-        .ok_or(FetchError::ParseError)?;
+    fn exit_code(&self) -> i32 {
+        match self {
+            RunError::Config(_) => EXIT_CONFIG,
+            RunError::Runtime(_) => EXIT_RUNTIME,
+        }
+    }
+}
 
-    Ok(CoinGeckoPrice { usd: price })
+impl From<io::Error> for RunError {
+    fn from(e: io::Error) -> Self {
+        RunError::Runtime(format!("Failed to read input: {}", e))
+    }
 }
 
-fn prompt_user(prompt: &str) -> String {
-    print!("{}", prompt);
-    io::stdout().flush().unwrap();
-    let mut input = String::new();
-    io::stdin().read_line(&mut input).unwrap();
-    input.trim().to_string()
+/// Formats a short duration compactly: `12s`, `4m 05s`, `2h 03m`.
+fn format_age(age: Duration) -> String {
+    let secs = age.as_secs();
+    match secs {
+        0..=59 => format!("{}s", secs),
+        60..=3599 => format!("{}m {:02}s", secs / 60, secs % 60),
+        _ => format!("{}h {:02}m", secs / 3600, (secs % 3600) / 60),
+    }
 }
 
-fn prompt_for_f64(prompt: &str) -> f64 {
-    loop {
-        let input = prompt_user(prompt);
-        match input.parse::<f64>() {
-            Ok(value) => return value,
-            Err(_) => println!("Invalid input. Please enter a valid number."),
-        }
+/// Redraws the `--dashboard` line or the table, whichever is in use, after
+/// a check or when the terminal is resized.
+fn show_live_display(watches: &[Monitored], settings: &AlertSettings, dashboard: bool) {
+    if dashboard {
+        output::show_status(&dashboard_line(watches, &settings.rules.vs_currency));
+    }
+    if output::is_table() {
+        output::show_table(&price_table(watches, settings));
     }
 }
 
-fn get_valid_ticker() -> String {
-    let valid_tickers: HashMap<&str, &str> = [
-        ("btc", "bitcoin"),
-        ("bitcoin", "bitcoin"),
-        ("eth", "ethereum"),
-        ("ethereum", "ethereum"),
-        ("ada", "cardano"),
-        ("cardano", "cardano"),
-    ]
+/// The `--dashboard` line: for each coin, `bitcoin $67,200.00 ▲0.80% | low
+/// 66.8k high 68.1k | alerts 2`, measured from its baseline.
+fn dashboard_line(watches: &[Monitored], vs_currency: &str) -> String {
+    let parts: Vec<String> = watches
         .iter()
-        .cloned()
+        .map(|watch| {
+            let pct = percent_change(watch.initial_price(), watch.last_price()).unwrap_or(0.0);
+            let arrow = if pct > 0.0 {
+                "▲".green()
+            } else if pct < 0.0 {
+                "▼".red()
+            } else {
+                "•".normal()
+            };
+            format!(
+                "{} {} {}{}% | low {} high {} | alerts {}",
+                watch.ticker(),
+                format_price(watch.last_price(), vs_currency),
+                arrow,
+                pct_digits(pct),
+                format_compact(watch.min_price()),
+                format_compact(watch.max_price()),
+                watch.alerts_fired
+            )
+        })
         .collect();
+    parts.join("  ||  ")
+}
 
-    loop {
-        let ticker = prompt_user("Enter the cryptocurrency ticker (e.g., btc, eth, ada): ").to_lowercase();
-        if let Some(valid_ticker) = valid_tickers.get(ticker.as_str()) {
-            return valid_ticker.to_string();
-        } else {
-            println!("Invalid ticker. Please enter one of the following: btc, eth, ada.");
+/// The `--format table` screen: a row per coin with its price and change
+/// from the baseline, and the latest alerts under it so a redraw doesn't
+/// lose them. A coin still cooling down from an alert is highlighted.
+fn price_table(watches: &[Monitored], settings: &AlertSettings) -> String {
+    const LATEST_ALERTS: usize = 5;
+    let vs_currency = &settings.rules.vs_currency;
+    let history = settings.history.lock().unwrap();
+    let mut rows = vec![["COIN", "PRICE", "CHANGE", "%CHANGE", "ALERTS", "STATUS"].map(String::from)];
+    for watch in watches {
+        let last = history.events().filter(|event| event.ticker == watch.ticker()).last();
+        let status = match last {
+            Some(event) if watch.in_cooldown(settings.cooldown) => {
+                format!("alerted {}", output::format_time(event.at)).yellow().bold().to_string()
+            }
+            Some(event) => format!("alerted {}", output::format_time(event.at)),
+            None => "armed".to_string(),
+        };
+        rows.push([
+            watch.ticker().to_string(),
+            format_price(watch.last_price(), vs_currency),
+            format_change(watch.last_price() - watch.initial_price(), vs_currency),
+            percent_change(watch.initial_price(), watch.last_price()).map_or_else(|| "-".to_string(), colored_pct),
+            watch.alerts_fired.to_string(),
+            status,
+        ]);
+    }
+    // Widths go by the visible text, since colors add escape codes.
+    let width = |cell: &String| strip_ansi(cell).chars().count();
+    let widths: Vec<usize> = (0..6).map(|col| rows.iter().map(|row| width(&row[col])).max().unwrap_or(0)).collect();
+    let mut table = format!("Prices in {}, updated {}\n\n", vs_currency.to_uppercase(), output::timestamp());
+    for row in &rows {
+        let cells: Vec<String> =
+            row.iter().zip(&widths).map(|(cell, &w)| format!("{}{}", cell, " ".repeat(w - width(cell)))).collect();
+        table.push_str(cells.join("  ").trim_end());
+        table.push('\n');
+    }
+    let skip = history.events().count().saturating_sub(LATEST_ALERTS);
+    if skip < history.events().count() {
+        table.push_str("\nLatest alerts:\n");
+        for event in history.events().skip(skip) {
+            table.push_str(&format!("  {}\n", describe_event(event, vs_currency)));
+        }
+    }
+    table
+}
+
+/// An alert history entry: `2024-05-01 14:03:22 bitcoin dollar at $111.00 (+$11.00)`.
+fn describe_event(event: &AlertEvent, vs_currency: &str) -> String {
+    format!(
+        "{} {} {} at {} ({})",
+        output::format_time(event.at),
+        event.ticker,
+        event.kind,
+        format_price(event.price, vs_currency),
+        format_change(event.change, vs_currency)
+    )
+}
+
+/// The typical time between points in a `market_chart` history.
+fn history_spacing(history: &[(DateTime<Utc>, f64)]) -> Option<Duration> {
+    let ((first, _), (last, _)) = (history.first()?, history.last()?);
+    let points = u32::try_from(history.len() - 1).ok().filter(|&points| points > 0)?;
+    (*last - *first).to_std().ok().map(|span| span / points)
+}
+
+/// Up to the last `keep` prices of `history`, oldest first, taken at least
+/// `step` apart so they line up with the checks that follow. A history
+/// sparser than `step` is used as it is.
+fn resample(history: &[(DateTime<Utc>, f64)], step: Duration, keep: usize) -> Vec<f64> {
+    let step = chrono::Duration::from_std(step).unwrap_or_default();
+    let mut picked = Vec::new();
+    let mut next_at: Option<DateTime<Utc>> = None;
+    // Walked from the newest, which sits just before the first live check.
+    for &(at, price) in history.iter().rev() {
+        if picked.len() == keep {
+            break;
+        }
+        if next_at.is_some_and(|next| at > next) {
+            continue;
+        }
+        picked.push(price);
+        next_at = Some(at - step);
+    }
+    picked.reverse();
+    picked
+}
+
+/// `interval` moved by a random offset within `±jitter` seconds, but never
+/// below the minimum interval.
+fn jittered(interval: Duration, jitter: u64) -> Duration {
+    if jitter == 0 {
+        return interval;
+    }
+    let jitter_ms = jitter.saturating_mul(1000) as i64;
+    let millis = interval.as_millis() as i64 + rand::random_range(-jitter_ms..=jitter_ms);
+    Duration::from_millis(millis.max(MIN_INTERVAL_SECS as i64 * 1000) as u64)
+}
+
+fn print_summary(elapsed: Duration, checks: u64, watches: &[Monitored], history: &AlertHistory, vs_currency: &str) {
+    let fmt = |value: f64| format_price(value, vs_currency);
+    report("");
+    log_line("Monitoring stopped.");
+    report(&format!("Total monitored time: {} ({} price checks)", format_elapsed(elapsed), checks));
+    for watch in watches {
+        report(&format!(
+            "{}: low {}, high {}, avg {}, {} alert(s) fired",
+            watch.ticker(), fmt(watch.min_price()), fmt(watch.max_price()), fmt(watch.avg_price()), watch.alerts_fired
+        ));
+    }
+    let total_alerts: u32 = watches.iter().map(|w| w.alerts_fired).sum();
+    report(&format!("Total alerts fired: {}", total_alerts));
+    if history.events().next().is_some() {
+        report("Alerts:");
+        if history.dropped() > 0 {
+            report(&format!("  ({} earlier alert(s) not kept; see --alert-history)", history.dropped()));
+        }
+    }
+    for event in history.events() {
+        report(&format!("  {}", describe_event(event, vs_currency)));
+    }
+}
+
+/// The `--summary-json` form of `print_summary`.
+fn run_summary<'a>(
+    elapsed: Duration,
+    checks: u64,
+    watches: &'a [Monitored],
+    history: &'a AlertHistory,
+    vs_currency: &'a str,
+) -> RunSummary<'a> {
+    let mut alerts_by_type = BTreeMap::new();
+    for watch in watches {
+        *alerts_by_type.entry(watch.alert_type().name()).or_insert(0) +=
+            watch.alerts_fired - watch.volatility_alerts - watch.ema_alerts - watch.spread_alerts;
+        if watch.volatility_alerts > 0 {
+            *alerts_by_type.entry("volatility").or_insert(0) += watch.volatility_alerts;
+        }
+        if watch.ema_alerts > 0 {
+            *alerts_by_type.entry("ema").or_insert(0) += watch.ema_alerts;
+        }
+        if watch.spread_alerts > 0 {
+            *alerts_by_type.entry("spread").or_insert(0) += watch.spread_alerts;
         }
     }
+    RunSummary {
+        duration_secs: elapsed.as_secs(),
+        checks,
+        vs_currency,
+        alerts: watches.iter().map(|w| w.alerts_fired).sum(),
+        alerts_by_type,
+        coins: watches
+            .iter()
+            .map(|watch| CoinSummary {
+                ticker: watch.ticker(),
+                alert_type: watch.alert_type().name(),
+                min_price: watch.min_price(),
+                max_price: watch.max_price(),
+                avg_price: watch.avg_price(),
+                alerts: watch.alerts_fired,
+            })
+            .collect(),
+        alert_history: history.events().collect(),
+    }
 }
 
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let ticker = get_valid_ticker();
-    let alert_type = prompt_user("Do you want to set an alert based on (1) $ change or (2) % change? Enter 1 or 2: ");
-    let threshold = prompt_for_f64("Enter the threshold value: ");
+async fn main() {
+    let args = Args::parse();
+    output::set_utc(args.utc);
+    output::set_color(!args.no_color);
+    output::set_format(args.format);
+    output::set_quiet(args.quiet);
+    output::set_dashboard(args.dashboard);
+    format::set_number_format(NumberFormat {
+        // Depegs play out in fractions of a cent, which two decimals would hide.
+        decimals: args.decimals.or(args.depeg.as_ref().map(|_| 4)),
+        full_amounts: args.verbose,
+        compact_numbers: args.compact_numbers,
+        currency_codes: args.coalesce_currency_symbol,
+        locale: format::system_locale(),
+    });
+    if let Err(e) = logging::init(args.log_file.as_deref(), args.verbose, env!("CARGO_CRATE_NAME")) {
+        eprintln!("Failed to open log file: {}", e);
+        std::process::exit(EXIT_CONFIG);
+    }
+
+    if let Err(e) = run(args).await {
+        log_error(&e.to_string());
+        std::process::exit(e.exit_code());
+    }
+}
+
+async fn run(args: Args) -> Result<(), RunError> {
+    let mut args = args;
+    apply_env_fallbacks(&mut args, |name| std::env::var(name).ok())?;
+    if args.sound.is_some() && !sound::SUPPORTED {
+        return Err(RunError::config("--sound needs sound support; rebuild with `cargo build --features sound`."));
+    }
+    if args.sma_short >= args.sma_long {
+        return Err(RunError::config("--sma-short must be smaller than --sma-long."));
+    }
+    if let Some(ticks) = args.self_test {
+        return self_test(ticks);
+    }
+    let mailer = match (&args.email_to, &args.smtp_host) {
+        (Some(to), Some(host)) => Some(
+            Mailer::new(&EmailConfig {
+                to,
+                from: args.email_from.as_deref(),
+                host,
+                port: args.smtp_port,
+                user: args.smtp_user.as_deref(),
+                password: args.smtp_password.as_deref(),
+                min_gap: Duration::from_secs(args.email_gap),
+            })
+            .map_err(RunError::Config)?,
+        ),
+        _ => None,
+    };
+
+    let config_path = args.config.clone().or_else(config::default_config_path);
+    let config = match &config_path {
+        Some(path) if path.exists() => match config::load_config(path) {
+            Ok(config) => {
+                log_line(&format!("Loaded settings from {}", path.display()));
+                config
+            }
+            Err(e) => {
+                log_warn(&format!("Ignoring {}: {}", path.display(), e));
+                Config::default()
+            }
+        },
+        _ => Config::default(),
+    };
+
+    // Flags (or their ALERT_* variables) take precedence over the config
+    // file; anything still missing is prompted for.
+    let mut prompted = false;
+
+    let contract = match &args.contract {
+        Some(address) => {
+            let address = provider::parse_contract(&args.platform, address).map_err(RunError::Config)?;
+            let label = args.label.clone().unwrap_or_else(|| provider::contract_label(&address));
+            Some((address, label))
+        }
+        None => None,
+    };
+
+    let client = api::build_client(Duration::from_secs(args.timeout), args.proxy.as_deref())
+        .map_err(|e| RunError::Config(format!("Failed to set up the HTTP client: {}", e)))?;
+    let coin_cache = coins::default_cache_path().map(|path| Cache::new(path, coins::CACHE_TTL));
+    let api_key = args.api_key.clone().map(|key| ApiKey { tier: args.api_tier, key });
+    let min_gap = Duration::from_millis(args.min_request_gap);
+    rate_limit::set_requests_per_minute(args.max_requests_per_minute);
+    rate_limit::set_max_concurrent_fetches(args.max_concurrent_fetches as usize);
+    let coingecko = CoinGecko::new(client.clone(), api_key, min_gap).with_base_url(args.base_url.clone());
+    // Shows a network or key problem up front, before the coin list and
+    // first fetch fail with it less clearly.
+    if args.replay.is_none() {
+        match coingecko.ping().await {
+            Ok(greeting) if args.verbose => log_line(&format!("CoinGecko is reachable: {}", greeting)),
+            Ok(_) => {}
+            Err(e) => log_warn(&format!("Could not reach CoinGecko ({}). {}", e, connection_hint(&e))),
+        }
+    }
+    let coins = coins::load_coin_list(&coingecko, coin_cache.as_ref()).await;
+    if args.list_coins {
+        print_coin_list(&coins);
+        return Ok(());
+    }
+    if let Some(path) = &args.check_config {
+        return check_config(path, &coins);
+    }
+
+    let nft = match &args.nft {
+        Some(id) => {
+            let id = id.trim().to_lowercase();
+            if args.replay.is_none() {
+                check_nft_id(&coingecko, &id).await?;
+            }
+            let label = format!("{} floor", id);
+            Some((id, label))
+        }
+        None => None,
+    };
+    let watchlist = match &args.watchlist {
+        Some(path) => Some(load_watchlist(path, &coins)?),
+        None => None,
+    };
+    // Holdings of the same coin, under two tickers, are added together.
+    let mut holdings: Vec<(String, f64)> = Vec::new();
+    for (ticker, amount) in args.holdings.iter().flatten() {
+        let id = match resolve_tickers(ticker, &coins, false)? {
+            Ok(ids) if !ids.is_empty() => ids[0].clone(),
+            Ok(_) => return Err(RunError::config("--holdings needs at least one coin.")),
+            Err(problem) => return Err(RunError::Config(format!("--holdings: {}", problem))),
+        };
+        match holdings.iter_mut().find(|(held, _)| *held == id) {
+            Some((_, held)) => *held += amount,
+            None => holdings.push((id, *amount)),
+        }
+    }
+    let pair = match &args.pair {
+        Some((base, quote)) => match resolve_tickers(&format!("{},{}", base, quote), &coins, false)? {
+            Ok(ids) if ids.len() == 2 => Some((ids[0].clone(), ids[1].clone())),
+            Ok(_) => return Err(RunError::config("--pair needs two different coins.")),
+            Err(problem) => return Err(RunError::Config(format!("--pair: {}", problem))),
+        },
+        None => None,
+    };
+
+    // Bad values given as flags are fatal; bad values from the config file
+    // are asked for again.
+    let tickers = if let Some((_, label)) = contract.as_ref().or(nft.as_ref()) {
+        vec![label.clone()]
+    } else if let Some((base, quote)) = &pair {
+        vec![format!("{}/{}", base, quote)]
+    } else if let Some(entries) = &watchlist {
+        entries.iter().map(|entry| entry.ticker.clone()).collect()
+    } else if !holdings.is_empty() {
+        vec![PORTFOLIO_LABEL.to_string()]
+    } else if let Some(coin) = &args.depeg {
+        match resolve_tickers(coin, &coins, false)? {
+            Ok(tickers) if !tickers.is_empty() => tickers,
+            Ok(_) => return Err(RunError::config("--depeg needs a coin.")),
+            Err(problem) => return Err(RunError::Config(format!("--depeg: {}", problem))),
+        }
+    } else if let Some(flag) = &args.ticker {
+        match resolve_tickers(&flag.join(","), &coins, false)? {
+            Ok(tickers) if !tickers.is_empty() => tickers,
+            Ok(_) => return Err(RunError::config("--ticker needs at least one coin.")),
+            Err(problem) => return Err(RunError::Config(format!("--ticker: {}", problem))),
+        }
+    } else if let Some(piped) = read_piped_tickers()? {
+        // Whitespace separates coins too, so `echo btc eth` works.
+        match resolve_tickers(&piped.split_whitespace().collect::<Vec<_>>().join(","), &coins, false)? {
+            Ok(tickers) if !tickers.is_empty() => tickers,
+            Ok(_) => return Err(RunError::config("The tickers piped to stdin named no coin.")),
+            Err(problem) => return Err(RunError::Config(format!("Tickers from stdin: {}", problem))),
+        }
+    } else {
+        let from_config = match &config.ticker {
+            Some(tickers) => resolve_tickers(&tickers.join(","), &coins, true)?,
+            None => Ok(Vec::new()),
+        };
+        match from_config {
+            Ok(tickers) if !tickers.is_empty() => tickers,
+            Err(problem) => {
+                println!("{}", problem);
+                prompted = true;
+                get_valid_tickers(&coins)?
+            }
+            Ok(_) => {
+                prompted = true;
+                get_valid_tickers(&coins)?
+            }
+        }
+    };
+    if args.starting_price.is_some() && tickers.len() > 1 {
+        return Err(RunError::config("--starting-price only works with a single coin."));
+    }
+    let vs_currency = match (args.currency.as_deref(), config.vs_currency.as_deref().map(parse_currency)) {
+        // The peg is to the dollar, whatever the config file says.
+        (Some(flag), _) if args.depeg.is_some() && parse_currency(flag).as_deref() != Some("usd") => {
+            return Err(RunError::config("--depeg watches a $1.00 peg, so it only works with --currency usd."));
+        }
+        (None, _) if args.depeg.is_some() => "usd".to_string(),
+        (Some(flag), _) => parse_currency(flag).ok_or_else(|| RunError::Config(format!("Unsupported currency '{}'.", flag)))?,
+        (None, Some(Some(currency))) => currency,
+        (None, Some(None)) => {
+            println!("Invalid currency.");
+            prompted = true;
+            get_valid_currency()?
+        }
+        (None, None) => {
+            prompted = true;
+            get_valid_currency()?
+        }
+    };
+    // A ratio is counted in units of the quote coin, so it is shown and
+    // alerted on without a currency symbol; both coins are still fetched
+    // in the chosen currency.
+    let fetch_currency = vs_currency.clone();
+    let vs_currency = match &pair {
+        Some((_, quote)) => quote.clone(),
+        None => vs_currency,
+    };
+    let symbol = currency_symbol(&vs_currency);
+    let fmt = |value: f64| format_price(value, &vs_currency);
+    let saved_alert_type = config
+        .alert_type
+        .as_deref()
+        .map(str::parse)
+        .transpose()
+        .map_err(|e| RunError::Config(format!("Config file: {}.", e)))?;
+    // A watchlist sets the type per coin; otherwise every coin shares one.
+    let alert_types: HashMap<String, AlertType> = match &watchlist {
+        Some(entries) => entries.iter().map(|entry| (entry.ticker.clone(), entry.alert_type)).collect(),
+        None => {
+            let depeg = args.depeg.as_ref().map(|_| AlertType::Band);
+            let alert_type = match depeg.or(args.alert_type).or(saved_alert_type) {
+                Some(alert_type) => alert_type,
+                None => {
+                    prompted = true;
+                    prompt_for_alert_type(&symbol)?
+                }
+            };
+            tickers.iter().map(|ticker| (ticker.clone(), alert_type)).collect()
+        }
+    };
+    let uses = |wanted: &[AlertType]| alert_types.values().any(|alert_type| wanted.contains(alert_type));
+    let has_direction = uses(&[AlertType::Target, AlertType::Volume, AlertType::MarketCap]);
+
+    let direction = match args.direction.or(config.direction) {
+        Some(direction) => direction,
+        None if has_direction => {
+            prompted = true;
+            prompt_for_direction()?
+        }
+        None => Direction::Above,
+    };
+    let percent_baseline = match args.percent_baseline.or(config.percent_baseline) {
+        Some(percent_baseline) => percent_baseline,
+        None if uses(&[AlertType::Percent]) => {
+            prompted = true;
+            prompt_for_percent_baseline()?
+        }
+        None => PercentBaseline::Start,
+    };
+    let trailing = match args.trailing.or(config.trailing) {
+        Some(trailing) => trailing,
+        None if uses(&[AlertType::Trailing]) => {
+            prompted = true;
+            prompt_for_trailing_mode()?
+        }
+        None => TrailingMode::TrailingStop,
+    };
+
+    let mut levels = args.threshold.clone().unwrap_or_default();
+    levels.sort_by(f64::total_cmp);
+    levels.dedup();
+    let layerable = |alert_type: &AlertType| match alert_type {
+        AlertType::Dollar => true,
+        AlertType::Percent => percent_baseline == PercentBaseline::Start,
+        _ => false,
+    };
+    if levels.len() > 1 && !alert_types.values().all(layerable) {
+        return Err(RunError::config(
+            "Several thresholds only work with $ change alerts and % change alerts measured from the starting price",
+        ));
+    }
+    let default_threshold = levels.first().copied().or(config.threshold.filter(|&t| t > 0.0));
+    let default_down_threshold = args.down_threshold.or(config.down_threshold.filter(|&t| t > 0.0));
+    let depeg_band = match &args.depeg {
+        Some(_) => {
+            let pct = levels.first().copied().unwrap_or(DEFAULT_DEPEG_PCT);
+            if pct >= 100.0 {
+                return Err(RunError::config("--depeg needs a --threshold below 100%."));
+            }
+            Some((1.0 - pct / 100.0, 1.0 + pct / 100.0))
+        }
+        None => None,
+    };
+    let default_band = depeg_band.or(args.band).or(config.band.filter(|&(low, high)| low > 0.0 && low < high));
+    let mut thresholds = HashMap::new();
+    let mut down_thresholds = HashMap::new();
+    let mut bands = HashMap::new();
+    for ticker in &tickers {
+        let alert_type = alert_types[ticker];
+        if let Some(entry) = watchlist.iter().flatten().find(|entry| &entry.ticker == ticker) {
+            // Crossovers compare two averages and have no threshold.
+            let threshold = entry.threshold.unwrap_or(0.0);
+            thresholds.insert(ticker.clone(), threshold);
+            down_thresholds.insert(ticker.clone(), threshold);
+            if let Some(band) = entry.band {
+                bands.insert(ticker.clone(), band);
+            }
+            continue;
+        }
+        if alert_type == AlertType::Band {
+            let (low, high) = match default_band {
+                Some(band) => band,
+                None => {
+                    prompted = true;
+                    prompt_for_band(ticker)?
+                }
+            };
+            thresholds.insert(ticker.clone(), low);
+            down_thresholds.insert(ticker.clone(), low);
+            bands.insert(ticker.clone(), (low, high));
+            continue;
+        }
+        let threshold = match default_threshold {
+            Some(threshold) => threshold,
+            // Crossovers compare two averages and have no threshold.
+            None if alert_type == AlertType::Sma => 0.0,
+            None => {
+                prompted = true;
+                if alert_type == AlertType::Target {
+                    prompt_for_threshold(&format!("Enter the target price for {}: ", ticker), false)
+                } else if alert_type == AlertType::Trailing && trailing == TrailingMode::TrailingBuy {
+                    prompt_for_threshold(&format!("Enter the % rise from the low to alert on for {}: ", ticker), true)
+                } else if alert_type == AlertType::Trailing {
+                    prompt_for_threshold(&format!("Enter the trailing stop percentage for {}: ", ticker), true)
+                } else if alert_type == AlertType::Volume {
+                    prompt_for_threshold(&format!("Enter the 24h volume ({}) to alert at for {}: ", vs_currency, ticker), false)
+                } else if alert_type == AlertType::MarketCap {
+                    prompt_for_threshold(&format!("Enter the market cap ({}) to alert at for {}: ", vs_currency, ticker), false)
+                } else if alert_type == AlertType::Flat {
+                    prompt_for_threshold(&format!("Enter the largest % spread that counts as flat for {}: ", ticker), true)
+                } else if alert_type == AlertType::Change24h {
+                    prompt_for_threshold(&format!("Enter the 24h % change to alert on for {}: ", ticker), true)
+                } else if alert_type == AlertType::Range24h {
+                    prompt_for_threshold(&format!("Enter how close (%) to the 24h high or low to alert for {}: ", ticker), true)
+                } else if alert_type == AlertType::Dollar {
+                    prompt_for_threshold(&format!("Enter the rise threshold for {}: ", ticker), false)
+                } else {
+                    prompt_for_threshold(&format!("Enter the threshold value for {}: ", ticker), alert_type == AlertType::Percent)
+                }?
+            }
+        };
+        let down_threshold = match default_down_threshold {
+            Some(down) => down,
+            None if alert_type == AlertType::Dollar && default_threshold.is_none() => prompt_for_down_threshold(ticker, threshold, &vs_currency)?,
+            None => threshold,
+        };
+        thresholds.insert(ticker.clone(), threshold);
+        down_thresholds.insert(ticker.clone(), down_threshold);
+    }
+    let interval = match args.interval.or(config.interval.filter(|&secs| secs >= MIN_INTERVAL_SECS)) {
+        Some(secs) => Duration::from_secs(secs),
+        None => {
+            prompted = true;
+            prompt_for_interval()?
+        }
+    };
+
+    if prompted {
+        if let Some(path) = &config_path {
+            let uniform_band = uniform_threshold(&bands);
+            let config = Config {
+                // The watchlist stays the place its coins and thresholds are kept.
+                ticker: (contract.is_none() && nft.is_none() && watchlist.is_none()).then(|| tickers.clone()),
+                alert_type: uniform_threshold(&alert_types)
+                    .filter(|_| watchlist.is_none())
+                    .map(|alert_type| alert_type.name().to_string()),
+                direction: has_direction.then_some(direction),
+                percent_baseline: uses(&[AlertType::Percent]).then_some(percent_baseline),
+                trailing: uses(&[AlertType::Trailing]).then_some(trailing),
+                threshold: uniform_threshold(&thresholds)
+                    .filter(|_| levels.len() < 2 && watchlist.is_none() && !uses(&[AlertType::Band])),
+                down_threshold: uniform_threshold(&down_thresholds).filter(|&down| Some(down) != uniform_threshold(&thresholds)),
+                band: uniform_band.filter(|_| watchlist.is_none()),
+                interval: Some(interval.as_secs()),
+                vs_currency: Some(fetch_currency.clone()),
+            };
+            offer_to_save_config(path, &config)?;
+        }
+    }
+
+    let coingecko_only = contract.is_some() || nft.is_some();
+    // These are monitored through a provider made for them, which can't
+    // take on other coins while running.
+    let coins_fixed = coingecko_only || pair.is_some() || !holdings.is_empty() || args.replay.is_some();
+    // A pair, portfolio or replay makes up its own prices, and a token or
+    // NFT is only on CoinGecko, so there is nothing to compare.
+    let compare = args.compare && !coins_fixed;
+    if args.compare && !compare {
+        log_warn("--compare compares exchanges' prices for whole coins, so it is ignored here.");
+    }
+    // Comparing one provider with itself would show nothing.
+    let provider_kinds =
+        if compare && args.provider.len() < 2 { ProviderKind::value_variants().to_vec() } else { args.provider.clone() };
+    let aggregate = args.aggregate.or(compare.then_some(Aggregate::Median));
+    let coingecko_prices = args.replay.is_none() && (coingecko_only || provider_kinds.contains(&ProviderKind::Coingecko));
+    if aggregate.is_some() && provider_kinds.len() < 2 && args.replay.is_none() && !coingecko_only {
+        log_warn("--aggregate combines several --provider sources, but only one was given, so it is used alone.");
+    }
+    if !args.extra_currencies.is_empty() && !coingecko_prices {
+        log_warn("--extra-currencies only works with CoinGecko prices, so only the main currency will be shown.");
+    }
+    if coingecko_prices {
+        let mut currencies = vec![fetch_currency.as_str()];
+        currencies.extend(args.extra_currencies.iter().map(String::as_str));
+        check_currencies(&coingecko, &currencies).await?;
+    }
+    let price_source = match (&args.replay, &contract, &nft) {
+        (Some(path), _, _) => format!("replay of {}", path.display()),
+        (None, Some((address, _)), _) => format!("coingecko ({} token {})", args.platform, address),
+        (None, None, Some((id, _))) => format!("coingecko (NFT floor of {})", id),
+        (None, None, None) => {
+            let names: Vec<&str> = provider_kinds.iter().map(|kind| kind.name()).collect();
+            match aggregate.filter(|_| names.len() > 1) {
+                Some(method) if compare => format!("{}, compared; alerts go by the {:?}", names.join(", "), method).to_lowercase(),
+                Some(method) => format!("the {:?} of {}", method, names.join(", ")).to_lowercase(),
+                None => names.join(", falling back to "),
+            }
+        }
+    };
+    let provider: Box<dyn PriceProvider> = match &args.replay {
+        Some(path) => {
+            Box::new(ReplayProvider::from_file(path).map_err(|e| RunError::Config(format!("Failed to read {}: {}", path.display(), e)))?)
+        }
+        None => match (contract, nft) {
+            (Some((address, label)), _) => {
+                Box::new(TokenProvider::new(coingecko.clone(), &args.platform, address, label, &vs_currency, &args.extra_currencies))
+            }
+            (None, Some((id, label))) => Box::new(NftProvider::new(coingecko.clone(), id, label, &vs_currency)),
+            (None, None) => {
+                let providers = ProviderKind::build_all(
+                    &provider_kinds,
+                    &coingecko,
+                    client.clone(),
+                    &fetch_currency,
+                    &args.extra_currencies,
+                    min_gap,
+                    uses(&[AlertType::Range24h]),
+                );
+                let chain = provider::chain(providers, aggregate);
+                match &pair {
+                    Some((base, quote)) => Box::new(RatioProvider::new(chain, base.clone(), quote.clone(), tickers[0].clone())),
+                    None if !holdings.is_empty() => Box::new(PortfolioProvider::new(chain, holdings.clone(), tickers[0].clone())),
+                    None => chain,
+                }
+            }
+        },
+    };
+    // A replay plays back as fast as it can rather than waiting out the interval.
+    let interval = if args.replay.is_some() { Duration::ZERO } else { interval };
+    let mut state = match &args.state {
+        Some(path) => State::load(path).unwrap_or_else(|e| {
+            log_warn(&format!("Ignoring state file {}: {}", path.display(), e));
+            State::default()
+        }),
+        None => State::default(),
+    };
+
+    // Coins with a saved baseline don't need a startup fetch, except in
+    // --once mode where that fetch is the check itself.
+    let to_fetch: Vec<String> = tickers
+        .iter()
+        .filter(|t| {
+            args.once || (args.starting_price.is_none() && (args.baseline_date.is_some() || state.baseline(t, &vs_currency).is_none()))
+        })
+        .cloned()
+        .collect();
+    let price_cache = provider::default_price_cache_path()
+        .filter(|_| args.replay.is_none() && args.price_cache_ttl > 0)
+        .map(|path| Cache::new(path, Duration::from_secs(args.price_cache_ttl)));
+    let cached_prices = price_cache
+        .as_ref()
+        .and_then(Cache::get::<CachedQuotes>)
+        .and_then(|cached| cached.covering(&to_fetch, &vs_currency));
+    let initial_prices = if to_fetch.is_empty() {
+        PriceBatch::new()
+    } else if let Some(prices) = cached_prices {
+        log_line(&format!("Using prices fetched less than {}s ago.", args.price_cache_ttl));
+        prices
+    } else {
+        // The network may not be up yet when started at boot, so the first
+        // fetch gets the same retries as every later one.
+        let prices = fetch_with_retry(provider.as_ref(), &to_fetch, args.max_retries)
+            .await
+            .map_err(|e| RunError::Config(format!("Could not fetch starting prices: {}", e)))?;
+        if let Some(cache) = &price_cache {
+            if let Err(e) = cache.put(&CachedQuotes::new(&prices, &vs_currency)) {
+                log_warn(&format!("Could not cache prices to {}: {}", cache.path().display(), e));
+            }
+        }
+        prices
+    };
+
+    // Looked up once; the details only appear in the startup banner.
+    let coin_info = if args.coin_info && args.contract.is_none() && args.nft.is_none() && pair.is_none() && holdings.is_empty() && args.replay.is_none() {
+        load_coin_info(&coingecko, &tickers, &vs_currency).await
+    } else {
+        HashMap::new()
+    };
+
+    let mut historical = HashMap::new();
+    if let Some(date) = args.baseline_date {
+        for ticker in &tickers {
+            match coingecko.fetch_historical_price(ticker, date, &vs_currency).await {
+                Ok(price) => {
+                    historical.insert(ticker.clone(), price);
+                }
+                Err(e) => log_warn(&format!("Could not fetch the {} price of {} ({}).", date, ticker, e)),
+            }
+        }
+    }
+
+    // Enough past prices to fill the longest window; an EMA has mostly
+    // forgotten its start after 3 / alpha prices.
+    let backfill_len = [
+        Some(args.volatility_window as usize),
+        uses(&[AlertType::Sma]).then_some(args.sma_long as usize),
+        args.sparkline.map(|size| size as usize),
+        args.ema_alpha.map(|alpha| (3.0 / alpha).ceil() as usize),
+    ]
+    .into_iter()
+    .flatten()
+    .max()
+    .unwrap_or_default();
+    let mut backfill = HashMap::new();
+    if args.backfill {
+        for ticker in &tickers {
+            match coingecko.fetch_market_chart(ticker, &vs_currency, 1).await {
+                Ok(history) => {
+                    let spacing = history_spacing(&history);
+                    if spacing.is_some_and(|spacing| spacing > interval) {
+                        log_line(&format!(
+                            "CoinGecko's {} history comes about every {}, less often than the {}s interval, so the \
+                             backfilled part of each window spans more time than live checks would.",
+                            ticker,
+                            format_age(spacing.unwrap_or_default()),
+                            interval.as_secs()
+                        ));
+                    }
+                    let prices = resample(&history, interval, backfill_len.saturating_sub(1));
+                    log_line(&format!("Backfilled {} with {} prices from the last 24h.", ticker, prices.len()));
+                    backfill.insert(ticker.clone(), prices);
+                }
+                Err(e) => {
+                    log_warn(&format!("Could not fetch recent {} prices to backfill with ({}); its windows start empty.", ticker, e))
+                }
+            }
+        }
+    }
+
+    let rules = AlertRules {
+        direction,
+        percent_baseline,
+        trailing,
+        flat_window: Duration::from_secs(args.flat_window),
+        vs_currency: vs_currency.clone(),
+        strict_threshold: args.strict_threshold,
+        target_tolerance: args.target_tolerance,
+        format: format::number_format(),
+    };
+    let mut watches = Vec::new();
+    let mut current_prices = HashMap::new();
+    for ticker in &tickers {
+        let alert_type = alert_types[ticker];
+        let fetched = match initial_prices.get(ticker) {
+            Some(Ok(quote)) => Some(quote.clone()),
+            Some(Err(e)) => {
+                log_warn(&format!("No price available for {} ({}).", ticker, e));
+                None
+            }
+            None => None,
+        };
+        let saved = state.baseline(ticker, &vs_currency).cloned();
+        let baseline = match (&saved, &fetched) {
+            _ if args.baseline_date.is_some() => historical.get(ticker).copied().filter(|_| fetched.is_some()),
+            (_, None) if args.once => None,
+            _ if args.starting_price.is_some() => args.starting_price,
+            (Some(saved), _) => Some(saved.price),
+            (None, fetched) => fetched.as_ref().map(|q| q.price),
+        };
+        let Some(baseline) = baseline else {
+            log_warn(&format!("Skipping {}: no price available.", ticker));
+            continue;
+        };
+        // Bad data or a delisted coin can report 0, and every % change from
+        // that would be infinite.
+        let bad_baseline = !(baseline > 0.0 && baseline.is_finite());
+        if bad_baseline && alert_type == AlertType::Percent && percent_baseline == PercentBaseline::Start {
+            log_warn(&format!(
+                "Skipping {}: its baseline price is {}, so a % change can't be measured from it.",
+                ticker,
+                fmt(baseline)
+            ));
+            continue;
+        }
+        let quote = fetched.unwrap_or_else(|| Quote::price(baseline));
+        let price = quote.price;
+
+        if !args.once && !output::is_json() {
+            let details = coin_info.get(ticker).map_or_else(String::new, describe_coin);
+            match (&saved, args.baseline_date) {
+                _ if args.starting_price.is_some() => log_line(&format!(
+                    "Monitoring {}{} price. Starting price: {} (set with --starting-price, not fetched)",
+                    ticker,
+                    details,
+                    fmt(baseline)
+                )),
+                (_, Some(date)) => log_line(&format!(
+                    "Monitoring {}{} price. Baseline: {} on {}; current price: {}",
+                    ticker,
+                    details,
+                    fmt(baseline),
+                    date,
+                    fmt(price)
+                )),
+                (Some(saved), None) => log_line(&format!(
+                    "Monitoring {}{} price. Saved baseline: {} (set {})",
+                    ticker,
+                    details,
+                    fmt(saved.price),
+                    saved.set_at.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M:%S")
+                )),
+                (None, None) => log_line(&format!("Monitoring {}{} price. Initial price: {}", ticker, details, fmt(price))),
+            }
+            if let Some((base, quote)) = &pair {
+                log_line(&format!(
+                    "{} is a ratio, not a {} price: how many {} one {} is worth, from their {} prices.",
+                    ticker, fetch_currency, quote, base, fetch_currency
+                ));
+            }
+            if !holdings.is_empty() {
+                let held: Vec<String> = holdings.iter().map(|(coin, amount)| format!("{} {}", amount, coin)).collect();
+                log_line(&format!("The {} is the total value of {}.", ticker, held.join(", ")));
+            }
+        }
+        // From a past date the session's low, high and average still start
+        // from today's price.
+        let (session_start, set_at) = match (args.baseline_date, &saved) {
+            (Some(date), _) => (price, Some(date.and_time(NaiveTime::MIN).and_utc())),
+            (None, Some(saved)) => (baseline, Some(saved.set_at)),
+            (None, None) => (baseline, None),
+        };
+        let mut watch = Watch::new(ticker.clone(), alert_type, thresholds[ticker], session_start)
+            .with_down_threshold(down_thresholds[ticker]);
+        if let Some(set_at) = set_at {
+            watch = watch.with_baseline(baseline, set_at);
+        }
+        if let Some(&band) = bands.get(ticker) {
+            watch = watch.with_band(band);
+        }
+        // Backfilled prices go in first, oldest first, then today's.
+        let seed: Vec<f64> = backfill.get(ticker).into_iter().flatten().copied().chain([price]).collect();
+        let mut watch = set_up_watch(watch, &quote, &seed, &levels, &rules, &args);
+        let entry = watchlist.iter().flatten().find(|entry| &entry.ticker == ticker);
+        // A replay ignores intervals, so it ignores the per-coin ones too.
+        let own_interval = entry.and_then(|entry| entry.interval);
+        watch.interval = own_interval.filter(|_| !interval.is_zero()).map_or(interval, Duration::from_secs);
+        watch.message = entry.and_then(|entry| entry.message.clone()).or_else(|| args.message.clone());
+        current_prices.insert(ticker.clone(), quote);
+        watches.push(watch);
+    }
+
+    if watches.is_empty() {
+        return Err(RunError::config("None of the requested coins returned a price."));
+    }
+    save_baselines(args.state.as_deref(), &mut state, &watches, &vs_currency);
+
+    let mut sinks: Vec<Box<dyn PriceSink>> = vec![Box::new(ConsoleSink)];
+    if output::is_json() {
+        sinks.push(Box::new(JsonLinesSink::stdout()));
+    }
+    for path in &args.output {
+        let sink = open_output(path).map_err(|e| RunError::Config(format!("Failed to open {}: {}", path.display(), e)))?;
+        sinks.push(match args.min_change_to_log {
+            Some(min_change_24h) => Box::new(Min24hChangeSink { inner: sink, min_change_24h }),
+            None => sink,
+        });
+    }
+    let db = match &args.db {
+        Some(path) => {
+            let db = Database::open(path, &vs_currency)
+                .map_err(|e| RunError::Config(format!("Failed to open the database {}: {}", path.display(), e)))?;
+            let db = Arc::new(db);
+            sinks.push(Box::new(DbSink(db.clone())));
+            Some(db)
+        }
+        None => None,
+    };
+
+    let telegram = match (&args.telegram_token, &args.telegram_chat_id) {
+        (Some(token), Some(chat_id)) => Some(TelegramSink::new(token.clone(), chat_id.clone())),
+        _ => None,
+    };
+    let metrics = match args.metrics_port {
+        Some(port) => {
+            let address = SocketAddr::new(args.metrics_address, port);
+            let listener = tokio::net::TcpListener::bind(address)
+                .await
+                .map_err(|e| RunError::Config(format!("Could not serve metrics on {}: {}", address, e)))?;
+            log_line(&format!("Serving Prometheus metrics at http://{}/metrics.", address));
+            let metrics = Arc::new(Metrics::new(&vs_currency));
+            tokio::spawn(metrics::serve(listener, metrics.clone()));
+            Some(metrics)
+        }
+        None => None,
+    };
+    let settings = AlertSettings {
+        rules,
+        cooldown: Duration::from_secs(args.cooldown),
+        reset_baseline: args.reset_baseline,
+        stale_after: args.stale_after,
+        max_price_age: Duration::from_secs(args.max_price_age),
+        started: Instant::now(),
+        extra_currencies: args.extra_currencies.clone(),
+        volatility_alert: args.volatility_alert,
+        ema_alert: args.ema_alert,
+        compare,
+        spread_alert: args.spread_alert.filter(|_| compare),
+        diff_only: args.diff_only,
+        remind_every: args.remind_every.map(Duration::from_secs),
+        confirm_cycles: args.confirm_cycles,
+        active_days: args.active_days,
+        notifier: Notifier::new(client.clone(), !args.no_desktop, args.webhook.clone())
+            .with_audio(!args.no_bell, args.sound.clone())
+            .with_email(mailer)
+            .with_telegram(telegram)
+            .with_command(args.on_alert.clone())
+            .with_quiet_hours(args.quiet_hours, args.quiet_hours_summary)
+            .with_batch_window(Duration::from_secs(args.batch_window)),
+        history: Mutex::new(AlertHistory::new(args.alert_history)),
+        metrics,
+        db,
+    };
+
+    if !args.once && !args.quiet && !output::is_json() {
+        log_settings(&watches, &settings, &price_source, interval);
+    }
 
-    let initial_price = fetch_prices(&ticker).await?.usd;
+    if args.once {
+        let mut any_fired = false;
+        for watch in &mut watches {
+            let quote = &current_prices[watch.ticker()];
+            any_fired |= check_price(watch, quote, &settings, &mut sinks).await;
+        }
+        let quotes: Vec<_> = watches.iter().map(|watch| (watch.ticker(), current_prices.get(watch.ticker()))).collect();
+        show_comparison(&quotes, &settings);
+        settings.notifier.release_batch().await;
+        save_baselines(args.state.as_deref(), &mut state, &watches, &vs_currency);
+        if let Some(db) = &settings.db {
+            db.close();
+        }
+        std::process::exit(if any_fired { EXIT_ALERT } else { 0 });
+    }
+
+    if args.notify_lifecycle {
+        let coins: Vec<&str> = watches.iter().map(|watch| watch.ticker()).collect();
+        let every = if interval.is_zero() { String::new() } else { format!(", checking every {}s", interval.as_secs()) };
+        let body = format!("Monitoring started for {}{}.", coins.join(", "), every);
+        settings.notifier.send_notice("Crypto price monitor started", &body).await;
+    }
+
+    let start = settings.started;
+    let deadline = args.max_duration.map(|secs| start + Duration::from_secs(secs));
+    let mut checks: u64 = 0;
+    let mut cycles: u64 = 0;
 
-    println!("Monitoring {} price. Initial price: ${:.2}", ticker, initial_price);
+    let mut commands = spawn_command_reader();
+    let mut commands_open = true;
+    let mut resizes = spawn_resize_watcher();
+    let mut resizes_open = args.dashboard || output::is_table();
+    let mut paused = false;
+    // Whether the last check overran the interval, so the warning isn't repeated every check.
+    let mut falling_behind = false;
+    // Failed checks in a row; at --failures-before-backoff the loop backs off.
+    let mut failed_checks: u32 = 0;
+    let backoff = Duration::from_secs(args.backoff_interval);
+    // Each coin keeps its own schedule; backing off or a Retry-After holds
+    // them all back until this time.
+    let mut hold_until = Instant::now();
+    for watch in &mut watches {
+        watch.next_due = hold_until + jittered(watch.interval, args.jitter);
+    }
+    // Scrapes before the first check still see the starting prices.
+    if let Some(metrics) = &settings.metrics {
+        for watch in &watches {
+            metrics.set_coin(watch.ticker(), watch.last_price(), 0);
+        }
+    }
+    // Made once, so a signal that arrives mid-check still stops the loop.
+    let shutdown = shutdown_signal();
+    tokio::pin!(shutdown);
+    if args.dashboard {
+        output::show_status(&dashboard_line(&watches, &vs_currency));
+    }
 
     loop {
-        sleep(Duration::from_secs(30)).await;
-
-        match fetch_prices(&ticker).await {
-            Ok(current_price) => {
-                println!("Current {} price: ${:.2}", ticker, current_price.usd);
-                let price_change = current_price.usd - initial_price;
-                let percent_change = (price_change / initial_price) * 100.0;
-
-                match alert_type.as_str() {
-                    "1" => {
-                        if price_change.abs() >= threshold {
-                            println!(
-                                "Alert! {} price changed by ${:.2}. Current price: ${:.2}",
-                                ticker, price_change, current_price.usd
-                            );
+        if args.max_checks.is_some_and(|max| cycles >= max) {
+            log_line("Reached the maximum number of checks.");
+            break;
+        }
+        if provider.exhausted() {
+            log_line("Replay finished.");
+            break;
+        }
+
+        let next_check = watches.iter().map(|watch| watch.next_due).min().map_or(hold_until, |due| due.max(hold_until));
+        tokio::select! {
+            _ = sleep_until(next_check) => {}
+            _ = wait_until(deadline) => {
+                log_line("Reached the maximum monitoring duration.");
+                break;
+            }
+            _ = &mut shutdown => break,
+            _ = wait_until(settings.notifier.batch_due()) => {
+                settings.notifier.release_batch().await;
+                continue;
+            }
+            command = commands.recv(), if commands_open => {
+                let Some(line) = command else {
+                    commands_open = false;
+                    continue;
+                };
+                match parse_command(&line) {
+                    Ok(Some(Command::Pause)) if !paused => {
+                        paused = true;
+                        log_line("Paused; no prices will be fetched. Type r and press enter to resume.");
+                    }
+                    Ok(Some(Command::Resume)) if paused => {
+                        paused = false;
+                        log_line("Resumed.");
+                    }
+                    Ok(Some(Command::Rearm)) => {
+                        let rearmed: Vec<&str> = watches
+                            .iter_mut()
+                            .filter_map(|watch| watch.rearm(settings.cooldown).then_some(watch.ticker()))
+                            .collect();
+                        if rearmed.is_empty() {
+                            log_line("No alerts to re-arm.");
+                        } else {
+                            log_line(&format!("Re-armed alerts for {}.", rearmed.join(", ")));
+                        }
+                    }
+                    Ok(Some(Command::Add(_))) if coins_fixed => {
+                        log_line("Coins can only be added when monitoring by ticker or watchlist.");
+                    }
+                    Ok(Some(Command::Add(coin))) => {
+                        // The price fetch can wait on the request budget, so a signal doesn't wait for it.
+                        let adding = new_watch(&coin, &watches, &coins, provider.as_ref(), &settings, &args, interval);
+                        let added = tokio::select! {
+                            added = adding => added,
+                            _ = &mut shutdown => break,
+                        };
+                        match added {
+                            Ok(watch) => {
+                                log_line(&format!(
+                                    "Now monitoring {}: {}, {} from {}.",
+                                    watch.ticker(),
+                                    watch.alert_type(),
+                                    describe_threshold(&watch, &vs_currency),
+                                    format_price(watch.last_price(), &vs_currency)
+                                ));
+                                if let Some(metrics) = &settings.metrics {
+                                    metrics.set_coin(watch.ticker(), watch.last_price(), 0);
+                                }
+                                watches.push(watch);
+                                show_live_display(&watches, &settings, args.dashboard);
+                            }
+                            Err(problem) => log_line(&format!("Could not add it: {}.", problem)),
                         }
                     }
-                    "2" => {
-                        if percent_change.abs() >= threshold {
-                            println!(
-                                "Alert! {} price changed by {:.2}%. Current price: ${:.2}",
-                                ticker, percent_change, current_price.usd
-                            );
+                    Ok(Some(Command::Remove(coin))) => {
+                        let id = match resolve_tickers(&coin, &coins, false) {
+                            Ok(Ok(ids)) => ids.into_iter().next().unwrap_or(coin),
+                            _ => coin,
+                        };
+                        match watches.iter().position(|watch| watch.ticker() == id) {
+                            None => log_line(&format!("{} isn't being monitored.", id)),
+                            Some(_) if watches.len() == 1 => {
+                                log_line("That's the only coin being monitored; type quit to stop instead.")
+                            }
+                            Some(index) => {
+                                let watch = watches.remove(index);
+                                log_line(&format!("Stopped monitoring {} ({} alert(s) fired).", watch.ticker(), watch.alerts_fired));
+                                if let Some(metrics) = &settings.metrics {
+                                    metrics.remove_coin(watch.ticker());
+                                }
+                                show_live_display(&watches, &settings, args.dashboard);
+                            }
                         }
                     }
-                    _ => println!("Invalid alert type."),
+                    Ok(Some(Command::List)) => list_watches(&watches, &vs_currency),
+                    Ok(Some(Command::Quit)) => break,
+                    Ok(Some(Command::Pause | Command::Resume) | None) => {}
+                    Err(problem) => log_line(&format!("Could not run that: {}. {}", problem, COMMAND_HELP)),
+                }
+                continue;
+            }
+            resize = resizes.recv(), if resizes_open => {
+                match resize {
+                    Some(()) => show_live_display(&watches, &settings, args.dashboard),
+                    None => resizes_open = false,
+                }
+                continue;
+            }
+        }
+        // Scheduled from the start of the cycle, so the time spent fetching
+        // comes out of the wait rather than pushing every later check back.
+        let cycle_start = Instant::now();
+        let mut due = Vec::new();
+        for watch in watches.iter_mut().filter(|watch| watch.next_due <= cycle_start) {
+            watch.next_due = cycle_start + jittered(watch.interval, args.jitter);
+            due.push(watch);
+        }
+        if paused {
+            continue;
+        }
+
+        // Before this check's alerts, so the summary reads in order.
+        settings.notifier.release_held().await;
+        cycles += 1;
+        // A check can wait a long time on retries or the request budget, so
+        // a signal stops it rather than waiting for it to finish.
+        let result = tokio::select! {
+            result = run_check(provider.as_ref(), due, &settings, &mut sinks, args.max_retries) => result,
+            _ = &mut shutdown => break,
+        };
+        let limit = args.failures_before_backoff;
+        match result {
+            Ok(_) => {
+                checks += 1;
+                if limit > 0 && failed_checks >= limit {
+                    log_line(&format!("Prices are coming through again; back to checking every {}s.", interval.as_secs()));
+                }
+                failed_checks = 0;
+            }
+            Err(e) => {
+                failed_checks += 1;
+                if let Some(metrics) = &settings.metrics {
+                    metrics.add_fetch_error();
+                }
+                let backing_off = limit > 0 && failed_checks >= limit;
+                // Once backed off, one message covers the outage rather than
+                // a warning per probe.
+                if !backing_off || failed_checks == limit {
+                    log_warn(&format!("Error fetching prices: {}", e));
+                } else {
+                    tracing::debug!("Provider still failing: {}", e);
+                }
+                if backing_off && failed_checks == limit {
+                    log_warn(&format!(
+                        "The price provider appears to be down ({} failed checks in a row); backing off to one \
+                         check every {}s until it recovers.",
+                        failed_checks,
+                        backoff.as_secs()
+                    ));
                 }
+                if backing_off {
+                    hold_until = cycle_start + backoff;
+                }
+                // Honour a Retry-After longer than the interval rather than
+                // getting rate limited again on the next check.
+                if let Some(wait) = e.retry_after().filter(|wait| *wait > interval && Instant::now() + *wait > hold_until) {
+                    log_line(&format!("Waiting {}s before the next check, as the API asked.", wait.as_secs()));
+                    hold_until = Instant::now() + wait;
+                }
+            }
+        }
+        let work = cycle_start.elapsed();
+        let behind = !interval.is_zero() && work > interval;
+        if behind && !falling_behind {
+            log_warn(&format!(
+                "That check took {:.1}s, longer than the {}s interval, so checks are running late. \
+                 Try a longer --interval or fewer coins.",
+                work.as_secs_f64(),
+                interval.as_secs()
+            ));
+        }
+        falling_behind = behind;
+        save_baselines(args.state.as_deref(), &mut state, &watches, &vs_currency);
+        show_live_display(&watches, &settings, args.dashboard);
+    }
+
+    output::end_status();
+    settings.notifier.release_batch().await;
+    if args.notify_lifecycle {
+        let alerts: u32 = watches.iter().map(|watch| watch.alerts_fired).sum();
+        let body = format!(
+            "Monitoring stopped after {} and {} check(s); {} alert(s) fired.",
+            format_elapsed(start.elapsed()),
+            checks,
+            alerts
+        );
+        settings.notifier.send_notice("Crypto price monitor stopped", &body).await;
+    }
+    let history = settings.history.lock().unwrap();
+    print_summary(start.elapsed(), checks, &watches, &history, &vs_currency);
+    if let Some(path) = &args.summary_json {
+        let summary = run_summary(start.elapsed(), checks, &watches, &history, &vs_currency);
+        if let Err(e) = output::write_summary(path, &summary) {
+            log_warn(&format!("Failed to write the summary to {}: {}", path.display(), e));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use std::collections::VecDeque;
+
+    /// Returns scripted prices, one per fetch, for a single coin.
+    struct MockProvider {
+        prices: Mutex<VecDeque<f64>>,
+    }
+
+    impl MockProvider {
+        fn new(prices: &[f64]) -> Self {
+            MockProvider { prices: Mutex::new(prices.iter().copied().collect()) }
+        }
+    }
+
+    #[async_trait]
+    impl PriceProvider for MockProvider {
+        async fn fetch(&self, _ticker: &str) -> Result<Quote, FetchError> {
+            self.prices.lock().unwrap().pop_front().map(Quote::price).ok_or(FetchError::PriceMissing)
+        }
+    }
+
+    fn settings(cooldown_secs: u64) -> AlertSettings {
+        AlertSettings {
+            rules: AlertRules {
+                direction: Direction::Above,
+                percent_baseline: PercentBaseline::Start,
+                trailing: TrailingMode::TrailingStop,
+                flat_window: Duration::from_secs(600),
+                vs_currency: "usd".to_string(),
+                strict_threshold: false,
+                target_tolerance: None,
+                format: NumberFormat::PLAIN,
+            },
+            cooldown: Duration::from_secs(cooldown_secs),
+            reset_baseline: false,
+            stale_after: 0,
+            max_price_age: Duration::ZERO,
+            started: Instant::now(),
+            extra_currencies: Vec::new(),
+            volatility_alert: None,
+            ema_alert: false,
+            compare: false,
+            spread_alert: None,
+            diff_only: None,
+            remind_every: None,
+            confirm_cycles: 1,
+            active_days: None,
+            notifier: Notifier::new(reqwest::Client::new(), false, None),
+            history: Mutex::new(AlertHistory::new(10)),
+            metrics: None,
+            db: None,
+        }
+    }
+
+    /// Runs one check per scripted price and returns how many alerts fired each time.
+    async fn replay(watch: Watch, settings: &AlertSettings, prices: &[f64]) -> Vec<u32> {
+        replay_every(&mut Monitored::new(watch), settings, prices, Duration::ZERO).await
+    }
+
+    /// `replay` with `gap` between the checks, on a paused clock, leaving
+    /// the watch to look at afterwards.
+    async fn replay_every(watch: &mut Monitored, settings: &AlertSettings, prices: &[f64], gap: Duration) -> Vec<u32> {
+        let provider = MockProvider::new(prices);
+        let mut fired = Vec::new();
+        for i in 0..prices.len() {
+            if i > 0 && !gap.is_zero() {
+                tokio::time::advance(gap).await;
             }
-            Err(e) => println!("Error fetching prices: {}", e),
+            fired.push(run_check(&provider, vec![&mut *watch], settings, &mut [], 0).await.unwrap());
+        }
+        fired
+    }
+
+    #[test]
+    fn parse_number_accepts_common_formats() {
+        assert_eq!(parse_number("1,5"), Some(1.5));
+        assert_eq!(parse_number("$1,500"), Some(1500.0));
+        assert_eq!(parse_number("1.234.567,89"), Some(1234567.89));
+        assert_eq!(parse_number("2,000,000.5"), Some(2000000.5));
+        assert_eq!(parse_number(" 2.5% "), Some(2.5));
+        assert_eq!(parse_number("abc"), None);
+        assert_eq!(parse_number(""), None);
+    }
+
+    #[test]
+    fn the_dashboard_shows_small_moves() {
+        let mut watch = Watch::new("usd-coin".into(), AlertType::Percent, 1.0, 1.0);
+        watch.end_check(1.0004);
+        colored::control::set_override(false);
+        let line = dashboard_line(&[Monitored::new(watch)], "usd");
+        colored::control::unset_override();
+        assert!(line.contains("▲0.04%"), "{}", line);
+    }
+
+    #[test]
+    fn the_self_test_walk_lands_on_its_thresholds() {
+        let walk = self_test_walk(AlertType::Dollar, 200, 2000);
+        assert!(walk.exact > 0 && walk.alerts.len() > walk.exact);
+        assert_eq!(walk.prices[0], 100.0);
+        assert!(self_test(2000).is_ok());
+    }
+
+    #[test]
+    fn thresholds_must_be_finite_and_positive() {
+        assert_eq!(parse_threshold("2.5"), Ok(2.5));
+        assert!(parse_threshold("0").is_err());
+        assert!(parse_threshold("inf").is_err());
+        assert!(parse_threshold("NaN").is_err());
+    }
+
+    #[test]
+    fn jitter_stays_within_range_and_above_the_minimum() {
+        let interval = Duration::from_secs(30);
+        assert_eq!(jittered(interval, 0), interval);
+        for _ in 0..100 {
+            let wait = jittered(interval, 5);
+            assert!(wait >= Duration::from_secs(25) && wait <= Duration::from_secs(35));
+            assert!(jittered(Duration::from_secs(MIN_INTERVAL_SECS), 60) >= Duration::from_secs(MIN_INTERVAL_SECS));
+        }
+    }
+
+    #[tokio::test]
+    async fn alerts_wait_for_the_condition_to_hold_for_confirm_cycles() {
+        let mut settings = settings(0);
+        settings.confirm_cycles = 3;
+        let watch = Watch::new("bitcoin".into(), AlertType::Dollar, 100.0, 1000.0);
+        // A one-check spike resets the count; three checks in a row alert.
+        let fired = replay(watch, &settings, &[1150.0, 1050.0, 1100.0, 1120.0, 1130.0, 1140.0]).await;
+        assert_eq!(fired, vec![0, 0, 0, 0, 1, 1]);
+
+        // A crossing alerts once, on the check that confirms it.
+        let watch = Watch::new("bitcoin".into(), AlertType::Target, 1100.0, 1000.0);
+        let fired = replay(watch, &settings, &[1150.0, 1150.0, 1150.0, 1150.0, 1000.0]).await;
+        assert_eq!(fired, vec![0, 0, 1, 0, 0]);
+    }
+
+    #[tokio::test]
+    async fn every_output_file_gets_each_price_check() {
+        let settings = settings(0);
+        let dir = std::env::temp_dir();
+        let csv = dir.join(format!("crypto_alerts_sink_test_{}.csv", std::process::id()));
+        let jsonl = dir.join(format!("crypto_alerts_sink_test_{}.jsonl", std::process::id()));
+        let mut sinks = vec![open_output(&csv).unwrap(), open_output(&jsonl).unwrap()];
+        let mut watch = Monitored::new(Watch::new("bitcoin".into(), AlertType::Target, 110.0, 100.0));
+        for price in [105.0, 112.0] {
+            check_price(&mut watch, &Quote::price(price), &settings, &mut sinks).await;
         }
+        drop(sinks);
+        let rows = std::fs::read_to_string(&csv).unwrap();
+        assert_eq!(rows.lines().count(), 3);
+        assert!(rows.lines().last().unwrap().ends_with(",bitcoin,112.0,12.0,12.0,true"));
+        let lines = std::fs::read_to_string(&jsonl).unwrap();
+        assert_eq!(lines.lines().count(), 2);
+        assert!(lines.lines().all(|line| serde_json::from_str::<serde_json::Value>(line).is_ok()));
+        std::fs::remove_file(&csv).unwrap();
+        std::fs::remove_file(&jsonl).unwrap();
+    }
+
+    #[test]
+    fn backfill_is_resampled_to_the_interval() {
+        let start = Utc::now();
+        let minutes = |m: i64| start + chrono::Duration::minutes(m);
+        let history: Vec<(DateTime<Utc>, f64)> = (0..10).map(|m| (minutes(m), m as f64)).collect();
+        assert_eq!(resample(&history, Duration::from_secs(180), 10), [0.0, 3.0, 6.0, 9.0]);
+        assert_eq!(resample(&history, Duration::from_secs(180), 2), [6.0, 9.0]);
+        // History sparser than the interval is used point for point.
+        assert_eq!(resample(&history, Duration::from_secs(30), 3), [7.0, 8.0, 9.0]);
+        assert_eq!(history_spacing(&history), Some(Duration::from_secs(60)));
+        assert_eq!(history_spacing(&history[..1]), None);
+    }
+
+    #[test]
+    fn run_summary_counts_alerts_by_type() {
+        let mut btc = Monitored::new(Watch::new("bitcoin".into(), AlertType::Dollar, 10.0, 100.0));
+        btc.alerts_fired = 3;
+        btc.volatility_alerts = 1;
+        let mut eth = Monitored::new(Watch::new("ethereum".into(), AlertType::Target, 3000.0, 2900.0));
+        eth.alerts_fired = 1;
+        let sol = Monitored::new(Watch::new("solana".into(), AlertType::Dollar, 5.0, 150.0));
+        let watches = [btc, eth, sol];
+        let mut history = AlertHistory::new(10);
+        history.push(AlertEvent { at: Utc::now(), ticker: "ethereum".into(), kind: "target", price: 3010.0, change: 110.0 });
+        let summary = run_summary(Duration::from_secs(90), 12, &watches, &history, "usd");
+        assert_eq!(summary.alerts, 4);
+        let by_type: Vec<(&str, u32)> = summary.alerts_by_type.into_iter().collect();
+        assert_eq!(by_type, [("dollar", 2), ("target", 1), ("volatility", 1)]);
+        assert_eq!(summary.coins[2].ticker, "solana");
+        assert_eq!(summary.alert_history[0].ticker, "ethereum");
+    }
+
+    #[test]
+    fn typed_commands_parse_into_watch_changes() {
+        let Ok(Some(Command::Add(coin))) = parse_command("add SOL percent 5") else { panic!("add didn't parse") };
+        let entry = &coin.entry;
+        assert_eq!((entry.ticker.as_str(), entry.alert_type, entry.threshold), ("sol", AlertType::Percent, Some(5.0)));
+        assert!(coin.levels.is_empty() && coin.down_threshold.is_none());
+        let Ok(Some(Command::Add(coin))) = parse_command("add ada band 0.4-0.6") else { panic!("band didn't parse") };
+        assert_eq!(coin.entry.band, Some((0.4, 0.6)));
+        let Ok(Some(Command::Add(coin))) = parse_command("add btc dollar 1000,500 down 300") else { panic!("levels didn't parse") };
+        assert_eq!((coin.entry.threshold, coin.levels, coin.down_threshold), (Some(500.0), vec![500.0, 1000.0], Some(300.0)));
+        assert_eq!(parse_command("  rm ETH "), Ok(Some(Command::Remove("eth".into()))));
+        assert_eq!(parse_command("list"), Ok(Some(Command::List)));
+        assert_eq!(parse_command("p"), Ok(Some(Command::Pause)));
+        assert_eq!(parse_command(""), Ok(None));
+        assert!(parse_command("add sol percent").is_err());
+        assert!(parse_command("add sol moon 5").is_err());
+        assert!(parse_command("add sol target 5,10").is_err());
+        assert!(parse_command("add sol percent 5 down 2").is_err());
+        assert!(parse_command("remove").is_err());
+        assert!(parse_command("dance").is_err());
+    }
+
+    #[test]
+    fn environment_fallbacks_give_way_to_conflicting_flags() {
+        let env = |name: &str| match name {
+            "ALERT_TYPE" => Some("dollar".to_string()),
+            "ALERT_TICKER" => Some("btc,eth".to_string()),
+            _ => None,
+        };
+        let mut args = Args::try_parse_from(["cryptoPriceTracker", "--watchlist", "coins.txt", "--once"]).unwrap();
+        apply_env_fallbacks(&mut args, env).unwrap();
+        assert_eq!((args.alert_type, args.ticker), (None, None));
+
+        let mut args = Args::try_parse_from(["cryptoPriceTracker", "--threshold", "50"]).unwrap();
+        apply_env_fallbacks(&mut args, env).unwrap();
+        assert_eq!(args.alert_type, Some(AlertType::Dollar));
+        assert_eq!(args.ticker, Some(vec!["btc".to_string(), "eth".to_string()]));
+        assert!(apply_env_fallbacks(&mut Args::try_parse_from(["cryptoPriceTracker"]).unwrap(), |_| Some("0".into())).is_err());
+    }
+
+    #[tokio::test]
+    async fn added_coins_keep_the_thresholds_typed_for_them() {
+        // The startup levels and drop are for other coins, so an added one doesn't take them.
+        let args = Args::try_parse_from(["cryptoPriceTracker", "--threshold", "500,1000", "--down-threshold", "25"]).unwrap();
+        let add = |command| match parse_command(command) {
+            Ok(Some(Command::Add(coin))) => coin,
+            _ => panic!("{} didn't parse", command),
+        };
+        let (coins, settings) = (CoinList::fallback(), settings(0));
+        let provider = MockProvider::new(&[100.0, 100.0, 100.0, 100.0]);
+        let interval = Duration::from_secs(60);
+        let (btc, eth) = (add("add btc dollar 200"), add("add eth percent 5"));
+        let watch = new_watch(&btc, &[], &coins, &provider, &settings, &args, interval).await.unwrap();
+        assert_eq!((watch.threshold(), watch.down_threshold(), watch.interval), (200.0, 200.0, interval));
+        assert!(watch.levels().is_empty());
+        let watch = new_watch(&eth, &[], &coins, &provider, &settings, &args, interval).await.unwrap();
+        assert_eq!(watch.threshold(), 5.0);
+        assert!(watch.levels().is_empty());
+
+        let (layered, ada) = (add("add eth dollar 10,20 down 5"), add("add ada target 90"));
+        let watch = new_watch(&layered, &[], &coins, &provider, &settings, &args, interval).await.unwrap();
+        assert_eq!((watch.threshold(), watch.down_threshold()), (10.0, 5.0));
+        assert_eq!(watch.levels().iter().map(|level| level.value).collect::<Vec<_>>(), [10.0, 20.0]);
+        // Already past its target, so it waits to be crossed again.
+        let watch = new_watch(&ada, &[], &coins, &provider, &settings, &args, interval).await.unwrap();
+        assert!(watch.level_breached() && watch.levels().is_empty());
+    }
+
+    #[test]
+    fn exchange_prices_line_up_in_the_comparison_table() {
+        let mut btc = Quote::price(100.5);
+        btc.sources = vec![("coingecko".to_string(), 100.0), ("binance".to_string(), 101.0)];
+        let mut sol = Quote::price(150.0);
+        sol.sources = vec![("coingecko".to_string(), 150.0)];
+        let (bitcoin, solana, ripple) = ("bitcoin".to_string(), "solana".to_string(), "ripple".to_string());
+        let table = comparison_table(&[(&bitcoin, Some(&btc)), (&solana, Some(&sol)), (&ripple, None)], "usd");
+        assert_eq!(
+            table.lines().collect::<Vec<_>>(),
+            [
+                "COIN     COINGECKO  BINANCE  SPREAD",
+                "bitcoin  $100.00    $101.00  1.00%",
+                "solana   $150.00    -        -",
+                "ripple   -          -        -",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn cooldown_suppresses_repeat_alerts() {
+        let watch = Watch::new("bitcoin".into(), AlertType::Dollar, 10.0, 100.0);
+        let fired = replay(watch, &settings(300), &[120.0, 130.0, 140.0]).await;
+        assert_eq!(fired, vec![1, 0, 0]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn held_conditions_remind_until_they_lapse() {
+        let settings = AlertSettings { remind_every: Some(Duration::from_secs(60)), ..settings(0) };
+        let start = Instant::now();
+        let mut watch = Monitored::new(Watch::new("bitcoin".into(), AlertType::Target, 110.0, 100.0));
+        let prices = [120.0, 121.0, 122.0, 90.0, 90.0, 90.0];
+        let fired = replay_every(&mut watch, &settings, &prices, Duration::from_secs(30)).await;
+        assert_eq!(fired, vec![1, 0, 0, 0, 0, 0]);
+        // Reminded at 60s, a minute after the alert; none after the drop at 90s.
+        assert_eq!(watch.reminded_at, Some(start + Duration::from_secs(60)));
+        assert_eq!(watch.triggered_at, None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn crossings_held_back_by_the_cooldown_alert_once_it_ends() {
+        let mut watch = Monitored::new(Watch::new("bitcoin".into(), AlertType::Target, 110.0, 100.0));
+        // Past, back and past again inside the 60s cooldown, then still past once it is over.
+        let fired = replay_every(&mut watch, &settings(60), &[120.0, 100.0, 115.0, 116.0], Duration::from_secs(20)).await;
+        assert_eq!(fired, vec![1, 0, 0, 1]);
     }
-}
\ No newline at end of file
+}
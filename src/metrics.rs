@@ -0,0 +1,124 @@
+use http_body_util::Full;
+use hyper::body::{Bytes, Incoming};
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{header, Method, Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+use tokio::net::TcpListener;
+use tokio::time::{sleep, Duration};
+
+/// The latest prices and running totals, as served by `--metrics-port`.
+pub struct Metrics {
+    vs_currency: String,
+    values: Mutex<Values>,
+}
+
+#[derive(Default)]
+struct Values {
+    /// Each coin's latest price and the alerts it has fired, by ticker.
+    coins: BTreeMap<String, (f64, u64)>,
+    fetch_errors: u64,
+}
+
+impl Metrics {
+    pub fn new(vs_currency: &str) -> Self {
+        Metrics { vs_currency: vs_currency.to_string(), values: Mutex::new(Values::default()) }
+    }
+
+    /// Records a coin's latest price and how many alerts it has fired in all.
+    pub fn set_coin(&self, ticker: &str, price: f64, alerts: u64) {
+        self.values.lock().unwrap().coins.insert(ticker.to_string(), (price, alerts));
+    }
+
+    /// Stops reporting a coin that is no longer monitored.
+    pub fn remove_coin(&self, ticker: &str) {
+        self.values.lock().unwrap().coins.remove(ticker);
+    }
+
+    /// Counts a check, or one coin in a check, that got no price.
+    pub fn add_fetch_error(&self) {
+        self.values.lock().unwrap().fetch_errors += 1;
+    }
+
+    /// The metrics in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let values = self.values.lock().unwrap();
+        let mut text = String::new();
+        text.push_str("# HELP crypto_price Latest price of the coin.\n# TYPE crypto_price gauge\n");
+        for (ticker, (price, _)) in &values.coins {
+            let _ = writeln!(text, "crypto_price{{ticker=\"{}\",currency=\"{}\"}} {}", escape(ticker), self.vs_currency, price);
+        }
+        text.push_str("# HELP crypto_alerts_total Alerts fired since monitoring began.\n# TYPE crypto_alerts_total counter\n");
+        for (ticker, (_, alerts)) in &values.coins {
+            let _ = writeln!(text, "crypto_alerts_total{{ticker=\"{}\"}} {}", escape(ticker), alerts);
+        }
+        text.push_str("# HELP crypto_fetch_errors_total Checks or coins that got no price.\n");
+        text.push_str("# TYPE crypto_fetch_errors_total counter\n");
+        let _ = writeln!(text, "crypto_fetch_errors_total {}", values.fetch_errors);
+        text
+    }
+}
+
+/// A label value with the characters the text format reserves escaped.
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+/// Serves `metrics` at `/metrics` to every connection on `listener`, until
+/// the task is dropped. Meant to be spawned, so scrapes never hold up a check.
+pub async fn serve(listener: TcpListener, metrics: Arc<Metrics>) {
+    loop {
+        let stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(e) => {
+                // Usually out of file descriptors; give some a chance to close.
+                tracing::debug!("Failed to accept a metrics connection: {}", e);
+                sleep(Duration::from_millis(100)).await;
+                continue;
+            }
+        };
+        let metrics = metrics.clone();
+        tokio::spawn(async move {
+            let service = service_fn(move |request| {
+                let response = respond(&request, &metrics);
+                async move { Ok::<_, Infallible>(response) }
+            });
+            if let Err(e) = http1::Builder::new().serve_connection(TokioIo::new(stream), service).await {
+                tracing::debug!("Metrics connection failed: {}", e);
+            }
+        });
+    }
+}
+
+fn respond(request: &Request<Incoming>, metrics: &Metrics) -> Response<Full<Bytes>> {
+    let (status, content_type, body) = match (request.method(), request.uri().path()) {
+        (&Method::GET, "/metrics") => (StatusCode::OK, "text/plain; version=0.0.4", metrics.render()),
+        _ => (StatusCode::NOT_FOUND, "text/plain", "Not found; the metrics are at /metrics\n".to_string()),
+    };
+    let mut response = Response::new(Full::new(Bytes::from(body)));
+    *response.status_mut() = status;
+    response.headers_mut().insert(header::CONTENT_TYPE, header::HeaderValue::from_static(content_type));
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn metrics_render_in_the_text_format() {
+        let metrics = Metrics::new("usd");
+        metrics.set_coin("bitcoin", 67200.5, 2);
+        metrics.set_coin("eth/\"btc\"", 0.05, 0);
+        metrics.add_fetch_error();
+        let text = metrics.render();
+        assert!(text.contains("\ncrypto_price{ticker=\"bitcoin\",currency=\"usd\"} 67200.5\n"));
+        assert!(text.contains("\ncrypto_alerts_total{ticker=\"eth/\\\"btc\\\"\"} 0\n"));
+        assert!(text.ends_with("\ncrypto_fetch_errors_total 1\n"));
+        assert!(text.contains("# TYPE crypto_alerts_total counter\n"));
+    }
+}
@@ -0,0 +1,387 @@
+use crate::email::Mailer;
+use crate::format::strip_ansi;
+use crate::output::{log_line, log_warn};
+use crate::sound;
+use chrono::{Local, NaiveTime};
+use notify_rust::Notification;
+use reqwest::Client;
+use serde_json::json;
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::str::FromStr;
+use std::sync::Mutex;
+use tokio::process::Command;
+use tokio::time::{Duration, Instant};
+
+/// A daily local-time window, such as `22:00-07:00`, in which alerts are
+/// not pushed anywhere. The window may cross midnight.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QuietHours {
+    start: NaiveTime,
+    end: NaiveTime,
+}
+
+impl QuietHours {
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+}
+
+impl FromStr for QuietHours {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let expected = || format!("expected HH:MM-HH:MM, such as 22:00-07:00, not '{}'", input);
+        let (start, end) = input.split_once('-').ok_or_else(expected)?;
+        let parse = |time: &str| NaiveTime::parse_from_str(time.trim(), "%H:%M").map_err(|_| expected());
+        let (start, end) = (parse(start)?, parse(end)?);
+        if start == end {
+            return Err("quiet hours must start and end at different times".to_string());
+        }
+        Ok(QuietHours { start, end })
+    }
+}
+
+/// Delivers alerts beyond the console: the terminal bell or a sound file,
+/// desktop popups, an optional chat webhook, Telegram bot and email.
+/// Delivery failures are logged and never stop monitoring. During quiet
+/// hours only the bell or sound goes off; pushed channels stay silent.
+/// With a batch window, alerts that fire close together are pushed as one.
+pub struct Notifier {
+    client: Client,
+    bell: bool,
+    sound: Option<PathBuf>,
+    desktop: bool,
+    webhook: Option<String>,
+    email: Option<Mailer>,
+    telegram: Option<TelegramSink>,
+    /// The `--on-alert` shell command.
+    command: Option<String>,
+    quiet_hours: Option<QuietHours>,
+    /// Alerts held back during quiet hours, to summarize once they end;
+    /// `None` when they are simply dropped.
+    held: Option<Mutex<Vec<String>>>,
+    /// How long the first alert of a batch waits for others to join it;
+    /// zero pushes every alert on its own.
+    batch_window: Duration,
+    batch: Mutex<Option<Batch>>,
+}
+
+/// Alerts waiting to go out together, as titles and bodies, and when the
+/// first of them fired.
+type Batch = (Instant, Vec<(String, String)>);
+
+impl Notifier {
+    pub fn new(client: Client, desktop: bool, webhook: Option<String>) -> Self {
+        Notifier {
+            client,
+            bell: false,
+            sound: None,
+            desktop,
+            webhook,
+            email: None,
+            telegram: None,
+            command: None,
+            quiet_hours: None,
+            held: None,
+            batch_window: Duration::ZERO,
+            batch: Mutex::new(None),
+        }
+    }
+
+    /// Rings the terminal bell on alerts, or plays `sound` instead when given.
+    pub fn with_audio(mut self, bell: bool, sound: Option<PathBuf>) -> Self {
+        self.bell = bell;
+        self.sound = sound;
+        self
+    }
+
+    pub fn with_email(mut self, email: Option<Mailer>) -> Self {
+        self.email = email;
+        self
+    }
+
+    pub fn with_telegram(mut self, telegram: Option<TelegramSink>) -> Self {
+        self.telegram = telegram;
+        self
+    }
+
+    pub fn with_command(mut self, command: Option<String>) -> Self {
+        self.command = command;
+        self
+    }
+
+    /// Keeps alerts off the pushed channels during `quiet_hours`. With
+    /// `summarize`, the held alerts go out together once the window ends.
+    pub fn with_quiet_hours(mut self, quiet_hours: Option<QuietHours>, summarize: bool) -> Self {
+        self.quiet_hours = quiet_hours;
+        self.held = (quiet_hours.is_some() && summarize).then(|| Mutex::new(Vec::new()));
+        self
+    }
+
+    /// Holds each pushed alert for up to `window` so any others that fire in
+    /// the meantime, as in a market-wide move, go out in the same message.
+    pub fn with_batch_window(mut self, window: Duration) -> Self {
+        self.batch_window = window;
+        self
+    }
+
+    /// The channels an alert goes out on besides the console, for the
+    /// startup banner.
+    pub fn channels(&self) -> Vec<&'static str> {
+        let mut channels = Vec::new();
+        if self.sound.is_some() {
+            channels.push("sound file");
+        } else if self.bell {
+            channels.push("bell");
+        }
+        if self.desktop {
+            channels.push("desktop");
+        }
+        if self.webhook.is_some() {
+            channels.push("webhook");
+        }
+        if self.telegram.is_some() {
+            channels.push("Telegram");
+        }
+        if self.email.is_some() {
+            channels.push("email");
+        }
+        if self.command.is_some() {
+            channels.push("--on-alert command");
+        }
+        channels
+    }
+
+    fn in_quiet_hours(&self) -> bool {
+        self.quiet_hours.is_some_and(|quiet| quiet.contains(Local::now().time()))
+    }
+
+    pub async fn send_notification(&self, title: &str, body: &str) {
+        let body = &strip_ansi(body);
+        match &self.sound {
+            Some(path) => sound::play(path.clone()),
+            None if self.bell => sound::ring_bell(),
+            None => {}
+        }
+        if self.in_quiet_hours() {
+            log_line("Quiet hours: the alert was not pushed.");
+            if let Some(held) = &self.held {
+                held.lock().unwrap().push(body.to_string());
+            }
+            return;
+        }
+        if !self.batch_window.is_zero() {
+            let mut batch = self.batch.lock().unwrap();
+            let (_, alerts) = batch.get_or_insert_with(|| (Instant::now(), Vec::new()));
+            alerts.push((title.to_string(), body.to_string()));
+            return;
+        }
+        self.push(title, body).await;
+    }
+
+    /// When the pending batch of alerts is due to go out, if there is one.
+    pub fn batch_due(&self) -> Option<Instant> {
+        self.batch.lock().unwrap().as_ref().map(|(first, _)| *first + self.batch_window)
+    }
+
+    /// Pushes the pending batch of alerts as one notification, or the
+    /// alert on its own if nothing joined it. Called once the batch is
+    /// due, and on the way out so none are lost.
+    pub async fn release_batch(&self) {
+        let Some((_, alerts)) = self.batch.lock().unwrap().take() else {
+            return;
+        };
+        let (title, body) = batch_message(&alerts);
+        self.push(&title, &body).await;
+    }
+
+    /// Starts the `--on-alert` command for an alert, passing its details in
+    /// `ALERT_TICKER`, `ALERT_PRICE`, `ALERT_CHANGE` and `ALERT_MESSAGE`.
+    /// The command runs in the background, so a slow script can't hold up
+    /// the next check, and its exit status is logged once it finishes.
+    /// Quiet hours don't apply to it.
+    pub fn run_command(&self, ticker: &str, price: f64, change: f64, message: &str) {
+        let Some(command) = &self.command else {
+            return;
+        };
+        let mut shell = shell_command(command);
+        shell
+            .env("ALERT_TICKER", ticker)
+            .env("ALERT_PRICE", price.to_string())
+            .env("ALERT_CHANGE", change.to_string())
+            .env("ALERT_MESSAGE", &*strip_ansi(message))
+            // The console's stdin carries the p/r/a commands.
+            .stdin(Stdio::null());
+        let mut child = match shell.spawn() {
+            Ok(child) => child,
+            Err(e) => return log_warn(&format!("Failed to run the --on-alert command: {}", e)),
+        };
+        tokio::spawn(async move {
+            match child.wait().await {
+                Ok(status) if status.success() => log_line(&format!("The --on-alert command finished ({}).", status)),
+                Ok(status) => log_warn(&format!("The --on-alert command failed ({}).", status)),
+                Err(e) => log_warn(&format!("Lost track of the --on-alert command: {}", e)),
+            }
+        });
+    }
+
+    /// Sends a `--notify-lifecycle` notice that monitoring started or stopped
+    /// to the desktop, webhook, Telegram and email. It isn't an alert, so
+    /// there's no bell, and quiet hours don't hold it back: a stop notice
+    /// that never arrives should mean the monitor died.
+    pub async fn send_notice(&self, title: &str, body: &str) {
+        self.push(title, body).await;
+    }
+
+    /// Sends the summary of alerts held during quiet hours once they have
+    /// ended. Called every check, so it goes out even if nothing new fires.
+    pub async fn release_held(&self) {
+        let Some(held) = self.held.as_ref().filter(|_| !self.in_quiet_hours()) else {
+            return;
+        };
+        let alerts = std::mem::take(&mut *held.lock().unwrap());
+        if alerts.is_empty() {
+            return;
+        }
+        let body = format!(
+            "{} alert(s) during quiet hours:\n{}",
+            alerts.len(),
+            alerts.iter().map(|alert| format!("- {}", alert)).collect::<Vec<_>>().join("\n")
+        );
+        log_line(&format!("Quiet hours are over; sending a summary of {} alert(s).", alerts.len()));
+        self.push("Crypto price alerts during quiet hours", &body).await;
+    }
+
+    /// Delivers to the channels that reach beyond this machine's speakers.
+    async fn push(&self, title: &str, body: &str) {
+        if self.desktop {
+            send_desktop(title, body);
+        }
+        if let Some(url) = &self.webhook {
+            self.send_webhook(url, body).await;
+        }
+        if let Some(telegram) = &self.telegram {
+            telegram.send(&self.client, body).await;
+        }
+        if let Some(email) = &self.email {
+            email.send(title, body).await;
+        }
+    }
+
+    /// Posts `body` to a Discord or Slack incoming webhook. Discord reads
+    /// `content` and Slack reads `text`, so the payload carries both.
+    async fn send_webhook(&self, url: &str, body: &str) {
+        let payload = json!({ "content": body, "text": body });
+        match self.client.post(url).json(&payload).send().await {
+            Ok(response) if !response.status().is_success() => {
+                log_warn(&format!("Webhook rejected the alert: HTTP {}", response.status()));
+            }
+            Ok(_) => {}
+            Err(e) => log_warn(&format!("Failed to send webhook: {}", e)),
+        }
+    }
+}
+
+/// The title and body of one notification standing for all of `alerts`.
+fn batch_message(alerts: &[(String, String)]) -> (String, String) {
+    match alerts {
+        [(title, body)] => (title.clone(), body.clone()),
+        _ => (
+            format!("{} crypto price alerts", alerts.len()),
+            alerts.iter().map(|(_, body)| format!("- {}", body)).collect::<Vec<_>>().join("\n"),
+        ),
+    }
+}
+
+/// `command` run through the platform's shell.
+fn shell_command(command: &str) -> Command {
+    let mut shell = if cfg!(windows) { Command::new("cmd") } else { Command::new("sh") };
+    shell.arg(if cfg!(windows) { "/C" } else { "-c" }).arg(command);
+    shell
+}
+
+/// A Telegram bot that messages one chat. Alerts reach it through the same
+/// cooldown as every other channel.
+pub struct TelegramSink {
+    token: String,
+    chat_id: String,
+}
+
+impl TelegramSink {
+    pub fn new(token: String, chat_id: String) -> Self {
+        TelegramSink { token, chat_id }
+    }
+
+    /// Sends `body` with the Bot API's `sendMessage`. The URL holds the bot
+    /// token, so it is left out of any error that gets logged.
+    async fn send(&self, client: &Client, body: &str) {
+        let url = format!("https://api.telegram.org/bot{}/sendMessage", self.token);
+        let payload = json!({ "chat_id": self.chat_id, "text": body });
+        match client.post(url).json(&payload).send().await {
+            Ok(response) if !response.status().is_success() => {
+                let status = response.status();
+                // Telegram explains the problem (bad token, unknown chat) in `description`.
+                let description = response
+                    .json::<serde_json::Value>()
+                    .await
+                    .ok()
+                    .and_then(|reply| reply["description"].as_str().map(str::to_string))
+                    .unwrap_or_default();
+                log_warn(&format!("Telegram rejected the alert: HTTP {} {}", status, description));
+            }
+            Ok(_) => {}
+            Err(e) => log_warn(&format!("Failed to send Telegram message: {}", e.without_url())),
+        }
+    }
+}
+
+/// Shows a desktop notification. Failures (no notification daemon, running
+/// over SSH, ...) are only logged.
+fn send_desktop(title: &str, body: &str) {
+    if let Err(e) = Notification::new().summary(title).body(body).show() {
+        log_warn(&format!("Desktop notification unavailable: {}", e));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quiet_hours_can_cross_midnight() {
+        let at = |h, m| NaiveTime::from_hms_opt(h, m, 0).unwrap();
+        let night: QuietHours = "22:00-07:00".parse().unwrap();
+        assert!(night.contains(at(23, 30)));
+        assert!(night.contains(at(3, 0)));
+        assert!(!night.contains(at(7, 0)));
+        assert!(!night.contains(at(12, 0)));
+        let lunch: QuietHours = "12:00-13:30".parse().unwrap();
+        assert!(lunch.contains(at(12, 0)));
+        assert!(!lunch.contains(at(13, 30)));
+        assert!("22:00".parse::<QuietHours>().is_err());
+        assert!("25:00-07:00".parse::<QuietHours>().is_err());
+    }
+
+    #[tokio::test]
+    async fn alerts_in_a_batch_window_go_out_together() {
+        let notifier = Notifier::new(Client::new(), false, None).with_batch_window(Duration::from_secs(2));
+        assert_eq!(notifier.batch_due(), None);
+        let before = Instant::now();
+        notifier.send_notification("Crypto price alert", "Alert! bitcoin price fell by $2,000.00").await;
+        notifier.send_notification("Crypto price alert", "Alert! ethereum price fell by $150.00").await;
+        let due = notifier.batch_due().unwrap();
+        assert!(due >= before + Duration::from_secs(2));
+        let (title, body) = batch_message(&notifier.batch.lock().unwrap().as_ref().unwrap().1);
+        assert_eq!(title, "2 crypto price alerts");
+        assert_eq!(body, "- Alert! bitcoin price fell by $2,000.00\n- Alert! ethereum price fell by $150.00");
+        notifier.release_batch().await;
+        assert_eq!(notifier.batch_due(), None);
+        let single = [("Crypto price alert".to_string(), "Alert! bitcoin".to_string())];
+        assert_eq!(batch_message(&single), single[0].clone());
+    }
+}
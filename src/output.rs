@@ -0,0 +1,363 @@
+use crate::alert::AlertEvent;
+use crate::format::{strip_ansi, truncate_visible};
+use chrono::{DateTime, Local, SecondsFormat, Utc};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::OpenOptions;
+use std::io::{self, IsTerminal, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use terminal_size::Width;
+
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+static USE_UTC: AtomicBool = AtomicBool::new(false);
+static JSON_MODE: AtomicBool = AtomicBool::new(false);
+static TABLE_MODE: AtomicBool = AtomicBool::new(false);
+static QUIET: AtomicBool = AtomicBool::new(false);
+static DASHBOARD: AtomicBool = AtomicBool::new(false);
+/// The dashboard line currently drawn at the bottom of the console, if any.
+static STATUS: Mutex<String> = Mutex::new(String::new());
+
+/// Clears the terminal line the cursor is on.
+pub const CLEAR_LINE: &str = "\r\x1b[2K";
+
+/// How price checks are written to stdout.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Human-readable, timestamped lines.
+    Text,
+    /// One JSON object per check (NDJSON); everything else goes to stderr.
+    Json,
+    /// A table of every coin, redrawn after each check, with the latest
+    /// alerts listed under it.
+    Table,
+}
+
+pub fn set_format(format: OutputFormat) {
+    JSON_MODE.store(format == OutputFormat::Json, Ordering::Relaxed);
+    TABLE_MODE.store(format == OutputFormat::Table, Ordering::Relaxed);
+}
+
+pub fn is_json() -> bool {
+    JSON_MODE.load(Ordering::Relaxed)
+}
+
+pub fn is_table() -> bool {
+    TABLE_MODE.load(Ordering::Relaxed)
+}
+
+/// Replaces the screen with `table`, its lines cut to the terminal's
+/// width. When stdout isn't a terminal, or off Unix, each table is printed
+/// after the last instead.
+pub fn show_table(table: &str) {
+    let mut stdout = io::stdout();
+    match terminal_width() {
+        Some(width) => {
+            let _ = write!(stdout, "\x1b[2J\x1b[H");
+            for line in table.lines() {
+                let _ = writeln!(stdout, "{}", truncate_visible(line, width));
+            }
+        }
+        None => {
+            let _ = writeln!(stdout, "{}", table);
+        }
+    }
+    let _ = stdout.flush();
+}
+
+/// The width of the terminal on stdout, read afresh each time so a resize
+/// is picked up. `None` when stdout isn't a terminal, where the dashboard
+/// and table scroll rather than redraw in place.
+pub fn terminal_width() -> Option<usize> {
+    let (Width(columns), _) = terminal_size::terminal_size_of(io::stdout())?;
+    (columns > 0).then_some(columns as usize)
+}
+
+/// Suppresses the routine per-check price lines, leaving alerts, startup
+/// messages and errors.
+pub fn set_quiet(quiet: bool) {
+    QUIET.store(quiet, Ordering::Relaxed);
+}
+
+pub fn is_quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
+/// Replaces the scrolling price lines with a single status line that is
+/// redrawn in place; see `show_status`.
+pub fn set_dashboard(dashboard: bool) {
+    DASHBOARD.store(dashboard, Ordering::Relaxed);
+}
+
+pub fn is_dashboard() -> bool {
+    DASHBOARD.load(Ordering::Relaxed)
+}
+
+/// Draws `line` over the current dashboard line, cut one column short of
+/// the terminal's width so it never wraps. Other console output is printed
+/// above it and the line redrawn after, so alerts still scroll. Without a
+/// terminal to redraw in, each line is printed on its own.
+pub fn show_status(line: &str) {
+    let mut stdout = io::stdout();
+    let Some(width) = terminal_width() else {
+        let _ = writeln!(stdout, "{}", line);
+        return;
+    };
+    let mut status = STATUS.lock().unwrap();
+    // Terminals that rewrap on a resize may have spread the last line over
+    // several rows; the cursor is left on the last of them.
+    let rows = strip_ansi(&status).chars().count().div_ceil(width).max(1);
+    let up = if rows > 1 { format!("\x1b[{}A", rows - 1) } else { String::new() };
+    *status = truncate_visible(line, width.saturating_sub(1)).into_owned();
+    let _ = write!(stdout, "{}\r\x1b[J{}", up, status);
+    let _ = stdout.flush();
+}
+
+/// The dashboard line to redraw after other output; empty if none is shown.
+pub fn status_line() -> String {
+    STATUS.lock().unwrap().clone()
+}
+
+/// Leaves the last dashboard line on screen and moves below it, so later
+/// output doesn't redraw it.
+pub fn end_status() {
+    let mut status = STATUS.lock().unwrap();
+    if !status.is_empty() {
+        status.clear();
+        println!();
+    }
+}
+
+/// Enables colored output unless disabled or stdout isn't a terminal.
+pub fn set_color(enabled: bool) {
+    colored::control::set_override(enabled && std::io::stdout().is_terminal());
+}
+
+/// Switches console timestamps between local time (the default) and UTC.
+pub fn set_utc(utc: bool) {
+    USE_UTC.store(utc, Ordering::Relaxed);
+}
+
+/// The current time as shown on console lines, in local time or UTC.
+pub fn timestamp() -> String {
+    format_time(Utc::now())
+}
+
+/// `at` in the console's timestamp format, in local time or UTC.
+pub fn format_time(at: DateTime<Utc>) -> String {
+    if USE_UTC.load(Ordering::Relaxed) {
+        format!("{} UTC", at.format(TIMESTAMP_FORMAT))
+    } else {
+        at.with_timezone(&Local).format(TIMESTAMP_FORMAT).to_string()
+    }
+}
+
+/// Logs `msg` at `info`. On the console it is prefixed with the current
+/// time, e.g. `[2024-05-01 14:03:22] ...`, and goes to stderr in JSON mode
+/// so stdout stays pure NDJSON.
+pub fn log_line(msg: &str) {
+    tracing::info!("{}", msg);
+}
+
+/// Logs a transient problem, such as a failed fetch that will be retried.
+pub fn log_warn(msg: &str) {
+    tracing::warn!("{}", msg);
+}
+
+/// Logs a fatal error.
+pub fn log_error(msg: &str) {
+    tracing::error!("{}", msg);
+}
+
+/// Prints an untimestamped report line, such as the shutdown summary.
+pub fn report(msg: &str) {
+    if is_json() {
+        eprintln!("{}", msg);
+    } else {
+        println!("{}", msg);
+    }
+}
+
+/// One price check, as emitted in JSON mode.
+#[derive(Serialize, Debug)]
+pub struct PriceUpdate<'a> {
+    pub ts: String,
+    pub ticker: &'a str,
+    pub price: f64,
+    pub change: f64,
+    pub pct: f64,
+    /// Percent change over the last 24 hours, when the provider reports it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub change_24h: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub volume_24h: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub market_cap: Option<f64>,
+    /// The price in the `--extra-currencies`, keyed by currency code.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub other_prices: Option<&'a HashMap<String, f64>>,
+    pub alert: bool,
+}
+
+impl<'a> PriceUpdate<'a> {
+    pub fn new(ticker: &'a str, price: f64, change: f64, pct: f64, alert: bool) -> Self {
+        PriceUpdate {
+            ts: Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+            ticker,
+            price,
+            change,
+            pct,
+            change_24h: None,
+            volume_24h: None,
+            market_cap: None,
+            other_prices: None,
+            alert,
+        }
+    }
+}
+
+/// Somewhere a shown price check goes: the console, a CSV file, JSON lines.
+/// Every active sink gets every check, so `--output` files don't silence
+/// the console. `line` is the human-readable form of `update`.
+pub trait PriceSink: Send {
+    /// How the sink is named in error messages, e.g. `prices.csv`.
+    fn name(&self) -> String;
+
+    fn record(&mut self, update: &PriceUpdate, line: &str, movement: Movement) -> io::Result<()>;
+}
+
+/// The timestamped price lines, through `log_price`; see there for when
+/// the console skips them.
+pub struct ConsoleSink;
+
+impl PriceSink for ConsoleSink {
+    fn name(&self) -> String {
+        "the console".to_string()
+    }
+
+    fn record(&mut self, _update: &PriceUpdate, line: &str, movement: Movement) -> io::Result<()> {
+        log_price(line, movement);
+        Ok(())
+    }
+}
+
+/// One JSON object per check (NDJSON), on stdout in JSON mode or appended
+/// to a `.jsonl` file.
+pub struct JsonLinesSink {
+    name: String,
+    writer: Box<dyn Write + Send>,
+}
+
+impl JsonLinesSink {
+    pub fn stdout() -> Self {
+        JsonLinesSink { name: "stdout".to_string(), writer: Box::new(io::stdout()) }
+    }
+
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(JsonLinesSink { name: path.display().to_string(), writer: Box::new(file) })
+    }
+}
+
+impl PriceSink for JsonLinesSink {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn record(&mut self, update: &PriceUpdate, _line: &str, _movement: Movement) -> io::Result<()> {
+        writeln!(self.writer, "{}", serde_json::to_string(update)?)?;
+        self.writer.flush()
+    }
+}
+
+/// What a run did, written as JSON by `--summary-json` when it stops.
+#[derive(Serialize, Debug)]
+pub struct RunSummary<'a> {
+    pub duration_secs: u64,
+    pub checks: u64,
+    pub vs_currency: &'a str,
+    pub alerts: u32,
+    /// Alerts fired, keyed by alert type name, plus `volatility` for
+    /// `--volatility-alert` and `ema` for `--ema-alert`.
+    pub alerts_by_type: BTreeMap<&'static str, u32>,
+    pub coins: Vec<CoinSummary<'a>>,
+    /// Each alert that fired, oldest first, up to `--alert-history`.
+    pub alert_history: Vec<&'a AlertEvent>,
+}
+
+/// One monitored coin in a `RunSummary`.
+#[derive(Serialize, Debug)]
+pub struct CoinSummary<'a> {
+    pub ticker: &'a str,
+    pub alert_type: &'static str,
+    pub min_price: f64,
+    pub max_price: f64,
+    pub avg_price: f64,
+    pub alerts: u32,
+}
+
+pub fn write_summary(path: &Path, summary: &RunSummary) -> io::Result<()> {
+    std::fs::write(path, serde_json::to_string_pretty(summary)?)
+}
+
+/// Passes a check on to `inner` only when it fired an alert or its 24h
+/// change is at least `min_change_24h` percent either way, so an
+/// `--output` file can follow different signals than the alerts do.
+/// Checks without a 24h change are left out.
+pub struct Min24hChangeSink {
+    pub inner: Box<dyn PriceSink>,
+    pub min_change_24h: f64,
+}
+
+impl PriceSink for Min24hChangeSink {
+    fn name(&self) -> String {
+        self.inner.name()
+    }
+
+    fn record(&mut self, update: &PriceUpdate, line: &str, movement: Movement) -> io::Result<()> {
+        if update.alert || update.change_24h.is_some_and(|change| change.abs() >= self.min_change_24h) {
+            self.inner.record(update, line, movement)?;
+        }
+        Ok(())
+    }
+}
+
+/// Direction of a price move since the previous check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Movement {
+    Up,
+    Down,
+    Flat,
+}
+
+impl Movement {
+    pub fn between(previous: f64, current: f64) -> Self {
+        if current > previous {
+            Movement::Up
+        } else if current < previous {
+            Movement::Down
+        } else {
+            Movement::Flat
+        }
+    }
+}
+
+/// Logs a routine price line, shown green if the price rose and red if it
+/// fell. The console skips it in JSON mode, where each check is emitted as
+/// a `PriceUpdate`, and in quiet mode; the log file still gets it.
+pub fn log_price(msg: &str, movement: Movement) {
+    let style = match movement {
+        Movement::Up => "up",
+        Movement::Down => "down",
+        Movement::Flat => "flat",
+    };
+    tracing::info!(style, "{}", msg);
+}
+
+/// Logs an alert so it stands out from the routine lines. It is logged at
+/// `warn` so `RUST_LOG=warn` still shows alerts.
+pub fn log_alert(msg: &str) {
+    tracing::warn!(style = "alert", "{}", msg);
+}
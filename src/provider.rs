@@ -0,0 +1,1019 @@
+use crate::api::{check_status, read_json, CoinGecko, CoinGeckoPrice, FetchError};
+use crate::config;
+use crate::output::{log_line, log_warn};
+use crate::rate_limit::RateLimiter;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::future::join_all;
+use reqwest::{Client, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tokio::time::{sleep, Duration};
+
+/// A coin's current price plus whatever market context the provider
+/// reports alongside it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Quote {
+    pub price: f64,
+    /// The price in any extra currencies asked for, keyed by currency code.
+    /// Only CoinGecko fills this in.
+    #[serde(default)]
+    pub other_prices: HashMap<String, f64>,
+    /// Percent change over the last 24 hours.
+    pub change_24h: Option<f64>,
+    /// Trading volume over the last 24 hours, in the quote currency.
+    pub volume_24h: Option<f64>,
+    pub market_cap: Option<f64>,
+    /// The highest and lowest prices over the last 24 hours.
+    pub high_24h: Option<f64>,
+    pub low_24h: Option<f64>,
+    /// When the provider last updated the price. Only CoinGecko reports it.
+    #[serde(default)]
+    pub updated_at: Option<DateTime<Utc>>,
+    /// For a portfolio, each holding's value, which add up to `price`.
+    #[serde(default)]
+    pub breakdown: Vec<(String, f64)>,
+    /// For an `--aggregate` price, each provider's price that went into it.
+    #[serde(default)]
+    pub sources: Vec<(String, f64)>,
+    /// For a price from a `FallbackProvider`, the provider that answered.
+    #[serde(default)]
+    pub served_by: Option<String>,
+}
+
+impl Quote {
+    /// A quote with nothing but the price.
+    pub fn price(price: f64) -> Self {
+        Quote {
+            price,
+            other_prices: HashMap::new(),
+            change_24h: None,
+            volume_24h: None,
+            market_cap: None,
+            high_24h: None,
+            low_24h: None,
+            updated_at: None,
+            breakdown: Vec::new(),
+            sources: Vec::new(),
+            served_by: None,
+        }
+    }
+
+    /// How far apart the `sources` are, as a percent of the lowest of them;
+    /// unset with fewer than two.
+    pub fn spread(&self) -> Option<f64> {
+        if self.sources.len() < 2 {
+            return None;
+        }
+        let low = self.sources.iter().map(|(_, price)| *price).fold(f64::INFINITY, f64::min);
+        let high = self.sources.iter().map(|(_, price)| *price).fold(f64::NEG_INFINITY, f64::max);
+        (low > 0.0).then(|| (high - low) / low * 100.0)
+    }
+
+    /// When the oldest of `quotes` was updated, for a price worked out from
+    /// them: it is only as fresh as its oldest part. Quotes that don't say
+    /// are left out.
+    pub fn oldest_update<'a>(quotes: impl IntoIterator<Item = &'a Quote>) -> Option<DateTime<Utc>> {
+        quotes.into_iter().filter_map(|quote| quote.updated_at).min()
+    }
+
+    /// How long ago the provider updated the price, if it said.
+    pub fn age(&self) -> Option<Duration> {
+        self.updated_at.map(|at| (Utc::now() - at).to_std().unwrap_or_default())
+    }
+}
+
+/// Per-coin results of a batch fetch.
+pub type PriceBatch = HashMap<String, Result<Quote, FetchError>>;
+
+/// The startup quotes, cached for a few seconds so `--once` runs close
+/// together share one fetch.
+#[derive(Serialize, Deserialize)]
+pub struct CachedQuotes {
+    vs_currency: String,
+    quotes: HashMap<String, Quote>,
+}
+
+impl CachedQuotes {
+    /// Keeps the successful quotes from `batch`.
+    pub fn new(batch: &PriceBatch, vs_currency: &str) -> Self {
+        let quotes = batch.iter().filter_map(|(ticker, quote)| Some((ticker.clone(), quote.as_ref().ok()?.clone()))).collect();
+        CachedQuotes { vs_currency: vs_currency.to_string(), quotes }
+    }
+
+    /// The cached quotes as a batch, if they cover every one of `tickers`
+    /// in `vs_currency`.
+    pub fn covering(self, tickers: &[String], vs_currency: &str) -> Option<PriceBatch> {
+        if self.vs_currency != vs_currency || !tickers.iter().all(|t| self.quotes.contains_key(t)) {
+            return None;
+        }
+        Some(self.quotes.into_iter().map(|(ticker, quote)| (ticker, Ok(quote))).collect())
+    }
+}
+
+/// `~/.crypto_alerts_prices.json`, where the startup quotes are cached.
+pub fn default_price_cache_path() -> Option<PathBuf> {
+    config::home_dir().map(|home| home.join(".crypto_alerts_prices.json"))
+}
+
+/// A source of spot prices, quoted in whatever currency the provider was
+/// created for.
+#[async_trait]
+pub trait PriceProvider: Send + Sync {
+    /// Fetches the current quote for `ticker`.
+    async fn fetch(&self, ticker: &str) -> Result<Quote, FetchError>;
+
+    /// Fetches several tickers. A coin that fails gets an error entry
+    /// rather than failing the batch; the whole call only fails, so it can
+    /// be retried, when every coin hit a failure worth retrying (network
+    /// trouble, rate limits). The default runs one `fetch` per ticker
+    /// concurrently, still spaced by the provider's rate limiter; providers
+    /// with a batch endpoint override it.
+    async fn fetch_many(&self, tickers: &[String]) -> Result<PriceBatch, FetchError> {
+        let results = join_all(tickers.iter().map(|ticker| self.fetch(ticker))).await;
+        if !results.is_empty() && results.iter().all(|result| result.as_ref().is_err_and(FetchError::is_retryable)) {
+            return Err(results.into_iter().find_map(Result::err).unwrap_or(FetchError::PriceMissing));
+        }
+        Ok(tickers.iter().cloned().zip(results).collect())
+    }
+
+    /// Whether the provider has run out of prices for good. Only a replay
+    /// ever does.
+    fn exhausted(&self) -> bool {
+        false
+    }
+}
+
+/// Prices from CoinGecko's `simple/price` endpoint, in the primary
+/// currency plus any extra ones. That endpoint has no 24h high or low, so
+/// with `with_24h_range` each fetch also asks `/coins/markets` for them.
+pub struct CoinGeckoProvider {
+    api: CoinGecko,
+    vs_currencies: Vec<String>,
+    range_24h: bool,
+}
+
+impl CoinGeckoProvider {
+    pub fn new(api: CoinGecko, vs_currency: &str, extra_currencies: &[String]) -> Self {
+        CoinGeckoProvider { api, vs_currencies: currency_list(vs_currency, extra_currencies), range_24h: false }
+    }
+
+    /// Also fetches each coin's 24h high and low, at the cost of a second request.
+    pub fn with_24h_range(mut self, range_24h: bool) -> Self {
+        self.range_24h = range_24h;
+        self
+    }
+
+    /// Fills in the 24h high and low of the quotes in `batch`. A failure only
+    /// leaves them unset.
+    async fn add_24h_range(&self, batch: &mut PriceBatch) {
+        let tickers: Vec<String> = batch.keys().cloned().collect();
+        match self.api.fetch_coin_info(&tickers, &self.vs_currencies[0]).await {
+            Ok(infos) => {
+                for info in infos {
+                    if let Some(Ok(quote)) = batch.get_mut(&info.id) {
+                        quote.high_24h = info.high_24h;
+                        quote.low_24h = info.low_24h;
+                    }
+                }
+            }
+            Err(e) => log_warn(&format!("Could not fetch the 24h high and low: {}", e)),
+        }
+    }
+}
+
+/// `vs_currency` followed by the extra currencies that differ from it.
+fn currency_list(vs_currency: &str, extra_currencies: &[String]) -> Vec<String> {
+    let mut currencies = vec![vs_currency.to_string()];
+    currencies.extend(extra_currencies.iter().filter(|c| *c != vs_currency).cloned());
+    currencies
+}
+
+#[async_trait]
+impl PriceProvider for CoinGeckoProvider {
+    async fn fetch(&self, ticker: &str) -> Result<Quote, FetchError> {
+        let mut batch = self.fetch_many(&[ticker.to_string()]).await?;
+        batch.remove(ticker).unwrap_or_else(|| Err(FetchError::UnknownCoin(ticker.to_string())))
+    }
+
+    async fn fetch_many(&self, tickers: &[String]) -> Result<PriceBatch, FetchError> {
+        let prices = self.api.fetch_prices(tickers, &self.vs_currencies).await?;
+        let mut batch: PriceBatch = prices.into_iter().map(|(ticker, p)| (ticker, p.map(Quote::from))).collect();
+        if self.range_24h {
+            self.add_24h_range(&mut batch).await;
+        }
+        Ok(batch)
+    }
+}
+
+impl From<CoinGeckoPrice> for Quote {
+    fn from(p: CoinGeckoPrice) -> Self {
+        Quote {
+            price: p.price,
+            other_prices: p.other_prices,
+            change_24h: p.change_24h,
+            volume_24h: p.volume_24h,
+            market_cap: p.market_cap,
+            high_24h: None,
+            low_24h: None,
+            updated_at: p.last_updated_at,
+            breakdown: Vec::new(),
+            sources: Vec::new(),
+            served_by: None,
+        }
+    }
+}
+
+/// Prices a single token by contract address through CoinGecko's
+/// `simple/token_price` endpoint. The token is reported under `label`,
+/// since it has no ticker of its own.
+pub struct TokenProvider {
+    api: CoinGecko,
+    platform: String,
+    address: String,
+    label: String,
+    vs_currencies: Vec<String>,
+}
+
+impl TokenProvider {
+    /// `address` must already be validated with `parse_contract`.
+    pub fn new(api: CoinGecko, platform: &str, address: String, label: String, vs_currency: &str, extra_currencies: &[String]) -> Self {
+        let vs_currencies = currency_list(vs_currency, extra_currencies);
+        TokenProvider { api, platform: platform.to_string(), address, label, vs_currencies }
+    }
+}
+
+#[async_trait]
+impl PriceProvider for TokenProvider {
+    async fn fetch(&self, ticker: &str) -> Result<Quote, FetchError> {
+        if ticker != self.label {
+            return Err(FetchError::UnknownCoin(ticker.to_string()));
+        }
+        let price = self.api.fetch_token_price(&self.platform, &self.address, &self.vs_currencies).await?;
+        price.map(Quote::from)
+    }
+}
+
+/// Checks a contract address before it is sent anywhere and normalizes it
+/// to lowercase. EVM platforms use `0x` plus 40 hex digits; Solana uses a
+/// base58 string of 32 to 44 characters.
+pub fn parse_contract(platform: &str, address: &str) -> Result<String, String> {
+    let address = address.trim();
+    if platform == "solana" {
+        let base58 = |c: char| c.is_ascii_alphanumeric() && !matches!(c, '0' | 'O' | 'I' | 'l');
+        if (32..=44).contains(&address.len()) && address.chars().all(base58) {
+            // Solana addresses are case-sensitive.
+            return Ok(address.to_string());
+        }
+        return Err(format!("'{}' is not a valid Solana token address", address));
+    }
+    match address.strip_prefix("0x") {
+        Some(hex) if hex.len() == 40 && hex.chars().all(|c| c.is_ascii_hexdigit()) => Ok(address.to_lowercase()),
+        _ => Err(format!("'{}' is not a valid contract address (expected 0x followed by 40 hex digits)", address)),
+    }
+}
+
+/// A short display name for a contract address, e.g. `0x1f98...f984`.
+pub fn contract_label(address: &str) -> String {
+    if address.len() <= 12 {
+        return address.to_string();
+    }
+    format!("{}...{}", &address[..6], &address[address.len() - 4..])
+}
+
+/// Prices an NFT collection by its floor, reported under `label`. CoinGecko
+/// only gives the floor in dollars and the collection's own currency, so
+/// for any other currency the native floor is converted at that coin's price,
+/// which costs a second request and leaves out the dollar-only 24h figures.
+pub struct NftProvider {
+    api: CoinGecko,
+    id: String,
+    label: String,
+    vs_currency: String,
+}
+
+impl NftProvider {
+    pub fn new(api: CoinGecko, id: String, label: String, vs_currency: &str) -> Self {
+        NftProvider { api, id, label, vs_currency: vs_currency.to_string() }
+    }
+}
+
+#[async_trait]
+impl PriceProvider for NftProvider {
+    async fn fetch(&self, ticker: &str) -> Result<Quote, FetchError> {
+        if ticker != self.label {
+            return Err(FetchError::UnknownCoin(ticker.to_string()));
+        }
+        let floor = self.api.fetch_nft_floor(&self.id).await?;
+        if let Some(usd) = floor.usd.filter(|_| self.vs_currency == "usd") {
+            return Ok(Quote {
+                change_24h: floor.change_24h,
+                volume_24h: floor.volume_24h,
+                market_cap: floor.market_cap,
+                ..Quote::price(usd)
+            });
+        }
+        let currencies = [self.vs_currency.clone()];
+        let mut prices = self.api.fetch_prices(std::slice::from_ref(&floor.native_currency), &currencies).await?;
+        let rate = prices.remove(&floor.native_currency).unwrap_or(Err(FetchError::UnknownCoin(floor.native_currency)))?;
+        Ok(Quote::price(floor.native * rate.price))
+    }
+}
+
+/// Prices a basket of holdings, `(coin id, amount)`, as its total value,
+/// reported under `label` as if it were one coin's price. Every coin is
+/// needed for the total, so one missing price fails the whole fetch.
+pub struct PortfolioProvider {
+    inner: Box<dyn PriceProvider>,
+    holdings: Vec<(String, f64)>,
+    label: String,
+}
+
+impl PortfolioProvider {
+    pub fn new(inner: Box<dyn PriceProvider>, holdings: Vec<(String, f64)>, label: String) -> Self {
+        PortfolioProvider { inner, holdings, label }
+    }
+}
+
+#[async_trait]
+impl PriceProvider for PortfolioProvider {
+    async fn fetch(&self, ticker: &str) -> Result<Quote, FetchError> {
+        if ticker != self.label {
+            return Err(FetchError::UnknownCoin(ticker.to_string()));
+        }
+        let ids: Vec<String> = self.holdings.iter().map(|(id, _)| id.clone()).collect();
+        let mut batch = self.inner.fetch_many(&ids).await?;
+        let mut quotes = Vec::new();
+        let mut breakdown = Vec::new();
+        for (id, amount) in &self.holdings {
+            let quote = batch.remove(id).unwrap_or_else(|| Err(FetchError::UnknownCoin(id.clone())))?;
+            breakdown.push((id.clone(), quote.price * amount));
+            quotes.push(quote);
+        }
+        let total = breakdown.iter().map(|(_, value)| value).sum();
+        Ok(Quote { updated_at: Quote::oldest_update(&quotes), breakdown, ..Quote::price(total) })
+    }
+}
+
+/// Prices the ratio of two coins, `base / quote` (for ETH/BTC, how many
+/// bitcoin one ether buys), reported under `label` as if it were one coin's
+/// price. Both coins come from a single `fetch_many` on the inner provider.
+pub struct RatioProvider {
+    inner: Box<dyn PriceProvider>,
+    base: String,
+    quote: String,
+    label: String,
+}
+
+impl RatioProvider {
+    pub fn new(inner: Box<dyn PriceProvider>, base: String, quote: String, label: String) -> Self {
+        RatioProvider { inner, base, quote, label }
+    }
+}
+
+#[async_trait]
+impl PriceProvider for RatioProvider {
+    async fn fetch(&self, ticker: &str) -> Result<Quote, FetchError> {
+        if ticker != self.label {
+            return Err(FetchError::UnknownCoin(ticker.to_string()));
+        }
+        let mut batch = self.inner.fetch_many(&[self.base.clone(), self.quote.clone()]).await?;
+        let mut take = |id: &str| batch.remove(id).unwrap_or_else(|| Err(FetchError::UnknownCoin(id.to_string())));
+        let (base, quote) = (take(&self.base)?, take(&self.quote)?);
+        if quote.price <= 0.0 {
+            return Err(FetchError::PriceMissing);
+        }
+        Ok(Quote { updated_at: Quote::oldest_update([&base, &quote]), ..Quote::price(base.price / quote.price) })
+    }
+}
+
+/// Which price source to use, chosen with `--provider`.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProviderKind {
+    Coingecko,
+    Binance,
+    Kraken,
+}
+
+impl ProviderKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            ProviderKind::Coingecko => "coingecko",
+            ProviderKind::Binance => "binance",
+            ProviderKind::Kraken => "kraken",
+        }
+    }
+
+    /// `min_gap` spaces out the exchanges' per-coin requests; CoinGecko requests
+    /// are already limited by `coingecko` itself. Only CoinGecko quotes the
+    /// `extra_currencies`, and only it needs telling to fetch the 24h range,
+    /// which the exchanges always report.
+    pub fn build(
+        self,
+        coingecko: &CoinGecko,
+        client: Client,
+        vs_currency: &str,
+        extra_currencies: &[String],
+        min_gap: Duration,
+        range_24h: bool,
+    ) -> Box<dyn PriceProvider> {
+        match self {
+            ProviderKind::Coingecko => Box::new(
+                CoinGeckoProvider::new(coingecko.clone(), vs_currency, extra_currencies).with_24h_range(range_24h),
+            ),
+            ProviderKind::Binance => Box::new(BinanceProvider::new(client, vs_currency, min_gap)),
+            ProviderKind::Kraken => Box::new(KrakenProvider::new(client, vs_currency, min_gap)),
+        }
+    }
+
+    /// Builds the providers in `kinds`, each with its name; see `chain` for
+    /// putting them together.
+    pub fn build_all(
+        kinds: &[ProviderKind],
+        coingecko: &CoinGecko,
+        client: Client,
+        vs_currency: &str,
+        extra_currencies: &[String],
+        min_gap: Duration,
+        range_24h: bool,
+    ) -> Vec<(&'static str, Box<dyn PriceProvider>)> {
+        kinds
+            .iter()
+            .map(|kind| (kind.name(), kind.build(coingecko, client.clone(), vs_currency, extra_currencies, min_gap, range_24h)))
+            .collect()
+    }
+}
+
+/// A single provider as it is. Several are wrapped in a `FallbackProvider`,
+/// or with `aggregate` an `AggregateProvider`.
+pub fn chain(mut providers: Vec<(&'static str, Box<dyn PriceProvider>)>, aggregate: Option<Aggregate>) -> Box<dyn PriceProvider> {
+    if providers.len() == 1 {
+        return providers.remove(0).1;
+    }
+    match aggregate {
+        Some(method) => Box::new(AggregateProvider::new(providers, method)),
+        None => Box::new(FallbackProvider::new(providers)),
+    }
+}
+
+const BINANCE_API_BASE: &str = "https://api.binance.com/api/v3";
+
+/// Binance trades by pair symbol (BTCUSDT) rather than CoinGecko id, so
+/// only coins listed here can be priced from Binance.
+const BINANCE_ASSETS: &[(&str, &str)] = &[
+    ("bitcoin", "BTC"),
+    ("ethereum", "ETH"),
+    ("cardano", "ADA"),
+    ("solana", "SOL"),
+    ("ripple", "XRP"),
+    ("dogecoin", "DOGE"),
+    ("binancecoin", "BNB"),
+    ("litecoin", "LTC"),
+    ("polkadot", "DOT"),
+    ("chainlink", "LINK"),
+];
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BinanceTicker {
+    last_price: String,
+    price_change_percent: String,
+    quote_volume: String,
+    high_price: String,
+    low_price: String,
+}
+
+/// Prices from Binance's public `ticker/24hr` endpoint. USD is quoted in
+/// USDT, the pair Binance actually has liquidity in. Volume is Binance's
+/// own for the pair, and it has no market cap.
+pub struct BinanceProvider {
+    client: Client,
+    quote: String,
+    limiter: RateLimiter,
+}
+
+impl BinanceProvider {
+    pub fn new(client: Client, vs_currency: &str, min_gap: Duration) -> Self {
+        let quote = match vs_currency {
+            "usd" => "USDT".to_string(),
+            other => other.to_uppercase(),
+        };
+        BinanceProvider { client, quote, limiter: RateLimiter::new(min_gap) }
+    }
+
+    /// The Binance pair for a CoinGecko id, e.g. `bitcoin` -> `BTCUSDT`.
+    fn symbol(&self, ticker: &str) -> Option<String> {
+        BINANCE_ASSETS
+            .iter()
+            .find(|(id, _)| *id == ticker)
+            .map(|(_, asset)| format!("{}{}", asset, self.quote))
+    }
+}
+
+#[async_trait]
+impl PriceProvider for BinanceProvider {
+    async fn fetch(&self, ticker: &str) -> Result<Quote, FetchError> {
+        let symbol = self.symbol(ticker).ok_or_else(|| FetchError::UnknownCoin(ticker.to_string()))?;
+        let api_url = format!("{}/ticker/24hr?symbol={}", BINANCE_API_BASE, symbol);
+        let _slot = self.limiter.wait().await;
+        let response = self.client.get(&api_url).send().await?;
+        // Binance answers 400 for pairs it doesn't list.
+        if response.status() == StatusCode::BAD_REQUEST {
+            return Err(FetchError::UnknownCoin(ticker.to_string()));
+        }
+        let ticker: BinanceTicker = read_json(check_status(response)?).await?;
+        let price = ticker.last_price.parse().map_err(|_| FetchError::ParseError)?;
+        Ok(Quote {
+            change_24h: ticker.price_change_percent.parse().ok(),
+            volume_24h: ticker.quote_volume.parse().ok(),
+            high_24h: ticker.high_price.parse().ok(),
+            low_24h: ticker.low_price.parse().ok(),
+            ..Quote::price(price)
+        })
+    }
+}
+
+const KRAKEN_API_BASE: &str = "https://api.kraken.com/0/public";
+
+/// Kraken's asset codes for the coins it can price; Bitcoin and Dogecoin
+/// go by their legacy names there.
+const KRAKEN_ASSETS: &[(&str, &str)] = &[
+    ("bitcoin", "XBT"),
+    ("ethereum", "ETH"),
+    ("cardano", "ADA"),
+    ("solana", "SOL"),
+    ("ripple", "XRP"),
+    ("dogecoin", "XDG"),
+    ("litecoin", "LTC"),
+    ("polkadot", "DOT"),
+    ("chainlink", "LINK"),
+];
+
+#[derive(Deserialize)]
+struct KrakenResponse {
+    error: Vec<String>,
+    #[serde(default)]
+    result: HashMap<String, KrakenTicker>,
+}
+
+/// The fields of a Kraken ticker we use. Each is an array of strings:
+/// `c` is `[last price, lot volume]`, while `v` (base volume), `p`
+/// (volume-weighted average price), `h` (high) and `l` (low) are
+/// `[today, last 24 hours]`.
+#[derive(Deserialize)]
+struct KrakenTicker {
+    c: Vec<String>,
+    v: Vec<String>,
+    p: Vec<String>,
+    h: Vec<String>,
+    l: Vec<String>,
+}
+
+/// Prices from Kraken's public `Ticker` endpoint. Kraken only reports the
+/// change since midnight UTC rather than over 24 hours, so quotes carry no
+/// 24h change, and it has no market cap.
+pub struct KrakenProvider {
+    client: Client,
+    quote: String,
+    limiter: RateLimiter,
+}
+
+impl KrakenProvider {
+    pub fn new(client: Client, vs_currency: &str, min_gap: Duration) -> Self {
+        KrakenProvider { client, quote: vs_currency.to_uppercase(), limiter: RateLimiter::new(min_gap) }
+    }
+
+    /// The Kraken pair for a CoinGecko id, e.g. `bitcoin` -> `XBTUSD`.
+    fn pair(&self, ticker: &str) -> Option<String> {
+        KRAKEN_ASSETS
+            .iter()
+            .find(|(id, _)| *id == ticker)
+            .map(|(_, asset)| format!("{}{}", asset, self.quote))
+    }
+}
+
+#[async_trait]
+impl PriceProvider for KrakenProvider {
+    async fn fetch(&self, ticker: &str) -> Result<Quote, FetchError> {
+        let pair = self.pair(ticker).ok_or_else(|| FetchError::UnknownCoin(ticker.to_string()))?;
+        let api_url = format!("{}/Ticker?pair={}", KRAKEN_API_BASE, pair);
+        let _slot = self.limiter.wait().await;
+        let response: KrakenResponse = read_json(check_status(self.client.get(&api_url).send().await?)?).await?;
+        if response.error.iter().any(|e| e.contains("Unknown asset pair")) {
+            return Err(FetchError::UnknownCoin(ticker.to_string()));
+        }
+        // The result is keyed by Kraken's own pair name (XXBTZUSD for
+        // XBTUSD), so take whatever single entry came back.
+        let ticker = response.result.into_values().next().ok_or(FetchError::PriceMissing)?;
+        let field = |values: &[String], i: usize| values.get(i).and_then(|v| v.parse::<f64>().ok());
+        let price = field(&ticker.c, 0).ok_or(FetchError::ParseError)?;
+        let volume_24h = field(&ticker.v, 1).zip(field(&ticker.p, 1)).map(|(base, vwap)| base * vwap);
+        Ok(Quote { volume_24h, high_24h: field(&ticker.h, 1), low_24h: field(&ticker.l, 1), ..Quote::price(price) })
+    }
+}
+
+/// Tries a list of providers in priority order. A coin the first provider
+/// can't price, or a batch it fails outright, goes to the next one; the
+/// call only fails when every provider did.
+pub struct FallbackProvider {
+    providers: Vec<(&'static str, Box<dyn PriceProvider>)>,
+    /// The provider that served the last price, so a switch is only
+    /// announced once.
+    last_used: Mutex<Option<&'static str>>,
+}
+
+impl FallbackProvider {
+    pub fn new(providers: Vec<(&'static str, Box<dyn PriceProvider>)>) -> Self {
+        FallbackProvider { providers, last_used: Mutex::new(None) }
+    }
+
+    /// Notes that `name` answered, announcing it when that is a switch.
+    fn served_by(&self, name: &'static str) {
+        let mut last_used = self.last_used.lock().unwrap();
+        if last_used.is_some_and(|last| last != name) {
+            log_line(&format!("Now getting prices from {}.", name));
+        }
+        *last_used = Some(name);
+    }
+}
+
+#[async_trait]
+impl PriceProvider for FallbackProvider {
+    async fn fetch(&self, ticker: &str) -> Result<Quote, FetchError> {
+        let mut prices = self.fetch_many(&[ticker.to_string()]).await?;
+        prices.remove(ticker).unwrap_or_else(|| Err(FetchError::UnknownCoin(ticker.to_string())))
+    }
+
+    async fn fetch_many(&self, tickers: &[String]) -> Result<PriceBatch, FetchError> {
+        let mut prices = PriceBatch::new();
+        let mut remaining = tickers.to_vec();
+        let mut last_error = None;
+        let mut any_answered = false;
+        for (i, (name, provider)) in self.providers.iter().enumerate() {
+            if remaining.is_empty() {
+                break;
+            }
+            match provider.fetch_many(&remaining).await {
+                Ok(batch) => {
+                    any_answered = true;
+                    for (ticker, mut result) in batch {
+                        match &mut result {
+                            Ok(quote) => {
+                                quote.served_by = Some(name.to_string());
+                                self.served_by(name);
+                                remaining.retain(|t| *t != ticker);
+                            }
+                            Err(e) => tracing::debug!("{} has no {} price: {}", name, ticker, e),
+                        }
+                        prices.insert(ticker, result);
+                    }
+                }
+                Err(e) => {
+                    let next = if i + 1 < self.providers.len() { " Trying the next provider." } else { "" };
+                    log_warn(&format!("{} failed: {}.{}", name, e, next));
+                    last_error = Some(e);
+                }
+            }
+        }
+        match last_error {
+            Some(e) if !any_answered => Err(e),
+            _ => Ok(prices),
+        }
+    }
+}
+
+/// How `--aggregate` combines the providers' prices into one.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Aggregate {
+    Mean,
+    /// The middle price, or the mean of the two middle ones; one bad tick
+    /// can't move it.
+    Median,
+}
+
+impl Aggregate {
+    pub fn combine(self, prices: &[f64]) -> Option<f64> {
+        if prices.is_empty() {
+            return None;
+        }
+        match self {
+            Aggregate::Mean => Some(prices.iter().sum::<f64>() / prices.len() as f64),
+            Aggregate::Median => {
+                let mut sorted = prices.to_vec();
+                sorted.sort_by(f64::total_cmp);
+                let mid = sorted.len() / 2;
+                Some(if sorted.len().is_multiple_of(2) { (sorted[mid - 1] + sorted[mid]) / 2.0 } else { sorted[mid] })
+            }
+        }
+    }
+}
+
+/// Asks every provider at once and prices each coin at the mean or median
+/// of the answers, leaving out providers that failed or don't list it.
+/// The rest of the quote comes from the first provider that answered, and
+/// `sources` lists every price that went in.
+pub struct AggregateProvider {
+    providers: Vec<(&'static str, Box<dyn PriceProvider>)>,
+    method: Aggregate,
+}
+
+impl AggregateProvider {
+    pub fn new(providers: Vec<(&'static str, Box<dyn PriceProvider>)>, method: Aggregate) -> Self {
+        AggregateProvider { providers, method }
+    }
+}
+
+#[async_trait]
+impl PriceProvider for AggregateProvider {
+    async fn fetch(&self, ticker: &str) -> Result<Quote, FetchError> {
+        let mut prices = self.fetch_many(&[ticker.to_string()]).await?;
+        prices.remove(ticker).unwrap_or_else(|| Err(FetchError::UnknownCoin(ticker.to_string())))
+    }
+
+    async fn fetch_many(&self, tickers: &[String]) -> Result<PriceBatch, FetchError> {
+        let batches = join_all(self.providers.iter().map(|(_, provider)| provider.fetch_many(tickers))).await;
+        let mut answered = Vec::new();
+        let mut last_error = None;
+        for ((name, _), batch) in self.providers.iter().zip(batches) {
+            match batch {
+                Ok(batch) => answered.push((*name, batch)),
+                Err(e) => {
+                    log_warn(&format!("{} failed: {}; leaving it out of the average.", name, e));
+                    last_error = Some(e);
+                }
+            }
+        }
+        if answered.is_empty() {
+            return Err(last_error.unwrap_or(FetchError::PriceMissing));
+        }
+        let mut prices = PriceBatch::new();
+        for ticker in tickers {
+            let mut quote: Option<Quote> = None;
+            let mut sources = Vec::new();
+            let mut error = None;
+            for (name, batch) in &mut answered {
+                match batch.remove(ticker) {
+                    Some(Ok(answer)) => {
+                        sources.push((name.to_string(), answer.price));
+                        quote.get_or_insert(answer);
+                    }
+                    Some(Err(e)) => {
+                        tracing::debug!("{} has no {} price: {}", name, ticker, e);
+                        error.get_or_insert(e);
+                    }
+                    None => {}
+                }
+            }
+            let result = match (quote, self.method.combine(&sources.iter().map(|(_, price)| *price).collect::<Vec<_>>())) {
+                (Some(quote), Some(price)) => Ok(Quote { price, sources, ..quote }),
+                _ => Err(error.unwrap_or(FetchError::PriceMissing)),
+            };
+            prices.insert(ticker.clone(), result);
+        }
+        Ok(prices)
+    }
+}
+
+/// Plays back prices from a file instead of fetching them, for trying out
+/// alert settings offline. Each line holds one price, optionally after a
+/// timestamp (`2024-05-01T12:00:00Z,65000`); blank lines and `#` comments
+/// are skipped. Every fetch takes the next line and gives that price to
+/// every coin.
+pub struct ReplayProvider {
+    prices: Mutex<VecDeque<f64>>,
+}
+
+impl ReplayProvider {
+    pub fn from_file(path: &Path) -> io::Result<Self> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    fn parse(contents: &str) -> io::Result<Self> {
+        let mut prices = VecDeque::new();
+        for (n, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let field = line.rsplit([',', ' ', '\t']).next().unwrap_or(line);
+            let price = field.parse::<f64>().map_err(|_| {
+                io::Error::new(io::ErrorKind::InvalidData, format!("line {}: '{}' has no valid price", n + 1, line))
+            })?;
+            prices.push_back(price);
+        }
+        if prices.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "the file holds no prices"));
+        }
+        Ok(ReplayProvider { prices: Mutex::new(prices) })
+    }
+
+    fn next_price(&self) -> Option<f64> {
+        self.prices.lock().unwrap().pop_front()
+    }
+}
+
+#[async_trait]
+impl PriceProvider for ReplayProvider {
+    async fn fetch(&self, _ticker: &str) -> Result<Quote, FetchError> {
+        self.next_price().map(Quote::price).ok_or(FetchError::PriceMissing)
+    }
+
+    async fn fetch_many(&self, tickers: &[String]) -> Result<PriceBatch, FetchError> {
+        let price = self.next_price();
+        Ok(tickers
+            .iter()
+            .map(|ticker| (ticker.clone(), price.map(Quote::price).ok_or(FetchError::PriceMissing)))
+            .collect())
+    }
+
+    fn exhausted(&self) -> bool {
+        self.prices.lock().unwrap().is_empty()
+    }
+}
+
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+/// The most added to a `Retry-After` wait, so clients limited together
+/// don't all come back in the same instant.
+const RETRY_AFTER_JITTER_MS: u64 = 1000;
+
+/// How long to wait before retry number `attempt + 1` after `error`. A
+/// rate-limited API that says when to come back is waited on for exactly
+/// that long, plus a little jitter; anything else backs off exponentially
+/// (1s, 2s, 4s, ...).
+fn retry_delay(error: &FetchError, attempt: u32) -> Duration {
+    match error.retry_after() {
+        Some(wait) => wait + Duration::from_millis(rand::random_range(0..=RETRY_AFTER_JITTER_MS)),
+        None => INITIAL_BACKOFF * 2u32.saturating_pow(attempt),
+    }
+}
+
+/// Calls `provider.fetch_many`, retrying retryable failures up to
+/// `max_retries` times, waiting as long as `retry_delay` says between tries.
+pub async fn fetch_with_retry(
+    provider: &dyn PriceProvider,
+    tickers: &[String],
+    max_retries: u32,
+) -> Result<PriceBatch, FetchError> {
+    let mut attempt = 0;
+    loop {
+        match provider.fetch_many(tickers).await {
+            Ok(prices) => {
+                if attempt > 0 {
+                    log_line(&format!("Fetch succeeded after {} retr{}.", attempt, if attempt == 1 { "y" } else { "ies" }));
+                }
+                return Ok(prices);
+            }
+            Err(e) if e.is_retryable() && attempt < max_retries => {
+                let backoff = retry_delay(&e, attempt);
+                attempt += 1;
+                log_warn(&format!(
+                    "Fetch failed: {}. Retry {} of {} in {:.1}s{}...",
+                    e,
+                    attempt,
+                    max_retries,
+                    backoff.as_secs_f64(),
+                    if e.retry_after().is_some() { ", as the API asked" } else { "" }
+                ));
+                sleep(backoff).await;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retries_wait_as_long_as_retry_after_says_or_back_off() {
+        let limited = FetchError::RateLimited { retry_after: Some(Duration::from_secs(30)) };
+        let wait = retry_delay(&limited, 3);
+        assert!(wait >= Duration::from_secs(30) && wait <= Duration::from_secs(31));
+        let unsaid = FetchError::RateLimited { retry_after: None };
+        assert_eq!(retry_delay(&unsaid, 0), Duration::from_secs(1));
+        assert_eq!(retry_delay(&unsaid, 2), Duration::from_secs(4));
+        assert_eq!(retry_delay(&FetchError::ServerError(StatusCode::BAD_GATEWAY), 1), Duration::from_secs(2));
+    }
+
+    #[test]
+    fn spreads_are_a_percent_of_the_lowest_source() {
+        let mut quote = Quote::price(100.0);
+        assert_eq!(quote.spread(), None);
+        quote.sources = vec![("coingecko".to_string(), 101.0)];
+        assert_eq!(quote.spread(), None);
+        quote.sources.push(("binance".to_string(), 100.0));
+        quote.sources.push(("kraken".to_string(), 102.0));
+        assert!((quote.spread().unwrap() - 2.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn derived_quotes_are_as_old_as_their_oldest_part() {
+        let at = |secs| DateTime::from_timestamp(secs, 0);
+        let (mut old, mut new) = (Quote::price(1.0), Quote::price(2.0));
+        (old.updated_at, new.updated_at) = (at(1_700_000_000), at(1_700_000_060));
+        assert_eq!(Quote::oldest_update([&new, &Quote::price(3.0), &old]), old.updated_at);
+        assert_eq!(Quote::oldest_update([&Quote::price(3.0)]), None);
+    }
+
+    #[test]
+    fn replay_files_accept_optional_timestamps() {
+        let replay = ReplayProvider::parse("# btc\n65000\n\n2024-05-01T12:00:00Z,66000.5\n2024-05-01 12:01:00 64000\n").unwrap();
+        assert_eq!(replay.next_price(), Some(65000.0));
+        assert_eq!(replay.next_price(), Some(66000.5));
+        assert_eq!(replay.next_price(), Some(64000.0));
+        assert!(replay.exhausted());
+        assert!(ReplayProvider::parse("65000\nabc\n").is_err());
+    }
+
+    #[test]
+    fn contract_addresses_are_validated_and_normalized() {
+        let uni = "0x1F9840a85d5aF5bf1D1762F925BDADdC4201F984";
+        assert_eq!(parse_contract("ethereum", uni).unwrap(), uni.to_lowercase());
+        assert_eq!(contract_label(&uni.to_lowercase()), "0x1f98...f984");
+        assert!(parse_contract("ethereum", "0x1234").is_err());
+        assert!(parse_contract("polygon-pos", "1f9840a85d5af5bf1d1762f925bdaddc4201f984").is_err());
+        assert!(parse_contract("solana", "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v").is_ok());
+        assert!(parse_contract("solana", uni).is_err());
+    }
+
+    #[test]
+    fn binance_symbols_use_usdt_for_usd() {
+        let usd = BinanceProvider::new(Client::new(), "usd", Duration::ZERO);
+        assert_eq!(usd.symbol("bitcoin").as_deref(), Some("BTCUSDT"));
+        let eur = BinanceProvider::new(Client::new(), "eur", Duration::ZERO);
+        assert_eq!(eur.symbol("ethereum").as_deref(), Some("ETHEUR"));
+        assert_eq!(eur.symbol("some-obscure-token"), None);
+    }
+
+    struct FailingProvider;
+
+    #[async_trait]
+    impl PriceProvider for FailingProvider {
+        async fn fetch(&self, _ticker: &str) -> Result<Quote, FetchError> {
+            Err(FetchError::ParseError)
+        }
+
+        async fn fetch_many(&self, _tickers: &[String]) -> Result<PriceBatch, FetchError> {
+            Err(FetchError::Timeout)
+        }
+    }
+
+    /// Times out for `down-coin` and prices everything else at 1.
+    struct PartlyDownProvider;
+
+    #[async_trait]
+    impl PriceProvider for PartlyDownProvider {
+        async fn fetch(&self, ticker: &str) -> Result<Quote, FetchError> {
+            if ticker == "down-coin" {
+                Err(FetchError::Timeout)
+            } else {
+                Ok(Quote::price(1.0))
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn one_failed_coin_does_not_fail_the_batch() {
+        let tickers = ["bitcoin".to_string(), "down-coin".to_string()];
+        let prices = PartlyDownProvider.fetch_many(&tickers).await.unwrap();
+        assert_eq!(prices["bitcoin"].as_ref().unwrap().price, 1.0);
+        assert!(matches!(prices["down-coin"], Err(FetchError::Timeout)));
+        assert!(PartlyDownProvider.fetch_many(&["down-coin".to_string()]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn fallback_tries_the_next_provider() {
+        let replay = ReplayProvider::parse("65000\n").unwrap();
+        let fallback = FallbackProvider::new(vec![("failing", Box::new(FailingProvider)), ("replay", Box::new(replay))]);
+        let prices = fallback.fetch_many(&["bitcoin".to_string()]).await.unwrap();
+        let quote = prices["bitcoin"].as_ref().unwrap();
+        assert_eq!((quote.price, quote.served_by.as_deref()), (65000.0, Some("replay")));
+
+        let fallback = FallbackProvider::new(vec![("failing", Box::new(FailingProvider))]);
+        assert!(fallback.fetch_many(&["bitcoin".to_string()]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn aggregates_leave_out_failed_providers() {
+        let providers: Vec<(&'static str, Box<dyn PriceProvider>)> = vec![
+            ("low", Box::new(ReplayProvider::parse("100\n").unwrap())),
+            ("failing", Box::new(FailingProvider)),
+            ("high", Box::new(ReplayProvider::parse("130\n").unwrap())),
+            ("one", Box::new(PartlyDownProvider)),
+        ];
+        let median = AggregateProvider::new(providers, Aggregate::Median);
+        let prices = median.fetch_many(&["bitcoin".to_string()]).await.unwrap();
+        let quote = prices["bitcoin"].as_ref().unwrap();
+        assert_eq!(quote.price, 100.0);
+        let sources: Vec<&str> = quote.sources.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(sources, ["low", "high", "one"]);
+        assert_eq!(Aggregate::Mean.combine(&[100.0, 130.0, 1.0]), Some(77.0));
+        assert_eq!(Aggregate::Median.combine(&[4.0, 1.0, 3.0, 2.0]), Some(2.5));
+        assert_eq!(Aggregate::Median.combine(&[]), None);
+    }
+}
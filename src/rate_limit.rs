@@ -0,0 +1,156 @@
+use crate::output::log_line;
+use std::collections::VecDeque;
+use std::sync::OnceLock;
+use tokio::sync::{Mutex, Semaphore, SemaphorePermit};
+use tokio::time::{sleep_until, Duration, Instant};
+
+/// The `--max-requests-per-minute` budget, shared by every limiter.
+static BUDGET: OnceLock<RequestBudget> = OnceLock::new();
+
+/// Caps all outbound API requests at `max` per minute; 0 leaves them uncapped.
+/// Only the first call has any effect.
+pub fn set_requests_per_minute(max: usize) {
+    if max > 0 {
+        let _ = BUDGET.set(RequestBudget::new(max, Duration::from_secs(60)));
+    }
+}
+
+/// The `--max-concurrent-fetches` slots, shared by every limiter.
+static SLOTS: OnceLock<Semaphore> = OnceLock::new();
+
+/// The most `--max-concurrent-fetches` allows; far more connections than
+/// any API lets one client hold.
+pub const MAX_CONCURRENT_FETCHES: u64 = 1024;
+
+/// Lets at most `max` requests be in flight at once. Only the first call
+/// has any effect.
+pub fn set_max_concurrent_fetches(max: usize) {
+    let _ = SLOTS.set(Semaphore::new(max.min(Semaphore::MAX_PERMITS)));
+}
+
+/// A request's place among the `--max-concurrent-fetches`; hold it until
+/// the response has been read.
+pub type FetchSlot = Option<SemaphorePermit<'static>>;
+
+/// Spaces out outbound requests so there is at least `min_gap` between any
+/// two, however the monitor's own timing lines up. Every limiter also draws
+/// on the global per-minute budget.
+pub struct RateLimiter {
+    min_gap: Duration,
+    last_call: Mutex<Option<Instant>>,
+}
+
+impl RateLimiter {
+    pub fn new(min_gap: Duration) -> Self {
+        RateLimiter { min_gap, last_call: Mutex::new(None) }
+    }
+
+    /// Waits until the next request may go out. Concurrent callers queue up
+    /// for a free slot, then are let through one gap apart, so the slots
+    /// bound how many requests are open at once while the gap and the
+    /// per-minute budget bound how often one starts. A caller waiting on the
+    /// gap or the budget already holds its slot.
+    pub async fn wait(&self) -> FetchSlot {
+        // The semaphore is never closed, so acquiring only fails if it is.
+        let slot = match SLOTS.get() {
+            Some(slots) => slots.acquire().await.ok(),
+            None => None,
+        };
+        let mut last_call = self.last_call.lock().await;
+        if let Some(last) = *last_call {
+            sleep_until(last + self.min_gap).await;
+        }
+        if let Some(budget) = BUDGET.get() {
+            budget.take().await;
+        }
+        *last_call = Some(Instant::now());
+        slot
+    }
+}
+
+/// A sliding-window cap: at most `max` requests in any `window`. Retries
+/// count like any other request, so a burst of them can't blow through it.
+pub struct RequestBudget {
+    max: usize,
+    window: Duration,
+    sent: Mutex<VecDeque<Instant>>,
+}
+
+impl RequestBudget {
+    pub fn new(max: usize, window: Duration) -> Self {
+        RequestBudget { max, window, sent: Mutex::new(VecDeque::with_capacity(max)) }
+    }
+
+    /// Waits until a request fits in the window, then counts it.
+    pub async fn take(&self) {
+        let mut sent = self.sent.lock().await;
+        loop {
+            let now = Instant::now();
+            while sent.front().is_some_and(|&at| now.duration_since(at) >= self.window) {
+                sent.pop_front();
+            }
+            let Some(&oldest) = sent.front().filter(|_| sent.len() >= self.max) else {
+                sent.push_back(now);
+                return;
+            };
+            let free_at = oldest + self.window;
+            log_line(&format!(
+                "Used the budget of {} requests per minute; waiting {}s.",
+                self.max,
+                (free_at - now).as_secs_f64().ceil()
+            ));
+            sleep_until(free_at).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn calls_are_spaced_by_the_minimum_gap() {
+        let limiter = RateLimiter::new(Duration::from_millis(50));
+        let start = Instant::now();
+        for _ in 0..3 {
+            limiter.wait().await;
+        }
+        // The first call goes straight through; the next two each wait a gap.
+        assert!(start.elapsed() >= Duration::from_millis(100));
+    }
+
+    #[tokio::test]
+    async fn no_more_callers_than_the_slots_are_let_through_at_once() {
+        set_max_concurrent_fetches(3);
+        let limiter = Arc::new(RateLimiter::new(Duration::ZERO));
+        let (holding, peak) = (Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0)));
+        let callers: Vec<_> = (0..8)
+            .map(|_| {
+                let (limiter, holding, peak) = (limiter.clone(), holding.clone(), peak.clone());
+                tokio::spawn(async move {
+                    let _slot = limiter.wait().await;
+                    peak.fetch_max(holding.fetch_add(1, Ordering::SeqCst) + 1, Ordering::SeqCst);
+                    tokio::time::sleep(Duration::from_millis(20)).await;
+                    holding.fetch_sub(1, Ordering::SeqCst);
+                })
+            })
+            .collect();
+        for caller in callers {
+            caller.await.unwrap();
+        }
+        assert!((1..=3).contains(&peak.load(Ordering::SeqCst)));
+    }
+
+    #[tokio::test]
+    async fn the_budget_holds_calls_until_the_window_slides() {
+        let budget = RequestBudget::new(2, Duration::from_millis(100));
+        let start = Instant::now();
+        budget.take().await;
+        budget.take().await;
+        assert!(start.elapsed() < Duration::from_millis(50));
+        budget.take().await;
+        assert!(start.elapsed() >= Duration::from_millis(100));
+    }
+}
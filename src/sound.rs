@@ -0,0 +1,45 @@
+use crate::output;
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+/// Rings the terminal bell. In JSON mode it goes to stderr so stdout stays
+/// pure NDJSON.
+pub fn ring_bell() {
+    // A failed write isn't worth reporting; the alert line was already printed.
+    if output::is_json() {
+        let _ = io::stderr().write_all(b"\x07");
+    } else {
+        let mut stdout = io::stdout();
+        let _ = stdout.write_all(b"\x07").and_then(|_| stdout.flush());
+    }
+}
+
+/// Plays `path` on the default audio device in the background. Failures
+/// (no audio device, unreadable or non-WAV file) are only logged.
+#[cfg(feature = "sound")]
+pub fn play(path: PathBuf) {
+    tokio::task::spawn_blocking(move || {
+        if let Err(e) = play_blocking(&path) {
+            output::log_warn(&format!("Failed to play {}: {}", path.display(), e));
+        }
+    });
+}
+
+#[cfg(feature = "sound")]
+fn play_blocking(path: &std::path::Path) -> Result<(), Box<dyn std::error::Error>> {
+    let file = io::BufReader::new(std::fs::File::open(path)?);
+    let (_stream, handle) = rodio::OutputStream::try_default()?;
+    let sink = handle.play_once(file)?;
+    sink.sleep_until_end();
+    Ok(())
+}
+
+/// Without the `sound` feature there is no audio backend; `main` refuses
+/// `--sound` up front, so this is never reached.
+#[cfg(not(feature = "sound"))]
+pub fn play(path: PathBuf) {
+    output::log_warn(&format!("Can't play {}: built without sound support.", path.display()));
+}
+
+/// Whether `--sound` can be used in this build.
+pub const SUPPORTED: bool = cfg!(feature = "sound");
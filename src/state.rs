@@ -0,0 +1,48 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Baseline prices that survive restarts, keyed by coin id.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct State {
+    baselines: HashMap<String, Baseline>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Baseline {
+    pub vs_currency: String,
+    pub price: f64,
+    pub set_at: DateTime<Utc>,
+}
+
+impl State {
+    /// Reads the state file; a missing file is an empty state.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(contents) => serde_json::from_str(&contents).map_err(io::Error::other),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(State::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, serde_json::to_string_pretty(self)?)
+    }
+
+    /// The saved baseline for `ticker`, if it was recorded in the same currency.
+    pub fn baseline(&self, ticker: &str, vs_currency: &str) -> Option<&Baseline> {
+        self.baselines.get(ticker).filter(|b| b.vs_currency == vs_currency)
+    }
+
+    /// Records a baseline, returning whether anything changed.
+    pub fn set_baseline(&mut self, ticker: &str, baseline: Baseline) -> bool {
+        if self.baselines.get(ticker) == Some(&baseline) {
+            return false;
+        }
+        self.baselines.insert(ticker.to_string(), baseline);
+        true
+    }
+}
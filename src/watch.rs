@@ -0,0 +1,646 @@
+use crate::alert::{
+    meets_threshold, percent_change, should_alert, trigger_levels, AlertLevel, AlertType, Crossover, Direction,
+    PercentBaseline, PriceWindow, SmaCrossover, TrailingMode,
+};
+use crate::format::{format_elapsed, NumberFormat};
+use crate::provider::Quote;
+use chrono::{DateTime, Utc};
+use tokio::time::{Duration, Instant};
+
+/// A coin's alert state from check to check: its baseline and threshold,
+/// the session's low, high and average, and whatever an alert type
+/// remembers between checks (a level already crossed, recent prices).
+/// Built with [`Watch::new`] and the `with_*` methods, then handed to
+/// [`evaluate_alert`] with each new quote.
+pub struct Watch {
+    ticker: String,
+    alert_type: AlertType,
+    threshold: f64,
+    /// Drop that triggers a $ change alert; the same as `threshold` unless set separately.
+    down_threshold: f64,
+    initial_price: f64,
+    baseline_set_at: DateTime<Utc>,
+    min_price: f64,
+    max_price: f64,
+    avg_price: f64,
+    price_count: u64,
+    /// Price seen on the previous check.
+    last_price: f64,
+    /// Whether the price was past the alert level (target or trailing level)
+    /// on the last check, so these alerts fire only when the level is crossed.
+    level_breached: bool,
+    /// The end of the 24h range, `"high"` or `"low"`, that the price was
+    /// near on the last check, for range alerts.
+    range_side: Option<&'static str>,
+    /// The `(low, high)` range for band alerts.
+    band: Option<(f64, f64)>,
+    /// Recent prices, kept only for flat-price alerts.
+    flat_window: Option<PriceWindow>,
+    /// Moving averages, kept only for SMA crossover alerts.
+    sma: Option<SmaCrossover>,
+    /// Layered thresholds, each alerting once, when there are several;
+    /// `threshold` is then the lowest of them.
+    levels: Vec<AlertLevel>,
+}
+
+/// Which crossings a watch had made, from [`Watch::crossings`], to put
+/// back with [`Watch::restore_crossings`] when an alert is held back and
+/// the crossing should count again on a later check.
+#[derive(Clone)]
+pub struct Crossings {
+    level_breached: bool,
+    range_side: Option<&'static str>,
+    triggered: Vec<bool>,
+}
+
+impl Watch {
+    /// Starts watching `ticker` from `initial_price`, which is both the
+    /// baseline and the start of the session's low, high and average.
+    pub fn new(ticker: String, alert_type: AlertType, threshold: f64, initial_price: f64) -> Self {
+        Watch {
+            ticker,
+            alert_type,
+            threshold,
+            down_threshold: threshold,
+            initial_price,
+            baseline_set_at: Utc::now(),
+            min_price: initial_price,
+            max_price: initial_price,
+            avg_price: initial_price,
+            price_count: 1,
+            last_price: initial_price,
+            level_breached: false,
+            range_side: None,
+            band: None,
+            flat_window: None,
+            sma: None,
+            levels: Vec::new(),
+        }
+    }
+
+    /// A separate drop for $ change alerts.
+    pub fn with_down_threshold(mut self, down_threshold: f64) -> Self {
+        self.down_threshold = down_threshold;
+        self
+    }
+
+    /// The `(low, high)` range a band alert fires on leaving.
+    pub fn with_band(mut self, band: (f64, f64)) -> Self {
+        self.band = Some(band);
+        self
+    }
+
+    /// Several thresholds for a $ or % change alert, each alerting once.
+    /// A single one is just the threshold.
+    pub fn with_levels(mut self, levels: &[f64]) -> Self {
+        if levels.len() > 1 {
+            self.levels = levels.iter().copied().map(AlertLevel::new).collect();
+        }
+        self
+    }
+
+    /// A baseline other than the starting price, such as a saved one, set
+    /// at `set_at`. The session's low, high and average still start from
+    /// the starting price.
+    pub fn with_baseline(mut self, price: f64, set_at: DateTime<Utc>) -> Self {
+        self.initial_price = price;
+        self.baseline_set_at = set_at;
+        self
+    }
+
+    /// Whether the price already starts past the alert level, so that a
+    /// level already passed alerts only once crossed again.
+    pub fn with_level_breached(mut self, breached: bool) -> Self {
+        self.level_breached = breached;
+        self
+    }
+
+    /// The span a flat-price alert needs the price to hold steady over.
+    pub fn with_flat_window(mut self, span: Duration) -> Self {
+        let mut window = PriceWindow::new(span);
+        window.push(Instant::now(), self.last_price);
+        self.flat_window = Some(window);
+        self
+    }
+
+    /// The short and long moving averages an SMA crossover alert compares,
+    /// filled with `seed`, oldest first.
+    pub fn with_sma(mut self, short: usize, long: usize, seed: &[f64]) -> Self {
+        let mut sma = SmaCrossover::new(short, long);
+        seed.iter().for_each(|&p| {
+            sma.push(p);
+        });
+        self.sma = Some(sma);
+        self
+    }
+
+    pub fn ticker(&self) -> &str {
+        &self.ticker
+    }
+
+    pub fn alert_type(&self) -> AlertType {
+        self.alert_type
+    }
+
+    pub fn threshold(&self) -> f64 {
+        self.threshold
+    }
+
+    pub fn down_threshold(&self) -> f64 {
+        self.down_threshold
+    }
+
+    /// The baseline changes are measured from.
+    pub fn initial_price(&self) -> f64 {
+        self.initial_price
+    }
+
+    pub fn baseline_set_at(&self) -> DateTime<Utc> {
+        self.baseline_set_at
+    }
+
+    pub fn min_price(&self) -> f64 {
+        self.min_price
+    }
+
+    pub fn max_price(&self) -> f64 {
+        self.max_price
+    }
+
+    pub fn avg_price(&self) -> f64 {
+        self.avg_price
+    }
+
+    /// The price the previous check ended with.
+    pub fn last_price(&self) -> f64 {
+        self.last_price
+    }
+
+    /// Whether the price was past the alert level on the last check.
+    pub fn level_breached(&self) -> bool {
+        self.level_breached
+    }
+
+    /// The end of the 24h range the price was near on the last check, for range alerts.
+    pub fn range_side(&self) -> Option<&'static str> {
+        self.range_side
+    }
+
+    pub fn band(&self) -> Option<(f64, f64)> {
+        self.band
+    }
+
+    pub fn levels(&self) -> &[AlertLevel] {
+        &self.levels
+    }
+
+    /// Clears the crossings left by fired alerts, so the next qualifying
+    /// check alerts again. Returns whether anything was tripped.
+    pub fn rearm(&mut self) -> bool {
+        let tripped = self.level_breached || self.range_side.is_some() || self.levels.iter().any(|level| level.triggered);
+        self.level_breached = false;
+        self.range_side = None;
+        self.levels.iter_mut().for_each(|level| level.triggered = false);
+        tripped
+    }
+
+    /// The crossings made so far, to put back with `restore_crossings`.
+    pub fn crossings(&self) -> Crossings {
+        Crossings {
+            level_breached: self.level_breached,
+            range_side: self.range_side,
+            triggered: self.levels.iter().map(|level| level.triggered).collect(),
+        }
+    }
+
+    /// Puts back crossings taken with `crossings`, so ones made since count again.
+    pub fn restore_crossings(&mut self, crossings: Crossings) {
+        self.level_breached = crossings.level_breached;
+        self.range_side = crossings.range_side;
+        self.levels.iter_mut().zip(crossings.triggered).for_each(|(level, triggered)| level.triggered = triggered);
+    }
+
+    /// Records whether the alert level is breached now and returns true only
+    /// on the check where it first became breached.
+    fn cross(&mut self, breached: bool) -> bool {
+        let crossed = breached && !self.level_breached;
+        self.level_breached = breached;
+        crossed
+    }
+
+    /// The threshold the next alert fires at: the lowest level that hasn't
+    /// triggered yet, or the single threshold.
+    pub fn next_threshold(&self) -> f64 {
+        self.levels.iter().find(|level| !level.triggered).map_or(self.threshold, |level| level.value)
+    }
+
+    /// Adds `price` to the session's low, high and average. Call it with
+    /// each new price before `evaluate_alert`.
+    pub fn record_price(&mut self, price: f64) {
+        self.min_price = self.min_price.min(price);
+        self.max_price = self.max_price.max(price);
+        self.price_count += 1;
+        self.avg_price += (price - self.avg_price) / self.price_count as f64;
+    }
+
+    /// Makes `price` the previous price for the next check. Call it once
+    /// a check is done with the price.
+    pub fn end_check(&mut self, price: f64) {
+        self.last_price = price;
+    }
+
+    /// Measures changes from `price` from now on, with every level armed again.
+    pub fn reset_baseline(&mut self, price: f64) {
+        self.initial_price = price;
+        self.baseline_set_at = Utc::now();
+        self.levels.iter_mut().for_each(|level| level.triggered = false);
+    }
+}
+
+/// The settings that decide whether a check alerts, shared by every watch
+/// in a session.
+pub struct AlertRules {
+    /// For target, volume and market-cap alerts, which side of the threshold alerts.
+    pub direction: Direction,
+    pub percent_baseline: PercentBaseline,
+    pub trailing: TrailingMode,
+    /// How long a flat-price alert needs the price to hold steady.
+    pub flat_window: Duration,
+    pub vs_currency: String,
+    /// Whether a change must pass its threshold (`>`) rather than reach it (`>=`).
+    pub strict_threshold: bool,
+    /// How far short of a target still reaches it; unset means half a `price_step`.
+    pub target_tolerance: Option<f64>,
+    /// How prices and percentages in alert messages are written.
+    pub format: NumberFormat,
+}
+
+impl AlertRules {
+    /// How far short of `target` a price may be and still reach it: the
+    /// `target_tolerance`, or half the smallest step prices around the
+    /// target are shown in, so that $67,999.996 reaches a $68,000 target
+    /// but $67,999.99 doesn't.
+    pub fn tolerance_at(&self, target: f64) -> f64 {
+        self.target_tolerance.unwrap_or_else(|| self.format.price_step(target, &self.vs_currency) / 2.0)
+    }
+}
+
+/// Returns the alert message if `quote` meets the watch's alert condition.
+pub fn evaluate_alert(watch: &mut Watch, quote: &Quote, rules: &AlertRules) -> Option<String> {
+    let fmt = |value: f64| rules.format.price(value, &rules.vs_currency);
+    let price = quote.price;
+    let price_change = price - watch.initial_price;
+    let strict = rules.strict_threshold;
+
+    match watch.alert_type {
+        AlertType::Dollar => {
+            let (moved, limit) = if price_change < 0.0 {
+                ("dropped", watch.down_threshold)
+            } else {
+                ("rose", watch.threshold)
+            };
+            // A separate --down-threshold keeps drops on a single level.
+            let separate_drop = price_change < 0.0 && watch.down_threshold != watch.threshold;
+            if !watch.levels.is_empty() && !separate_drop {
+                let level = trigger_levels(&mut watch.levels, price_change.abs(), strict)?;
+                return Some(format!(
+                    "Alert! {} price {} by {}, past the {} level. Current price: {}",
+                    watch.ticker, moved, fmt(price_change.abs()), fmt(level), fmt(price)
+                ));
+            }
+            should_alert(AlertType::Dollar, watch.initial_price, price, limit, strict).map(|alert| {
+                format!(
+                    "Alert! {} price {} by {}. Current price: {}",
+                    watch.ticker, alert.verb(), fmt(alert.moved.abs()), fmt(price)
+                )
+            })
+        }
+        AlertType::Percent => match rules.percent_baseline {
+            PercentBaseline::Start if !watch.levels.is_empty() => {
+                let percent_change = percent_change(watch.initial_price, price)?;
+                let level = trigger_levels(&mut watch.levels, percent_change.abs(), strict)?;
+                Some(format!(
+                    "Alert! {} price changed by {}, past the {}% level. Current price: {}",
+                    watch.ticker, rules.format.pct(percent_change), level, fmt(price)
+                ))
+            }
+            PercentBaseline::Start => {
+                should_alert(AlertType::Percent, watch.initial_price, price, watch.threshold, strict).map(|alert| {
+                    format!(
+                        "Alert! {} price changed by {}. Current price: {}",
+                        watch.ticker, rules.format.pct(alert.moved), fmt(price)
+                    )
+                })
+            }
+            PercentBaseline::Previous => {
+                should_alert(AlertType::Percent, watch.last_price, price, watch.threshold, strict).map(|alert| {
+                    format!(
+                        "Alert! {} price moved {} since the last check. Current price: {}",
+                        watch.ticker, rules.format.pct(alert.moved), fmt(price)
+                    )
+                })
+            }
+        },
+        AlertType::Target => {
+            let tolerance = rules.tolerance_at(watch.threshold);
+            let reached = rules.direction.reached(price, watch.threshold, tolerance);
+            // Crossed when short of the target as far as alerts go (the last
+            // check, or a watch started short of it or for --once, a rearm, a
+            // crossing still being confirmed or held back by the cooldown, or
+            // one made on a day off --active-days), however far past it the
+            // price jumped.
+            watch.cross(reached).then(|| {
+                format!(
+                    "Alert! {} price crossed {} {}. Current price: {}",
+                    watch.ticker, rules.direction, fmt(watch.threshold), fmt(price)
+                )
+            })
+        }
+        AlertType::Trailing => {
+            // The session high and low only ever ratchet outwards, so the
+            // level follows the price one way and holds still the other.
+            let level = rules.trailing.level(watch.min_price, watch.max_price, watch.threshold);
+            let breached = rules.trailing.is_breached(price, level);
+            watch.cross(breached).then(|| match rules.trailing {
+                TrailingMode::TrailingStop => format!(
+                    "Alert! {} fell {}% from its session high of {}. Current price: {}",
+                    watch.ticker,
+                    rules.format.pct_digits((watch.max_price - price) / watch.max_price * 100.0),
+                    fmt(watch.max_price),
+                    fmt(price)
+                ),
+                TrailingMode::TrailingBuy => format!(
+                    "Alert! {} rose {}% from its session low of {}. Current price: {}",
+                    watch.ticker,
+                    rules.format.pct_digits((price - watch.min_price) / watch.min_price * 100.0),
+                    fmt(watch.min_price),
+                    fmt(price)
+                ),
+            })
+        }
+        AlertType::Change24h => {
+            // The 24h change stays high for hours, so like a target this
+            // fires when it first passes the threshold rather than every check.
+            let change = quote.change_24h?;
+            let breached = meets_threshold(change, watch.threshold, strict);
+            watch.cross(breached).then(|| {
+                format!(
+                    "Alert! {} is {} over the last 24 hours. Current price: {}",
+                    watch.ticker, rules.format.pct(change), fmt(price)
+                )
+            })
+        }
+        AlertType::Range24h => {
+            let (side, distance) = range_distance(quote)?;
+            // Going straight from near one end to near the other alerts again.
+            let near = (distance <= watch.threshold).then_some(side);
+            let was_near = std::mem::replace(&mut watch.range_side, near);
+            (near.is_some() && near != was_near).then(|| {
+                format!(
+                    "Alert! {} is within {}% of its 24h {} ({} - {}). Current price: {}",
+                    watch.ticker,
+                    rules.format.pct_digits(distance.max(0.0)),
+                    side,
+                    quote.low_24h.map_or_else(String::new, fmt),
+                    quote.high_24h.map_or_else(String::new, fmt),
+                    fmt(price)
+                )
+            })
+        }
+        AlertType::Flat => {
+            let window = watch.flat_window.as_mut()?;
+            window.push(Instant::now(), price);
+            let (low, high) = window.range()?;
+            let spread = (high - low) / low * 100.0;
+            let breached = spread <= watch.threshold;
+            watch.cross(breached).then(|| {
+                format!(
+                    "Alert! {} has stayed between {} and {} ({}% spread) for {}. Current price: {}",
+                    watch.ticker,
+                    fmt(low),
+                    fmt(high),
+                    rules.format.pct_digits(spread),
+                    format_elapsed(rules.flat_window),
+                    fmt(price)
+                )
+            })
+        }
+        AlertType::Sma => {
+            let sma = watch.sma.as_mut()?;
+            let cross = sma.push(price)?;
+            let (short, long) = sma.averages()?;
+            Some(format!(
+                "Alert! {} {}: the short average ({}) crossed {} the long average ({}). Current price: {}",
+                watch.ticker,
+                cross,
+                fmt(short),
+                if cross == Crossover::Golden { "above" } else { "below" },
+                fmt(long),
+                fmt(price)
+            ))
+        }
+        AlertType::Band => {
+            let (low, high) = watch.band?;
+            let exit = if price < low {
+                Some("fell below")
+            } else if price > high {
+                Some("rose above")
+            } else {
+                None
+            };
+            watch.cross(exit.is_some()).then(|| {
+                format!(
+                    "Alert! {} price {} the {} - {} band. Current price: {}",
+                    watch.ticker,
+                    exit.unwrap_or_default(),
+                    fmt(low),
+                    fmt(high),
+                    fmt(price)
+                )
+            })
+        }
+        AlertType::Volume | AlertType::MarketCap => {
+            let (label, value) = match watch.alert_type {
+                AlertType::Volume => ("24h volume", quote.volume_24h?),
+                _ => ("market cap", quote.market_cap?),
+            };
+            let breached = rules.direction.is_breached(value, watch.threshold);
+            watch.cross(breached).then(|| {
+                format!(
+                    "Alert! {} {} crossed {} {}. Now {}, price {}",
+                    watch.ticker,
+                    label,
+                    rules.direction,
+                    rules.format.amount(watch.threshold, &rules.vs_currency),
+                    rules.format.amount(value, &rules.vs_currency),
+                    fmt(price)
+                )
+            })
+        }
+    }
+}
+
+/// Which end of the 24h range the price is nearer, `"high"` or `"low"`, and
+/// how far from it as a percentage of that extreme. Past either end the
+/// distance is negative.
+pub fn range_distance(quote: &Quote) -> Option<(&'static str, f64)> {
+    let (high, low) = (quote.high_24h?, quote.low_24h?);
+    if !(low > 0.0 && low <= high) {
+        return None;
+    }
+    let from_high = (high - quote.price) / high * 100.0;
+    let from_low = (quote.price - low) / low * 100.0;
+    Some(if from_high <= from_low { ("high", from_high) } else { ("low", from_low) })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::format_pct;
+
+    fn rules() -> AlertRules {
+        AlertRules {
+            direction: Direction::Above,
+            percent_baseline: PercentBaseline::Start,
+            trailing: TrailingMode::TrailingStop,
+            flat_window: Duration::from_secs(600),
+            vs_currency: "usd".to_string(),
+            strict_threshold: false,
+            target_tolerance: None,
+            format: NumberFormat::PLAIN,
+        }
+    }
+
+    /// Checks each quote in turn the way the monitor does, and returns which ones alerted.
+    fn replay_quotes(mut watch: Watch, rules: &AlertRules, quotes: impl IntoIterator<Item = Quote>) -> Vec<bool> {
+        let mut check = |quote: Quote| {
+            watch.record_price(quote.price);
+            let fired = evaluate_alert(&mut watch, &quote, rules).is_some();
+            watch.end_check(quote.price);
+            fired
+        };
+        quotes.into_iter().map(&mut check).collect()
+    }
+
+    fn replay(watch: Watch, rules: &AlertRules, prices: &[f64]) -> Vec<bool> {
+        replay_quotes(watch, rules, prices.iter().map(|&price| Quote::price(price)))
+    }
+
+    #[test]
+    fn dollar_alert_fires_at_threshold() {
+        let watch = Watch::new("bitcoin".into(), AlertType::Dollar, 100.0, 1000.0);
+        assert_eq!(replay(watch, &rules(), &[1050.0, 1099.99, 1100.0, 900.0]), [false, false, true, true]);
+    }
+
+    #[test]
+    fn dollar_alert_can_use_separate_down_threshold() {
+        let watch = Watch::new("bitcoin".into(), AlertType::Dollar, 5000.0, 60000.0).with_down_threshold(2000.0);
+        assert_eq!(replay(watch, &rules(), &[63000.0, 58000.0, 64999.0, 65000.0]), [false, true, false, true]);
+    }
+
+    #[test]
+    fn percent_alert_fires_at_threshold() {
+        let watch = Watch::new("bitcoin".into(), AlertType::Percent, 5.0, 200.0);
+        assert_eq!(replay(watch, &rules(), &[205.0, 190.0, 212.0]), [false, true, true]);
+    }
+
+    #[test]
+    fn tiny_percent_moves_fire_and_show_as_nonzero() {
+        let watch = Watch::new("usd-coin".into(), AlertType::Percent, 0.05, 100.0);
+        // 100.05 is 0.04999...% up once in binary, and still alerts.
+        assert_eq!(replay(watch, &rules(), &[100.04, 100.05]), [false, true]);
+        assert_eq!(format_pct(percent_change(100.0, 100.05).unwrap()), "+0.05%");
+        assert_eq!(format_pct(percent_change(1.0, 1.00004).unwrap()), "+0.0040%");
+    }
+
+    #[test]
+    fn percent_alert_can_measure_from_previous_check() {
+        let rules = AlertRules { percent_baseline: PercentBaseline::Previous, ..rules() };
+        let watch = Watch::new("bitcoin".into(), AlertType::Percent, 2.0, 100.0);
+        // Each step is small, but the drift from the start passes 2%.
+        assert_eq!(replay(watch, &rules, &[101.0, 102.0, 103.0, 106.0]), [false, false, false, true]);
+    }
+
+    #[test]
+    fn target_alert_fires_only_on_crossing() {
+        let watch = Watch::new("bitcoin".into(), AlertType::Target, 70000.0, 65000.0);
+        let fired = replay(watch, &rules(), &[69000.0, 70500.0, 71000.0, 69500.0, 70000.0]);
+        assert_eq!(fired, [false, true, false, false, true]);
+    }
+
+    #[test]
+    fn target_alert_fires_on_jumps_past_the_target() {
+        let watch = Watch::new("bitcoin".into(), AlertType::Target, 70000.0, 65000.0);
+        // Never landing on the target: straight past it, back, past again.
+        let fired = replay(watch, &rules(), &[69999.0, 72500.0, 68000.0, 70000.01, 71000.0]);
+        assert_eq!(fired, [false, true, false, true, false]);
+
+        // Started past the target, as the startup check leaves it, the
+        // first check doesn't alert.
+        let watch = Watch::new("bitcoin".into(), AlertType::Target, 70000.0, 71000.0).with_level_breached(true);
+        assert_eq!(replay(watch, &rules(), &[71500.0, 69000.0, 70100.0]), [false, false, true]);
+
+        // A price that shows as the target reaches it; a tolerance can widen that.
+        let watch = Watch::new("bitcoin".into(), AlertType::Target, 70000.0, 65000.0);
+        assert_eq!(replay(watch, &rules(), &[69999.99, 69999.996]), [false, true]);
+        let rules = AlertRules { direction: Direction::Below, target_tolerance: Some(50.0), ..rules() };
+        let watch = Watch::new("bitcoin".into(), AlertType::Target, 60000.0, 65000.0);
+        assert_eq!(replay(watch, &rules, &[60100.0, 60040.0]), [false, true]);
+    }
+
+    #[test]
+    fn band_alert_fires_on_leaving_either_side() {
+        let watch = Watch::new("bitcoin".into(), AlertType::Band, 65000.0, 67000.0).with_band((65000.0, 70000.0));
+        let fired = replay(watch, &rules(), &[68000.0, 70500.0, 71000.0, 69000.0, 64000.0, 63000.0]);
+        assert_eq!(fired, [false, true, false, false, true, false]);
+    }
+
+    #[test]
+    fn trailing_stop_follows_the_session_high() {
+        let watch = Watch::new("bitcoin".into(), AlertType::Trailing, 10.0, 100.0);
+        // 95 is within 10% of the 100 start, but not of the later 120 peak.
+        let fired = replay(watch, &rules(), &[95.0, 120.0, 109.0, 107.0, 115.0, 100.0]);
+        assert_eq!(fired, [false, false, false, true, false, true]);
+    }
+
+    #[test]
+    fn trailing_buy_follows_the_session_low() {
+        let rules = AlertRules { trailing: TrailingMode::TrailingBuy, ..rules() };
+        let watch = Watch::new("bitcoin".into(), AlertType::Trailing, 10.0, 100.0);
+        // 105 is within 10% of the 100 start and 87 within 10% of the later 80 low.
+        let fired = replay(watch, &rules, &[105.0, 80.0, 87.0, 90.0, 85.0, 95.0]);
+        assert_eq!(fired, [false, false, false, true, false, true]);
+    }
+
+    #[test]
+    fn day_change_alert_fires_once_per_crossing() {
+        let watch = Watch::new("bitcoin".into(), AlertType::Change24h, 5.0, 100.0);
+        let changes = [Some(2.0), Some(-5.5), Some(-6.0), None, Some(1.0), Some(7.0)];
+        let quotes = changes.map(|change| Quote { change_24h: change, ..Quote::price(100.0) });
+        assert_eq!(replay_quotes(watch, &rules(), quotes), [false, true, false, false, false, true]);
+    }
+
+    #[test]
+    fn range_alert_fires_near_either_end_of_the_24h_range() {
+        let watch = Watch::new("bitcoin".into(), AlertType::Range24h, 2.0, 100.0);
+        let prices = [100.0, 108.5, 109.0, 100.0, 91.0, 89.0, 109.5];
+        let quotes = prices.map(|price| Quote { high_24h: Some(110.0), low_24h: Some(90.0), ..Quote::price(price) });
+        assert_eq!(replay_quotes(watch, &rules(), quotes), [false, true, false, false, true, false, true]);
+    }
+
+    #[test]
+    fn level_alerts_fire_once_per_crossing() {
+        let mut watch = Watch::new("bitcoin".into(), AlertType::Target, 110.0, 100.0);
+        let mut check = |price| evaluate_alert(&mut watch, &Quote::price(price), &rules());
+        assert_eq!(check(105.0), None);
+        assert_eq!(check(120.0).as_deref(), Some("Alert! bitcoin price crossed above $110.00. Current price: $120.00"));
+        assert_eq!(check(125.0), None);
+        assert_eq!(check(100.0), None);
+        assert!(check(111.0).is_some());
+
+        let mut watch = Watch::new("bitcoin".into(), AlertType::Band, 90.0, 100.0).with_band((90.0, 110.0));
+        let mut check = |price| evaluate_alert(&mut watch, &Quote::price(price), &rules()).is_some();
+        let fired = [105.0, 85.0, 80.0, 100.0, 112.0].map(&mut check);
+        assert_eq!(fired, [false, true, false, false, true]);
+    }
+}
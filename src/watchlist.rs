@@ -0,0 +1,115 @@
+use crate::alert::AlertType;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// One coin from a `--watchlist` file, with its own alert settings.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WatchlistEntry {
+    /// The ticker or coin id as written; resolved against the coin list later.
+    pub ticker: String,
+    pub alert_type: AlertType,
+    /// Unset only for SMA crossovers, which have no threshold.
+    pub threshold: Option<f64>,
+    /// For band alerts, the `LOW-HIGH` range written in the threshold column.
+    pub band: Option<(f64, f64)>,
+    /// Seconds between this coin's checks, from the optional fourth
+    /// column; unset means the global `--interval`.
+    pub interval: Option<u64>,
+    /// Custom alert text from the optional fifth column, which runs to the
+    /// end of the line and so may contain commas.
+    pub message: Option<String>,
+}
+
+/// Reads a watchlist: one `ticker,alert_type,threshold[,interval[,message]]`
+/// line per coin, the interval in seconds. Blank
+/// lines and `#` comments are ignored. Malformed lines are left out and
+/// described in the second list, as `line N: problem`, rather than failing
+/// the whole file.
+pub fn load(path: &Path) -> io::Result<(Vec<WatchlistEntry>, Vec<String>)> {
+    Ok(parse(&fs::read_to_string(path)?))
+}
+
+pub fn parse(contents: &str) -> (Vec<WatchlistEntry>, Vec<String>) {
+    let mut entries = Vec::new();
+    let mut problems = Vec::new();
+    for (i, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        match parse_line(line) {
+            Ok(entry) => entries.push(entry),
+            Err(e) => problems.push(format!("line {}: {}", i + 1, e)),
+        }
+    }
+    (entries, problems)
+}
+
+/// Reads one `ticker,alert_type,threshold[,interval[,message]]` line.
+pub fn parse_line(line: &str) -> Result<WatchlistEntry, String> {
+    let fields: Vec<&str> = line.splitn(5, ',').map(str::trim).collect();
+    let [ticker, alert_type, threshold, interval, message] = match fields.as_slice() {
+        [ticker, alert_type] => [*ticker, *alert_type, "", "", ""],
+        [ticker, alert_type, threshold] => [*ticker, *alert_type, *threshold, "", ""],
+        [ticker, alert_type, threshold, interval] => [*ticker, *alert_type, *threshold, *interval, ""],
+        [ticker, alert_type, threshold, interval, message] => [*ticker, *alert_type, *threshold, *interval, *message],
+        _ => return Err("expected ticker,alert_type,threshold[,interval[,message]] but found 1 field".to_string()),
+    };
+    if ticker.is_empty() {
+        return Err("the ticker is empty".to_string());
+    }
+    let alert_type: AlertType = alert_type.parse()?;
+    let interval = match interval {
+        "" => None,
+        secs => Some(
+            secs.parse::<u64>()
+                .ok()
+                .filter(|&secs| secs > 0)
+                .ok_or_else(|| format!("'{}' is not an interval in whole seconds", secs))?,
+        ),
+    };
+    let message = (!message.is_empty()).then(|| message.to_string());
+    let mut entry = WatchlistEntry { ticker: ticker.to_lowercase(), alert_type, threshold: None, band: None, interval, message };
+    match alert_type {
+        AlertType::Sma if threshold.is_empty() => {}
+        AlertType::Band => {
+            let band = threshold
+                .split_once('-')
+                .and_then(|(low, high)| Some((low.trim().parse::<f64>().ok()?, high.trim().parse::<f64>().ok()?)))
+                .filter(|&(low, high)| low > 0.0 && low < high)
+                .ok_or_else(|| format!("'{}' is not a band; expected LOW-HIGH, such as 65000-70000", threshold))?;
+            entry.threshold = Some(band.0);
+            entry.band = Some(band);
+        }
+        _ => match threshold.parse::<f64>() {
+            Ok(value) if value > 0.0 => entry.threshold = Some(value),
+            _ => return Err(format!("'{}' is not a threshold greater than zero", threshold)),
+        },
+    }
+    Ok(entry)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn malformed_lines_are_reported_and_skipped() {
+        let (entries, problems) = parse(
+            "# my coins\nbtc,dollar,1500,15\n\neth, 2, 5\nsol,percent\nada,band,0.4-0.6,,ADA left its range, at {price}\nxrp,sma,,300\ndoge,moon,1\nbtc,target,-5\nltc,dollar,5,soon\n",
+        );
+        let tickers: Vec<&str> = entries.iter().map(|e| e.ticker.as_str()).collect();
+        assert_eq!(tickers, ["btc", "eth", "ada", "xrp"]);
+        assert_eq!(entries[1].alert_type, AlertType::Percent);
+        assert_eq!(entries[1].threshold, Some(5.0));
+        assert_eq!(entries[2].band, Some((0.4, 0.6)));
+        assert_eq!(entries[3].threshold, None);
+        assert_eq!(entries.iter().map(|e| e.interval).collect::<Vec<_>>(), [Some(15), None, None, Some(300)]);
+        assert_eq!(entries[2].message.as_deref(), Some("ADA left its range, at {price}"));
+        assert_eq!(entries[0].message, None);
+        assert_eq!(problems.len(), 4);
+        assert!(problems[0].starts_with("line 5: "));
+        assert!(problems[1].starts_with("line 8: unknown alert type"));
+    }
+}